@@ -0,0 +1,4 @@
+// migrationのSQLは`sqlx::migrate!`でバイナリに埋め込むので、変更されたら再ビルドさせる
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE records (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    book_id INTEGER NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    start_page INTEGER NOT NULL,
+    end_page INTEGER NOT NULL,
+    registered_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    comment VARCHAR
+)
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX records_book_id_idx;
+DROP INDEX records_user_id_registered_at_idx;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+CREATE INDEX records_user_id_registered_at_idx ON records (user_id, registered_at);
+CREATE INDEX records_book_id_idx ON records (book_id);
@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP TABLE book_authors;
+DROP TABLE authors;
+
+ALTER TABLE books
+    DROP COLUMN isbn,
+    DROP COLUMN publisher,
+    DROP COLUMN page_count,
+    DROP COLUMN language,
+    DROP COLUMN published_date;
@@ -0,0 +1,21 @@
+-- Add up migration script here
+ALTER TABLE books
+    ADD COLUMN isbn VARCHAR(13) UNIQUE,
+    ADD COLUMN publisher VARCHAR,
+    ADD COLUMN page_count INTEGER,
+    ADD COLUMN language VARCHAR,
+    ADD COLUMN published_date DATE;
+
+CREATE TABLE authors (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR UNIQUE NOT NULL
+);
+
+CREATE TABLE book_authors (
+    book_id INTEGER NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    author_id INTEGER NOT NULL REFERENCES authors (id) ON DELETE CASCADE,
+    -- 著者の表示順
+    position INTEGER NOT NULL,
+    PRIMARY KEY (book_id, author_id)
+);
+CREATE INDEX book_authors_author_id_idx ON book_authors (author_id);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX books_created_at_id_idx;
+DROP INDEX books_title_id_idx;
+
+ALTER TABLE books
+    DROP COLUMN created_at;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE books
+    ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- 一覧のカーソルによるページングで、(並び替えのキー, id) の順に辿る
+CREATE INDEX books_title_id_idx ON books (title, id);
+CREATE INDEX books_created_at_id_idx ON books (created_at, id);
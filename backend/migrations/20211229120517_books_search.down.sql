@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX authors_name_trgm_idx;
+DROP INDEX books_title_trgm_idx;
+
+DROP EXTENSION IF EXISTS pg_trgm;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- ILIKEによる部分一致と、trigramの類似度による曖昧検索の両方に使う。
+-- 空白で区切られない日本語のタイトルも、部分文字列として検索できる。
+CREATE INDEX books_title_trgm_idx ON books USING gin (title gin_trgm_ops);
+CREATE INDEX authors_name_trgm_idx ON authors USING gin (name gin_trgm_ops);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE user_books;
+
+ALTER TABLE books
+    DROP COLUMN created_by;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- 本の情報は全ユーザで共有し、登録したユーザだけが編集・削除できる。
+-- 既存の本は登録者が分からないのでNULLのままにする。
+ALTER TABLE books
+    ADD COLUMN created_by INTEGER REFERENCES users (id) ON DELETE SET NULL;
+
+-- ユーザごとの本棚に入れた本
+CREATE TABLE user_books (
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    book_id INTEGER NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, book_id)
+);
+CREATE INDEX user_books_book_id_idx ON user_books (book_id);
@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- 1冊の本を1回読むこと。再読は別の行になる。
+CREATE TABLE readings (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    book_id INTEGER NOT NULL REFERENCES books (id) ON DELETE CASCADE,
+    status VARCHAR NOT NULL
+        CHECK (status IN ('want_to_read', 'reading', 'finished', 'abandoned')),
+    started_at TIMESTAMPTZ,
+    -- 読み終えた、または読むのをやめた日時
+    finished_at TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK (started_at IS NULL OR finished_at IS NULL OR started_at <= finished_at)
+);
+
+-- 読み終えていない読書は、ユーザと本の組ごとに一つだけ
+CREATE UNIQUE INDEX readings_open_idx ON readings (user_id, book_id)
+    WHERE status IN ('want_to_read', 'reading');
+CREATE INDEX readings_book_id_idx ON readings (book_id);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- 「2026年に24冊」「毎日30ページ」のような読書の目標
+CREATE TABLE goals (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    metric VARCHAR NOT NULL CHECK (metric IN ('books', 'pages')),
+    period VARCHAR NOT NULL CHECK (period IN ('year', 'month', 'day')),
+    target INTEGER NOT NULL CHECK (target > 0),
+    starts_on DATE NOT NULL,
+    -- 日付の区切りに使うIANAのタイムゾーン名
+    timezone VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- 同じ期間に同じものを数える目標は一つだけ
+CREATE UNIQUE INDEX goals_user_id_idx ON goals (user_id, metric, period, starts_on);
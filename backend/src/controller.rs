@@ -1,3 +1,13 @@
 pub mod book;
+pub mod goal;
+pub mod health;
+pub mod metrics;
 pub mod models;
+pub mod record;
+pub mod shelf;
+pub mod stats;
 pub mod user;
+pub mod well_known;
+
+#[cfg(test)]
+mod e2e;
@@ -1,77 +1,703 @@
-use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
 use serde_json::{json, Value};
 
-use crate::domain::entity::book::{BookEntity, BookEntityForCreation};
+use crate::controller::models::{BookQuery, BookSearchExtract};
+use crate::domain::entity::book::{BookEntity, BookEntityForCreation, BookError, BookPage};
+use crate::domain::repo_if::RepoError;
 use crate::domain::service::book::BookService;
-use crate::domain::service::user::UserId;
+use crate::domain::service::user::{ReadAccess, UserId};
+use crate::state::AppState;
 
 pub fn book_app() -> Router {
     Router::new()
         .route("/books", get(list_books).post(create_book))
+        .route("/books/search", get(search_books))
         .route(
             "/books/:id",
             get(get_book).put(update_book).delete(delete_book),
         )
+        .route("/library", get(list_library))
+        .route(
+            "/library/:id",
+            put(add_to_library).delete(remove_from_library),
+        )
         .route(
             "/protected",
-            get(|UserId(id): UserId| async move {
-                format!("Hello {}", id);
-            }),
+            get(|UserId(id): UserId| async move { format!("Hello {}", id) }),
         )
 }
 
-async fn list_books(book_service: BookService) -> Json<Value> {
-    let books: Vec<BookEntity> = book_service.list_books().await;
-    Json(json!({
-        "books": books,
-    }))
+fn page_json(page: BookPage) -> Json<Value> {
+    let mut body = json!({
+        "books": page.books,
+        "next": page.next.map(|cursor| cursor.encode()),
+    });
+    if let Some(total) = page.total {
+        body["total"] = json!(total);
+    }
+    Json(body)
+}
+
+async fn list_books(
+    book_service: BookService,
+    _: ReadAccess,
+    BookQuery(query): BookQuery,
+) -> Result<Json<Value>, RepoError> {
+    let page = book_service.list_books(query).await?;
+    Ok(page_json(page))
+}
+
+async fn search_books(
+    book_service: BookService,
+    _: ReadAccess,
+    BookSearchExtract(query): BookSearchExtract,
+) -> Result<Json<Value>, RepoError> {
+    let results = book_service.search_books(query).await?;
+    Ok(Json(json!({
+        "results": results,
+    })))
 }
 
 async fn get_book(
     book_service: BookService,
+    _: ReadAccess,
     Path(book_id): Path<u32>,
-) -> Result<Json<Value>, StatusCode> {
-    let book: Option<BookEntity> = book_service.get_book(book_id).await;
-    book.map(|book| {
-        Json(json!({
-            "book": book,
-        }))
-    })
-    .ok_or(StatusCode::NOT_FOUND)
+) -> Result<Json<Value>, RepoError> {
+    let book: BookEntity = book_service.get_book(book_id).await?;
+    Ok(Json(json!({
+        "book": book,
+    })))
 }
 
 async fn create_book(
     book_service: BookService,
+    UserId(user_id): UserId,
     Json(payload): Json<BookEntityForCreation>,
-) -> Result<Json<Value>, StatusCode> {
-    let book_id = book_service.create_book(payload).await;
-    book_id
-        .map(|book_id| {
-            Json(json!({
-                "book_id": book_id,
-            }))
-        })
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    AppState { metrics, .. }: AppState,
+) -> Result<Json<Value>, BookError> {
+    let book_id = book_service.create_book(user_id, payload).await?;
+    metrics.books_created.inc();
+    Ok(Json(json!({
+        "book_id": book_id,
+    })))
 }
 
 async fn update_book(
     book_service: BookService,
+    UserId(user_id): UserId,
     Path(book_id): Path<u32>,
     Json(payload): Json<BookEntityForCreation>,
-) -> StatusCode {
-    let result = book_service.update_book((book_id, payload).into()).await;
-    if result {
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
-    }
+) -> Result<StatusCode, BookError> {
+    book_service.update_book(user_id, book_id, payload).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn delete_book(
+    book_service: BookService,
+    UserId(user_id): UserId,
+    Path(book_id): Path<u32>,
+) -> Result<StatusCode, BookError> {
+    book_service.delete_book(user_id, book_id).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn list_library(
+    book_service: BookService,
+    UserId(user_id): UserId,
+    BookQuery(query): BookQuery,
+) -> Result<Json<Value>, RepoError> {
+    let page = book_service.list_library(user_id, query).await?;
+    Ok(page_json(page))
 }
 
-async fn delete_book(book_service: BookService, Path(book_id): Path<u32>) -> StatusCode {
-    let result = book_service.delete_book(book_id).await;
-    if result {
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
+async fn add_to_library(
+    book_service: BookService,
+    UserId(user_id): UserId,
+    Path(book_id): Path<u32>,
+) -> Result<StatusCode, RepoError> {
+    book_service.add_to_library(user_id, book_id).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn remove_from_library(
+    book_service: BookService,
+    UserId(user_id): UserId,
+    Path(book_id): Path<u32>,
+) -> Result<StatusCode, RepoError> {
+    book_service.remove_from_library(user_id, book_id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{self, Method, Request},
+        AddExtensionLayer,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::controller::record::record_app;
+    use crate::state::testing::{in_memory_with_users, json_request};
+
+    async fn test_app() -> (Router, Vec<String>) {
+        test_app_with(vec![]).await
+    }
+
+    /// 指定した環境変数で設定したアプリと、2人のユーザそれぞれのaccess tokenを返す
+    async fn test_app_with(vars: Vec<(&str, &str)>) -> (Router, Vec<String>) {
+        let settings = envy::from_iter(
+            vars.into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        )
+        .unwrap();
+        let (state, access_tokens) = in_memory_with_users(settings).await;
+
+        (
+            book_app()
+                .merge(record_app())
+                .layer(AddExtensionLayer::new(state)),
+            access_tokens,
+        )
+    }
+
+    async fn body_json(response: http::Response<axum::body::BoxBody>) -> Value {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_and_get_book() {
+        let (app, access_tokens) = test_app().await;
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t", "authors": ["a", "b"], "isbn": "4873115655"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let book_id = body_json(response).await["book_id"].as_u64().unwrap();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/books/{}", book_id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["book"]["authors"], json!(["a", "b"]));
+        assert_eq!(body["book"]["isbn"], "9784873115658");
+    }
+
+    #[tokio::test]
+    async fn create_book_with_duplicated_isbn() {
+        let (app, access_tokens) = test_app().await;
+        let book = json!({"title": "t", "isbn": "9784873115658"});
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                book.to_owned(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                book,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn create_invalid_book() {
+        let (app, access_tokens) = test_app().await;
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "", "pageCount": 0}),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = body_json(response).await;
+        assert_eq!(body["fields"][0]["field"], "title");
+        assert_eq!(body["fields"][1]["field"], "pageCount");
+    }
+
+    #[tokio::test]
+    async fn create_book_with_invalid_isbn_check_digit() {
+        let (app, access_tokens) = test_app().await;
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t", "isbn": "978-4-87311-565-9"}),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = body_json(response).await;
+        assert_eq!(body["fields"][0]["field"], "isbn");
+        assert_eq!(
+            body["fields"][0]["message"],
+            "ISBN check digit does not match"
+        );
+    }
+
+    #[tokio::test]
+    async fn update_nonexistent_book() {
+        let (app, access_tokens) = test_app().await;
+        let response = app
+            .oneshot(json_request(
+                Method::PUT,
+                "/books/1",
+                &access_tokens[0],
+                json!({"title": "t"}),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn get_json(app: Router, uri: &str) -> (StatusCode, Value) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        (response.status(), body_json(response).await)
+    }
+
+    fn titles(body: &Value) -> Vec<&str> {
+        body["books"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|book| book["title"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn list_books_by_pages() {
+        let (app, access_tokens) = test_app().await;
+        for title in ["c", "a", "d", "b", "e"] {
+            app.to_owned()
+                .oneshot(json_request(
+                    Method::POST,
+                    "/books",
+                    &access_tokens[0],
+                    json!({ "title": title }),
+                ))
+                .await
+                .unwrap();
+        }
+
+        let (status, body) = get_json(
+            app.to_owned(),
+            "/books?sort=title&order=desc&limit=2&total=true",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(titles(&body), vec!["e", "d"]);
+        assert_eq!(body["total"], 5);
+
+        // 2ページ目以降はカーソルだけで同じ並び順になる
+        let mut pages = vec![];
+        let mut next = body["next"].as_str().unwrap().to_string();
+        loop {
+            let (status, body) =
+                get_json(app.to_owned(), &format!("/books?limit=2&cursor={}", next)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body.get("total").is_none());
+            pages.push(titles(&body).join(""));
+            match body["next"].as_str() {
+                Some(cursor) => next = cursor.to_string(),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec!["cb", "a"]);
+    }
+
+    #[tokio::test]
+    async fn list_books_with_invalid_query() {
+        let (app, _) = test_app().await;
+
+        let (status, body) = get_json(app.to_owned(), "/books?limit=0&sort=isbn").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "limit");
+        assert_eq!(body["fields"][1]["field"], "sort");
+
+        let (status, body) = get_json(app, "/books?cursor=invalid").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "cursor");
+    }
+
+    fn search_uri(q: &str) -> String {
+        let query: String = form_urlencoded::Serializer::new(String::new())
+            .append_pair("q", q)
+            .finish();
+        format!("/books/search?{}", query)
+    }
+
+    #[tokio::test]
+    async fn search_books() {
+        let (app, access_tokens) = test_app().await;
+        for book in [
+            json!({"title": "プログラミングRust 第2版", "authors": ["Jim Blandy"], "isbn": "4873115655"}),
+            json!({"title": "Rust in Action", "authors": ["Tim McNamara"]}),
+            json!({"title": "すごいHaskellたのしく学ぼう！", "authors": ["Miran Lipovača"]}),
+        ] {
+            app.to_owned()
+                .oneshot(json_request(
+                    Method::POST,
+                    "/books",
+                    &access_tokens[0],
+                    book,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let (status, body) = get_json(app.to_owned(), &search_uri("rust")).await;
+        assert_eq!(status, StatusCode::OK);
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["book"]["title"], "Rust in Action");
+        assert_eq!(results[1]["highlights"]["title"], json!([[0, 4]]));
+
+        // 空白で区切った語はすべて含むものだけに一致する
+        let (_, body) = get_json(app.to_owned(), &search_uri("プログラミング rust")).await;
+        assert_eq!(body["results"].as_array().unwrap().len(), 1);
+        assert_eq!(body["results"][0]["highlights"]["title"], json!([[0, 11]]));
+
+        let (_, body) = get_json(app.to_owned(), &search_uri("blandy")).await;
+        assert_eq!(
+            body["results"][0]["highlights"]["authors"],
+            json!([[[4, 10]]])
+        );
+
+        let (_, body) = get_json(app.to_owned(), &search_uri("978-4-87311-565-8")).await;
+        assert_eq!(body["results"][0]["book"]["isbn"], "9784873115658");
+
+        let (_, body) = get_json(app.to_owned(), &search_uri("python")).await;
+        assert!(body["results"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_books_with_invalid_query() {
+        let (status, body) = get_json(test_app().await.0, "/books/search?limit=0").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "q");
+        assert_eq!(body["fields"][1]["field"], "limit");
+    }
+
+    #[tokio::test]
+    async fn update_and_delete_book_of_other_user() {
+        let (app, access_tokens) = test_app().await;
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t"}),
+            ))
+            .await
+            .unwrap();
+        let book_id = body_json(response).await["book_id"].as_u64().unwrap();
+        let uri = format!("/books/{}", book_id);
+
+        for (method, body) in [
+            (Method::PUT, json!({"title": "u"})),
+            (Method::DELETE, json!(null)),
+        ] {
+            let response = app
+                .to_owned()
+                .oneshot(json_request(method, &uri, &access_tokens[1], body))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+
+        let (_, body) = get_json(app.to_owned(), &uri).await;
+        assert_eq!(body["book"]["title"], "t");
+        assert!(body["book"]["createdBy"].is_u64());
+
+        let response = app
+            .oneshot(json_request(
+                Method::DELETE,
+                &uri,
+                &access_tokens[0],
+                json!(null),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn delete_book_referenced_by_other_user() {
+        let (app, access_tokens) = test_app().await;
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t", "pageCount": 100}),
+            ))
+            .await
+            .unwrap();
+        let book_id = body_json(response).await["book_id"].as_u64().unwrap();
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/records",
+                &access_tokens[1],
+                json!({"bookId": book_id, "startPage": 1, "endPage": 10}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // 削除すると他のユーザの読書記録まで消えてしまうので拒む
+        let uri = format!("/books/{}", book_id);
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::DELETE,
+                &uri,
+                &access_tokens[0],
+                json!(null),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let (status, _) = get_json(app.to_owned(), &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        let response = app
+            .oneshot(json_request(
+                Method::GET,
+                "/records",
+                &access_tokens[1],
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        let body = body_json(response).await;
+        assert_eq!(body["records"][0]["bookId"], book_id);
+    }
+
+    #[tokio::test]
+    async fn mutate_books_without_access_token() {
+        let (app, _) = test_app().await;
+        for (method, uri) in [
+            (Method::POST, "/books"),
+            (Method::PUT, "/books/1"),
+            (Method::DELETE, "/books/1"),
+            (Method::GET, "/library"),
+            (Method::PUT, "/library/1"),
+            (Method::DELETE, "/library/1"),
+        ] {
+            let response = app
+                .to_owned()
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .header(http::header::CONTENT_TYPE, "application/json")
+                        .body(Body::from(r#"{"title": "t"}"#))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        }
+    }
+
+    async fn get_status(app: Router, uri: &str, access_token: Option<&str>) -> StatusCode {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(access_token) = access_token {
+            builder = builder.header(
+                http::header::AUTHORIZATION,
+                format!("Bearer {}", access_token),
+            );
+        }
+        app.oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn read_books_anonymously() {
+        let (app, access_tokens) = test_app().await;
+        app.to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t"}),
+            ))
+            .await
+            .unwrap();
+
+        for uri in ["/books", "/books/1", "/books/search?q=t"] {
+            assert_eq!(get_status(app.to_owned(), uri, None).await, StatusCode::OK);
+            // 付けたaccess tokenは検証する
+            assert_eq!(
+                get_status(app.to_owned(), uri, Some("invalid")).await,
+                StatusCode::UNAUTHORIZED
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn read_books_without_anonymous_access() {
+        let (app, access_tokens) = test_app_with(vec![("ALLOW_ANONYMOUS_READ", "false")]).await;
+        app.to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t"}),
+            ))
+            .await
+            .unwrap();
+
+        for uri in ["/books", "/books/1", "/books/search?q=t"] {
+            assert_eq!(
+                get_status(app.to_owned(), uri, None).await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                get_status(app.to_owned(), uri, Some(&access_tokens[1])).await,
+                StatusCode::OK
+            );
+        }
+    }
+
+    async fn library_titles(app: Router, access_token: &str) -> Vec<String> {
+        let response = app
+            .oneshot(json_request(
+                Method::GET,
+                "/library?sort=title",
+                access_token,
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        titles(&body_json(response).await)
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn add_and_remove_books_in_library() {
+        let (app, access_tokens) = test_app().await;
+        for (title, access_token) in [("a", &access_tokens[0]), ("b", &access_tokens[1])] {
+            app.to_owned()
+                .oneshot(json_request(
+                    Method::POST,
+                    "/books",
+                    access_token,
+                    json!({ "title": title }),
+                ))
+                .await
+                .unwrap();
+        }
+
+        // 登録した本は自分の本棚に入る
+        assert_eq!(
+            library_titles(app.to_owned(), &access_tokens[0]).await,
+            vec!["a"]
+        );
+
+        // 他のユーザが登録した本も入れられ、二度入れても一冊のまま
+        for _ in 0..2 {
+            let response = app
+                .to_owned()
+                .oneshot(json_request(
+                    Method::PUT,
+                    "/library/2",
+                    &access_tokens[0],
+                    json!(null),
+                ))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(
+            library_titles(app.to_owned(), &access_tokens[0]).await,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            library_titles(app.to_owned(), &access_tokens[1]).await,
+            vec!["b"]
+        );
+
+        let request = |method| json_request(method, "/library/2", &access_tokens[0], json!(null));
+        let response = app
+            .to_owned()
+            .oneshot(request(Method::DELETE))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .to_owned()
+            .oneshot(request(Method::DELETE))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            library_titles(app.to_owned(), &access_tokens[0]).await,
+            vec!["a"]
+        );
+
+        let response = app
+            .oneshot(json_request(
+                Method::PUT,
+                "/library/3",
+                &access_tokens[0],
+                json!(null),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 }
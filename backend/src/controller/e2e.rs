@@ -0,0 +1,256 @@
+//! idp_mockをテストのプロセス内で起動し、
+//! メモリ上のrepositoryと組み合わせてログインから読書記録の登録までを通しで確認する。
+
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, BoxBody},
+    http::{self, Method, Request, Response, StatusCode},
+    AddExtensionLayer, Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::controller::{book::book_app, record::record_app, user::user_app};
+use crate::infra::{id_provider::discover_client, repo::memory::user::InMemoryUserRepository};
+use crate::settings::Settings;
+use crate::state::AppState;
+
+const IDP_RSA_PEM: &str = include_str!("../../testdata/idp_rsa.pem");
+
+/// idp_mockを空いているポートで起動し、そのURLを返す
+async fn start_id_provider() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let app = idp_mock::app(idp_mock::Settings {
+        base_url: Some(url.to_owned()),
+        rsa_pem: IDP_RSA_PEM.to_string(),
+    });
+    tokio::spawn(
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service()),
+    );
+
+    url
+}
+
+/// `main`と同じく`/v1`以下にまとめたアプリ。
+/// ユーザのrepositoryだけは起動したidp_mockで認可コードを引き換える。
+async fn test_app() -> (Router, String) {
+    let mut settings: Settings = envy::from_iter(vec![]).unwrap();
+    settings.id_provider_url = start_id_provider().await;
+
+    let id_cli = discover_client(&settings).await.unwrap();
+    let (mut state, _) = AppState::in_memory(settings.to_owned());
+    state.user_repository =
+        Arc::new(InMemoryUserRepository::new(settings.to_owned()).with_id_provider(id_cli));
+
+    let app = Router::new()
+        .nest(
+            "/v1",
+            Router::new()
+                .merge(book_app())
+                .merge(record_app())
+                .merge(user_app()),
+        )
+        .layer(AddExtensionLayer::new(state));
+    (app, settings.id_provider_url)
+}
+
+fn request(
+    method: Method,
+    uri: &str,
+    headers: &[(http::header::HeaderName, &str)],
+    body: Option<Value>,
+) -> Request<Body> {
+    let mut builder = Request::builder().method(method).uri(uri);
+    for (name, value) in headers {
+        builder = builder.header(name, *value);
+    }
+    match body {
+        Some(body) => builder
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(&body).unwrap())),
+        None => builder.body(Body::empty()),
+    }
+    .unwrap()
+}
+
+async fn body_bytes(response: Response<BoxBody>) -> Vec<u8> {
+    hyper::body::to_bytes(response.into_body())
+        .await
+        .unwrap()
+        .to_vec()
+}
+
+async fn body_json(response: Response<BoxBody>) -> Value {
+    serde_json::from_slice(&body_bytes(response).await).unwrap()
+}
+
+fn refresh_token_cookie(response: &Response<BoxBody>) -> String {
+    response.headers()[http::header::SET_COOKIE]
+        .to_str()
+        .unwrap()
+        .split(';')
+        .next()
+        .unwrap()
+        .to_owned()
+}
+
+/// ブラウザの代わりにidp_mockの認証フォームを送信し、認可コードを受け取る
+async fn authorize(idp_url: &str, nonce: &str, subject: &str) -> String {
+    let reqwest_cli = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let res = reqwest_cli
+        .post(format!("{}/auth", idp_url))
+        .form(&[
+            ("nonce", nonce),
+            ("state", "dummy"),
+            ("redirect_uri", "http://localhost:8000"),
+            ("sub", subject),
+            ("email", "test@example.jp"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    let url =
+        reqwest::Url::parse(res.headers()[reqwest::header::LOCATION].to_str().unwrap()).unwrap();
+    let pairs = url
+        .query_pairs()
+        .into_owned()
+        .collect::<HashMap<String, String>>();
+    pairs["code"].to_owned()
+}
+
+// ログイン → sign up → tokenのリフレッシュ → 本と読書記録の登録
+#[tokio::test]
+async fn login_sign_up_and_record() {
+    let (app, idp_url) = test_app().await;
+
+    let response = app
+        .to_owned()
+        .oneshot(request(Method::POST, "/v1/login-session", &[], None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_json(response).await;
+    let session_id = body["session_id"].as_str().unwrap();
+    let code = authorize(&idp_url, body["nonce"].as_str().unwrap(), "testsub").await;
+
+    // ユーザがいないのでsign upコードが返ってくる
+    let response = app
+        .to_owned()
+        .oneshot(request(
+            Method::POST,
+            "/v1/login",
+            &[],
+            Some(json!({"session_id": session_id, "code": code})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let code = body_json(response).await["error"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let response = app
+        .to_owned()
+        .oneshot(request(
+            Method::POST,
+            "/v1/signup",
+            &[],
+            Some(json!({"code": code, "user": {"username": "testname"}})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let refresh_token = refresh_token_cookie(&response);
+
+    let response = app
+        .to_owned()
+        .oneshot(request(
+            Method::POST,
+            "/v1/token",
+            &[(http::header::COOKIE, &refresh_token)],
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let access_token = String::from_utf8(body_bytes(response).await).unwrap();
+    let authorization = format!("Bearer {}", access_token);
+
+    let response = app
+        .to_owned()
+        .oneshot(request(
+            Method::POST,
+            "/v1/books",
+            &[(http::header::AUTHORIZATION, &authorization)],
+            Some(json!({"title": "t", "pageCount": 100})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let book_id = body_json(response).await["book_id"].as_u64().unwrap();
+
+    let response = app
+        .to_owned()
+        .oneshot(request(
+            Method::POST,
+            "/v1/records",
+            &[(http::header::AUTHORIZATION, &authorization)],
+            Some(json!({"bookId": book_id, "startPage": 1, "endPage": 20})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(request(
+            Method::GET,
+            "/v1/records",
+            &[(http::header::AUTHORIZATION, &authorization)],
+            None,
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_json(response).await;
+    assert_eq!(body["records"].as_array().unwrap().len(), 1);
+    assert_eq!(body["records"][0]["bookId"], book_id);
+}
+
+// idp_mockが発行していないコードではログインできない
+#[tokio::test]
+async fn login_with_code_not_issued_by_id_provider() {
+    let (app, _) = test_app().await;
+
+    let response = app
+        .to_owned()
+        .oneshot(request(Method::POST, "/v1/login-session", &[], None))
+        .await
+        .unwrap();
+    let session_id = body_json(response).await["session_id"]
+        .as_str()
+        .unwrap()
+        .to_owned();
+
+    let response = app
+        .oneshot(request(
+            Method::POST,
+            "/v1/login",
+            &[],
+            Some(json!({"session_id": session_id, "code": "invalid"})),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(body_json(response).await["error"], "");
+}
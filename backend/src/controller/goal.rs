@@ -0,0 +1,197 @@
+use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+use serde_json::{json, Value};
+
+use crate::domain::entity::goal::{GoalEntityForCreation, GoalError, GoalProgress};
+use crate::domain::repo_if::RepoError;
+use crate::domain::service::goal::GoalService;
+use crate::domain::service::user::UserId;
+
+pub fn goal_app() -> Router {
+    Router::new()
+        .route("/goals", get(list_goals).post(create_goal))
+        .route(
+            "/goals/:id",
+            get(get_goal).put(update_goal).delete(delete_goal),
+        )
+}
+
+async fn list_goals(
+    goal_service: GoalService,
+    UserId(user_id): UserId,
+) -> Result<Json<Value>, RepoError> {
+    let goals = goal_service.list_goals(user_id).await?;
+    Ok(Json(json!({
+        "goals": goals,
+    })))
+}
+
+async fn get_goal(
+    goal_service: GoalService,
+    UserId(user_id): UserId,
+    Path(goal_id): Path<u32>,
+) -> Result<Json<GoalProgress>, RepoError> {
+    goal_service.get_goal(user_id, goal_id).await.map(Json)
+}
+
+async fn create_goal(
+    goal_service: GoalService,
+    UserId(user_id): UserId,
+    Json(payload): Json<GoalEntityForCreation>,
+) -> Result<Json<Value>, GoalError> {
+    let goal_id = goal_service.create_goal(user_id, payload).await?;
+    Ok(Json(json!({
+        "goal_id": goal_id,
+    })))
+}
+
+async fn update_goal(
+    goal_service: GoalService,
+    UserId(user_id): UserId,
+    Path(goal_id): Path<u32>,
+    Json(payload): Json<GoalEntityForCreation>,
+) -> Result<StatusCode, GoalError> {
+    goal_service.update_goal(user_id, goal_id, payload).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn delete_goal(
+    goal_service: GoalService,
+    UserId(user_id): UserId,
+    Path(goal_id): Path<u32>,
+) -> Result<StatusCode, RepoError> {
+    goal_service.delete_goal(user_id, goal_id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::Method, AddExtensionLayer};
+    use chrono::{Datelike, Utc};
+
+    use super::*;
+    use crate::controller::{book::book_app, record::record_app};
+    use crate::state::testing::{call, in_memory_with_users};
+
+    /// 100ページの本を一冊登録したアプリと、ユーザのaccess tokenを返す
+    async fn test_app() -> (Router, String) {
+        let (state, mut access_tokens) =
+            in_memory_with_users(envy::from_iter(vec![]).unwrap()).await;
+        let access_token = access_tokens.remove(0);
+
+        let app = book_app()
+            .merge(record_app())
+            .merge(goal_app())
+            .layer(AddExtensionLayer::new(state));
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/books",
+            &access_token,
+            json!({"title": "t", "pageCount": 100}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        (app, access_token)
+    }
+
+    #[tokio::test]
+    async fn goal_progress() {
+        let (app, access_token) = test_app().await;
+
+        let (status, body) = call(
+            &app,
+            Method::POST,
+            "/goals",
+            &access_token,
+            json!({"metric": "books", "period": "year", "target": 24}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["goal_id"], 1);
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/goals",
+            &access_token,
+            json!({"metric": "pages", "period": "day", "target": 30}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(&app, Method::GET, "/goals/1", &access_token, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let year = Utc::now().year();
+        assert_eq!(body["goal"]["startsOn"], format!("{}-01-01", year));
+        assert_eq!(body["until"], format!("{}-12-31", year));
+        assert_eq!(body["current"], 0);
+        assert_eq!(body["projectedFinish"], Value::Null);
+
+        // 最後まで読むと読了になり、冊数の目標に数えられる
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 1, "startPage": 1, "endPage": 100}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(&app, Method::GET, "/goals", &access_token, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let goals = body["goals"].as_array().unwrap();
+        let books = goals.iter().find(|g| g["goal"]["id"] == 1).unwrap();
+        assert_eq!(books["current"], 1);
+        assert_eq!(books["today"], 1);
+        assert!(books["projectedFinish"].is_string());
+
+        let pages = goals.iter().find(|g| g["goal"]["id"] == 2).unwrap();
+        assert_eq!(pages["current"], 100);
+        assert_eq!(pages["target"], 30);
+        assert_eq!(pages["pace"], "ahead");
+        assert_eq!(pages["completed"], true);
+    }
+
+    #[tokio::test]
+    async fn update_and_delete_goal() {
+        let (app, access_token) = test_app().await;
+
+        let goal = json!({"metric": "books", "period": "month", "target": 2, "startsOn": "2026-01-01", "timezone": "Asia/Tokyo"});
+        let (status, _) = call(&app, Method::POST, "/goals", &access_token, goal.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, Method::POST, "/goals", &access_token, goal).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = call(
+            &app,
+            Method::POST,
+            "/goals",
+            &access_token,
+            json!({"metric": "books", "period": "month", "target": 0, "startsOn": "2026-01-02"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["fields"][0]["field"], "target");
+        assert_eq!(body["fields"][1]["field"], "startsOn");
+
+        let (status, _) = call(
+            &app,
+            Method::PUT,
+            "/goals/1",
+            &access_token,
+            json!({"metric": "books", "period": "month", "target": 3, "startsOn": "2026-01-01", "timezone": "Asia/Tokyo"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = call(&app, Method::GET, "/goals/1", &access_token, Value::Null).await;
+        assert_eq!(body["goal"]["target"], 3);
+        assert_eq!(body["goal"]["timezone"], "Asia/Tokyo");
+        assert_eq!(body["until"], "2026-01-31");
+
+        let (status, _) = call(&app, Method::DELETE, "/goals/1", &access_token, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(&app, Method::GET, "/goals/1", &access_token, Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
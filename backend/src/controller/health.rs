@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{http::StatusCode, routing::get, Json, Router};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::state::AppState;
+
+/// オーケストレータ向けの死活監視。`/v1`の外に公開する。
+pub fn health_app() -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+}
+
+/// 終了のシグナル`signal`を受けたらすぐに`/readyz`を503にし、
+/// `pre_stop_delay`の間はそのままリクエストを受け付ける。戻ったらgraceful shutdownを始めてよい。
+pub async fn start_draining(
+    signal: impl Future<Output = ()>,
+    draining: Arc<AtomicBool>,
+    pre_stop_delay: Duration,
+) {
+    signal.await;
+    draining.store(true, Ordering::SeqCst);
+    tracing::info!(
+        "shutdown signal received; failing readiness for {} secs before draining",
+        pre_stop_delay.as_secs()
+    );
+    tokio::time::sleep(pre_stop_delay).await;
+}
+
+/// プロセスが応答できるかだけを返す
+async fn healthz() -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckResult {
+    status: &'static str,
+    latency_ms: u128,
+    /// 詳細はログにだけ出し、レスポンスには固定の文言だけを返す
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    status: &'static str,
+    checks: BTreeMap<&'static str, CheckResult>,
+}
+
+/// 依存先を並行して確認し、1つでも失敗すれば503を返す。
+/// 終了処理中は依存先を確認せずに503を返す。
+async fn readyz(state: AppState) -> (StatusCode, Json<Readiness>) {
+    if state.draining.load(Ordering::SeqCst) {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(Readiness {
+                status: "draining",
+                checks: BTreeMap::new(),
+            }),
+        );
+    }
+
+    let timeout = Duration::from_secs(state.settings.readiness_timeout);
+
+    let handles: Vec<_> = state
+        .probes
+        .iter()
+        .cloned()
+        .map(|probe| {
+            let name = probe.name();
+            let handle = tokio::spawn(async move {
+                let start = Instant::now();
+                let result = match tokio::time::timeout(timeout, probe.check()).await {
+                    Ok(Ok(())) => Ok(()),
+                    Ok(Err(err)) => Err(("unavailable", err)),
+                    Err(_) => Err(("timed out", "timed out".to_string())),
+                };
+                (result, start.elapsed())
+            });
+            (name, handle)
+        })
+        .collect();
+
+    let mut checks = BTreeMap::new();
+    for (name, handle) in handles {
+        let (result, elapsed) = handle
+            .await
+            .unwrap_or_else(|err| (Err(("unavailable", err.to_string())), Duration::ZERO));
+        if let Err((_, err)) = &result {
+            tracing::info!("readiness check of {} failed: {}", name, err);
+        }
+        checks.insert(
+            name,
+            CheckResult {
+                status: if result.is_ok() { "ok" } else { "error" },
+                latency_ms: elapsed.as_millis(),
+                error: result.err().map(|(error, _)| error),
+            },
+        );
+    }
+
+    let ready = checks.values().all(|check| check.error.is_none());
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(Readiness {
+            status: if ready { "ok" } else { "unavailable" },
+            checks,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{async_trait, body::Body, http::Request, AddExtensionLayer};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::infra::health::Probe;
+
+    struct FixedProbe(&'static str, Result<(), String>);
+
+    #[async_trait]
+    impl Probe for FixedProbe {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        async fn check(&self) -> Result<(), String> {
+            self.1.clone()
+        }
+    }
+
+    struct HangingProbe;
+
+    #[async_trait]
+    impl Probe for HangingProbe {
+        fn name(&self) -> &'static str {
+            "hanging"
+        }
+
+        async fn check(&self) -> Result<(), String> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        }
+    }
+
+    fn test_state(probes: Vec<Arc<dyn Probe>>) -> AppState {
+        let settings =
+            envy::from_iter(vec![("READINESS_TIMEOUT".to_string(), "1".to_string())]).unwrap();
+        let (mut state, _) = AppState::in_memory(settings);
+        state.probes = probes;
+        state
+    }
+
+    fn test_app(probes: Vec<Arc<dyn Probe>>) -> Router {
+        health_app().layer(AddExtensionLayer::new(test_state(probes)))
+    }
+
+    async fn get_json(app: Router, uri: &str) -> (StatusCode, Value) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn healthz() {
+        let app = test_app(vec![Arc::new(FixedProbe("postgres", Err("down".into())))]);
+
+        let (status, body) = get_json(app, "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+    }
+
+    #[tokio::test]
+    async fn readyz_ok() {
+        let app = test_app(vec![
+            Arc::new(FixedProbe("postgres", Ok(()))),
+            Arc::new(FixedProbe("redis", Ok(()))),
+        ]);
+
+        let (status, body) = get_json(app, "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["checks"]["postgres"]["status"], "ok");
+        assert_eq!(body["checks"]["redis"]["status"], "ok");
+        assert!(body["checks"]["redis"]["latencyMs"].is_u64());
+        assert!(body["checks"]["redis"].get("error").is_none());
+    }
+
+    #[tokio::test]
+    async fn readyz_unavailable() {
+        let app = test_app(vec![
+            Arc::new(FixedProbe("postgres", Ok(()))),
+            Arc::new(FixedProbe("redis", Err("connection refused".into()))),
+            Arc::new(HangingProbe),
+        ]);
+
+        let (status, body) = get_json(app, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["checks"]["postgres"]["status"], "ok");
+        assert_eq!(body["checks"]["redis"]["status"], "error");
+        // 接続先などが分かる詳細は返さない
+        assert_eq!(body["checks"]["redis"]["error"], "unavailable");
+        assert_eq!(body["checks"]["hanging"]["error"], "timed out");
+    }
+
+    #[tokio::test]
+    async fn readyz_draining() {
+        let state = test_state(vec![Arc::new(FixedProbe("postgres", Ok(())))]);
+        state.draining.store(true, Ordering::SeqCst);
+        let app = health_app().layer(AddExtensionLayer::new(state));
+
+        let (status, body) = get_json(app.to_owned(), "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "draining");
+
+        // livenessは終了処理中も変わらない
+        let (status, _) = get_json(app, "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn readyz_fails_as_soon_as_draining_starts() {
+        let state = test_state(vec![Arc::new(FixedProbe("postgres", Ok(())))]);
+        let app = health_app().layer(AddExtensionLayer::new(state.clone()));
+        let (status, _) = get_json(app.to_owned(), "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let drain = start_draining(
+            std::future::ready(()),
+            state.draining.clone(),
+            Duration::from_millis(100),
+        );
+        tokio::pin!(drain);
+        // シグナルを受けた直後、pre-stopの待機中
+        tokio::select! {
+            biased;
+            _ = &mut drain => panic!("draining finished before the pre-stop delay"),
+            _ = tokio::task::yield_now() => {}
+        }
+
+        // 待機中もリクエストには応答し、readinessだけを落とす
+        let (status, body) = get_json(app.to_owned(), "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "draining");
+        let (status, _) = get_json(app, "/healthz").await;
+        assert_eq!(status, StatusCode::OK);
+
+        drain.await;
+    }
+}
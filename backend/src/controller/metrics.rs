@@ -0,0 +1,70 @@
+use axum::{http::header, response::Headers, routing::get, Router};
+
+use crate::state::AppState;
+
+/// Prometheusのスクレイプ先。`/v1`の外に公開する。
+pub fn metrics_app() -> Router {
+    Router::new().route("/metrics", get(metrics))
+}
+
+async fn metrics(state: AppState) -> (Headers<Vec<(header::HeaderName, &'static str)>>, String) {
+    (
+        Headers(vec![(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )]),
+        state.metrics.render(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        AddExtensionLayer,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::controller::book::book_app;
+    use crate::infra::metrics::MetricsLayer;
+
+    #[tokio::test]
+    async fn count_requests_by_matched_route() {
+        let (state, _) = AppState::in_memory(envy::from_iter(vec![]).unwrap());
+        let app = Router::new()
+            .nest(
+                "/v1",
+                book_app()
+                    .layer(AddExtensionLayer::new(state.clone()))
+                    .layer(MetricsLayer(state.metrics.clone())),
+            )
+            .merge(metrics_app().layer(AddExtensionLayer::new(state)));
+
+        for uri in ["/v1/books/1", "/v1/books/2"] {
+            let response = app
+                .to_owned()
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/metrics")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(
+            r#"book_record_http_requests_total{method="GET",route="/v1/books/:id",status="404"} 2"#
+        ));
+    }
+}
@@ -1,6 +1,24 @@
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequest, RequestParts, TypedHeader},
+    http::StatusCode,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use headers::UserAgent;
 use serde::Deserialize;
 
-use crate::domain::entity::user::{SignUpCode, UserEntityForCreation};
+use crate::domain::entity::{
+    book::{BookCursor, BookListQuery, BookSearchQuery, BookSortKey, SortOrder},
+    record::RecordFilter,
+    stats::StatsQuery,
+    user::{ClientInfo, SignUpCode, UserEntityForCreation},
+    validation::ValidationError,
+    Pid,
+};
+use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
 pub struct LoginExtract {
@@ -13,3 +31,320 @@ pub struct SignUpExtract {
     pub code: SignUpCode,
     pub user: UserEntityForCreation,
 }
+
+/// セッション一覧に表示するためのクライアント情報。
+/// IPアドレスは接続元のものを使う。`TRUST_FORWARDED_FOR`が有効な場合だけ、
+/// 信頼するリバースプロキシがX-Forwarded-Forの末尾に付けたアドレスを優先する。
+#[derive(Debug)]
+pub struct ClientInfoExtract(pub ClientInfo);
+
+#[async_trait]
+impl<B> FromRequest<B> for ClientInfoExtract
+where
+    B: Send,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user_agent = Option::<TypedHeader<UserAgent>>::from_request(req)
+            .await
+            .ok()
+            .flatten()
+            .map(|TypedHeader(user_agent)| user_agent.as_str().to_string());
+
+        let trust_forwarded_for = AppState::from_request(req)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .settings
+            .trust_forwarded_for;
+        // 先頭の方はクライアントが自由に付けられるので、プロキシが追記した末尾だけを見る
+        let forwarded_for = req
+            .headers()
+            .filter(|_| trust_forwarded_for)
+            .and_then(|headers| headers.get("x-forwarded-for"))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty());
+        let ip = match forwarded_for {
+            Some(ip) => Some(ip),
+            None => Option::<ConnectInfo<SocketAddr>>::from_request(req)
+                .await
+                .ok()
+                .flatten()
+                .map(|ConnectInfo(addr)| addr.ip().to_string()),
+        };
+
+        Ok(Self(ClientInfo { user_agent, ip }))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogoutQuery {
+    /// trueの場合、同じユーザの全てのrefresh tokenを無効にする
+    #[serde(default)]
+    pub everywhere: bool,
+}
+
+/// 1ページに返す本の最大数
+const MAX_BOOKS_LIMIT: u32 = 100;
+
+/// `GET /books`のクエリパラメータ。
+/// `cursor`を指定した場合、`sort`と`order`は省略するとカーソルを作ったときのものになる。
+#[derive(Debug)]
+pub struct BookQuery(pub BookListQuery);
+
+#[async_trait]
+impl<B> FromRequest<B> for BookQuery
+where
+    B: Send,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let query = req.uri().query().unwrap_or_default();
+        let mut result = BookListQuery::default();
+        let mut sort = None;
+        let mut order = None;
+        let mut error = ValidationError::new();
+
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "limit" => match value.parse() {
+                    Ok(limit) if (1..=MAX_BOOKS_LIMIT).contains(&limit) => result.limit = limit,
+                    _ => error.add(
+                        "limit",
+                        format!("must be an integer from 1 to {}", MAX_BOOKS_LIMIT),
+                    ),
+                },
+                "sort" => match value.as_ref() {
+                    "id" => sort = Some(BookSortKey::Id),
+                    "title" => sort = Some(BookSortKey::Title),
+                    "created_at" => sort = Some(BookSortKey::CreatedAt),
+                    _ => error.add("sort", "must be one of id, title and created_at"),
+                },
+                "order" => match value.as_ref() {
+                    "asc" => order = Some(SortOrder::Asc),
+                    "desc" => order = Some(SortOrder::Desc),
+                    _ => error.add("order", "must be asc or desc"),
+                },
+                "cursor" => match BookCursor::decode(&value) {
+                    Some(cursor) => result.after = Some(cursor),
+                    None => error.add("cursor", "is invalid"),
+                },
+                "total" => match value.parse() {
+                    Ok(with_total) => result.with_total = with_total,
+                    Err(_) => error.add("total", "must be true or false"),
+                },
+                _ => {}
+            }
+        }
+
+        if let Some(cursor) = &result.after {
+            if sort.is_some_and(|sort| sort != cursor.sort())
+                || order.is_some_and(|order| order != cursor.order)
+            {
+                error.add("cursor", "does not match sort and order");
+            }
+            result.sort = cursor.sort();
+            result.order = cursor.order;
+        } else {
+            result.sort = sort.unwrap_or(result.sort);
+            result.order = order.unwrap_or(result.order);
+        }
+
+        error.into_result().map(|_| Self(result))
+    }
+}
+
+/// 検索結果の既定の件数
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+
+/// `GET /books/search`のクエリパラメータ。`q`は必須。
+#[derive(Debug)]
+pub struct BookSearchExtract(pub BookSearchQuery);
+
+#[async_trait]
+impl<B> FromRequest<B> for BookSearchExtract
+where
+    B: Send,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let query = req.uri().query().unwrap_or_default();
+        let mut q = String::new();
+        let mut limit = DEFAULT_SEARCH_LIMIT;
+        let mut error = ValidationError::new();
+
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "q" => q = value.into_owned(),
+                "limit" => match value.parse() {
+                    Ok(value) if (1..=MAX_BOOKS_LIMIT).contains(&value) => limit = value,
+                    _ => error.add(
+                        "limit",
+                        format!("must be an integer from 1 to {}", MAX_BOOKS_LIMIT),
+                    ),
+                },
+                _ => {}
+            }
+        }
+
+        match BookSearchQuery::parse(&q, limit) {
+            Ok(query) => error.into_result().map(|_| Self(query)),
+            Err(mut query_error) => {
+                query_error.errors.extend(error.errors);
+                Err(query_error)
+            }
+        }
+    }
+}
+
+/// `GET /records`のクエリパラメータ。
+/// 配列は`user_ids=1&user_ids=2`のようにキーを繰り返して指定する。
+#[derive(Debug, Default)]
+pub struct RecordQuery(pub RecordFilter);
+
+#[async_trait]
+impl<B> FromRequest<B> for RecordQuery
+where
+    B: Send,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let query = req.uri().query().unwrap_or_default();
+        let mut filter = RecordFilter::default();
+        let mut error = ValidationError::new();
+
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "user_ids" => match parse_id(&value) {
+                    Ok(id) => filter.user_ids.push(id),
+                    Err(message) => error.add("user_ids", message),
+                },
+                "book_ids" => match parse_id(&value) {
+                    Ok(id) => filter.book_ids.push(id),
+                    Err(message) => error.add("book_ids", message),
+                },
+                "since_datetime" => match parse_datetime(&value) {
+                    Ok(datetime) => filter.since = Some(datetime),
+                    Err(message) => error.add("since_datetime", message),
+                },
+                "until_datetime" => match parse_datetime(&value) {
+                    Ok(datetime) => filter.until = Some(datetime),
+                    Err(message) => error.add("until_datetime", message),
+                },
+                _ => {}
+            }
+        }
+
+        error.into_result().map(|_| Self(filter))
+    }
+}
+
+/// `GET /stats`のクエリパラメータ。
+/// `since`と`until`は`timezone`での日付で、`timezone`の既定はUTC。
+#[derive(Debug)]
+pub struct StatsExtract(pub StatsQuery);
+
+#[async_trait]
+impl<B> FromRequest<B> for StatsExtract
+where
+    B: Send,
+{
+    type Rejection = ValidationError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let query = req.uri().query().unwrap_or_default();
+        let mut since = None;
+        let mut until = None;
+        let mut timezone = Tz::UTC;
+        let mut error = ValidationError::new();
+
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "since" => match value.parse::<NaiveDate>() {
+                    Ok(date) => since = Some(date),
+                    Err(_) => error.add("since", "must be a date like 2022-01-31"),
+                },
+                "until" => match value.parse::<NaiveDate>() {
+                    Ok(date) => until = Some(date),
+                    Err(_) => error.add("until", "must be a date like 2022-01-31"),
+                },
+                "timezone" => match value.parse() {
+                    Ok(tz) => timezone = tz,
+                    Err(_) => error.add("timezone", "must be an IANA time zone name"),
+                },
+                _ => {}
+            }
+        }
+
+        error.into_result()?;
+        StatsQuery::new(since, until, timezone, Utc::now()).map(Self)
+    }
+}
+
+fn parse_id(value: &str) -> Result<Pid, &'static str> {
+    value.parse().map_err(|_| "must be an array of integers")
+}
+
+fn parse_datetime(value: &str) -> Result<DateTime<Utc>, &'static str> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .map_err(|_| "must be an RFC 3339 date-time")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    async fn client_ip(trust_forwarded_for: &str, forwarded_for: Option<&str>) -> Option<String> {
+        let settings = envy::from_iter(vec![(
+            "TRUST_FORWARDED_FOR".to_string(),
+            trust_forwarded_for.to_string(),
+        )])
+        .unwrap();
+        let (state, _) = AppState::in_memory(settings);
+
+        let mut request = Request::builder();
+        if let Some(forwarded_for) = forwarded_for {
+            request = request.header("x-forwarded-for", forwarded_for);
+        }
+        let mut request = request.body(()).unwrap();
+        request.extensions_mut().insert(state);
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 50000))));
+
+        let mut req = RequestParts::new(request);
+        ClientInfoExtract::from_request(&mut req)
+            .await
+            .unwrap()
+            .0
+            .ip
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_for_is_ignored_by_default() {
+        assert_eq!(
+            client_ip("false", Some("203.0.113.1")).await.as_deref(),
+            Some("10.0.0.1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_for_behind_trusted_proxy() {
+        // クライアントが付けた先頭のアドレスではなく、プロキシが追記した末尾を使う
+        assert_eq!(
+            client_ip("true", Some("192.0.2.1, 203.0.113.1"))
+                .await
+                .as_deref(),
+            Some("203.0.113.1")
+        );
+        assert_eq!(client_ip("true", None).await.as_deref(), Some("10.0.0.1"));
+    }
+}
@@ -0,0 +1,326 @@
+use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+use serde_json::{json, Value};
+
+use crate::controller::models::RecordQuery;
+use crate::domain::entity::record::{RecordEntity, RecordEntityForCreation, RecordError};
+use crate::domain::repo_if::RepoError;
+use crate::domain::service::record::RecordService;
+use crate::domain::service::user::UserId;
+use crate::state::AppState;
+
+pub fn record_app() -> Router {
+    Router::new()
+        .route("/records", get(list_records).post(create_record))
+        .route(
+            "/records/:id",
+            get(get_record).put(update_record).delete(delete_record),
+        )
+}
+
+async fn list_records(
+    record_service: RecordService,
+    UserId(user_id): UserId,
+    RecordQuery(filter): RecordQuery,
+) -> Result<Json<Value>, RepoError> {
+    let records: Vec<RecordEntity> = record_service.list_records(user_id, filter).await?;
+    Ok(Json(json!({
+        "records": records,
+    })))
+}
+
+async fn get_record(
+    record_service: RecordService,
+    UserId(_): UserId,
+    Path(record_id): Path<u32>,
+) -> Result<Json<RecordEntity>, RepoError> {
+    record_service.get_record(record_id).await.map(Json)
+}
+
+async fn create_record(
+    record_service: RecordService,
+    UserId(user_id): UserId,
+    Json(payload): Json<RecordEntityForCreation>,
+    AppState { metrics, .. }: AppState,
+) -> Result<Json<Value>, RecordError> {
+    let record_id = record_service.create_record(user_id, payload).await?;
+    metrics.records_created.inc();
+    Ok(Json(json!({
+        "record_id": record_id,
+    })))
+}
+
+async fn update_record(
+    record_service: RecordService,
+    UserId(user_id): UserId,
+    Path(record_id): Path<u32>,
+    Json(payload): Json<RecordEntityForCreation>,
+) -> Result<StatusCode, RecordError> {
+    record_service
+        .update_record(user_id, record_id, payload)
+        .await?;
+    Ok(StatusCode::OK)
+}
+
+async fn delete_record(
+    record_service: RecordService,
+    UserId(user_id): UserId,
+    Path(record_id): Path<u32>,
+) -> Result<StatusCode, RepoError> {
+    record_service.delete_record(user_id, record_id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::Method, AddExtensionLayer};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::controller::book::book_app;
+    use crate::state::testing::{call, in_memory_with_users, json_request};
+
+    /// 本を一冊登録したアプリと、ユーザのaccess tokenを返す
+    async fn test_app(page_count: i32) -> (Router, String) {
+        let (app, mut access_tokens) = test_app_with_users(page_count).await;
+        (app, access_tokens.remove(0))
+    }
+
+    /// 本を一冊登録したアプリと、2人のユーザそれぞれのaccess tokenを返す。本は1人目が登録する
+    async fn test_app_with_users(page_count: i32) -> (Router, Vec<String>) {
+        let (state, access_tokens) = in_memory_with_users(envy::from_iter(vec![]).unwrap()).await;
+
+        let app = book_app()
+            .merge(record_app())
+            .layer(AddExtensionLayer::new(state));
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/books",
+                &access_tokens[0],
+                json!({"title": "t", "pageCount": page_count}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        (app, access_tokens)
+    }
+
+    #[tokio::test]
+    async fn create_and_list_records() {
+        let (app, access_token) = test_app(300).await;
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/records",
+                &access_token,
+                json!({"bookId": 1, "startPage": 1, "endPage": 300}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(json_request(
+                Method::GET,
+                "/records",
+                &access_token,
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["records"][0]["endPage"], 300);
+    }
+
+    async fn get_record(app: &Router, record_id: u64, access_token: &str) -> (StatusCode, Value) {
+        let uri = format!("/records/{}", record_id);
+        call(app, Method::GET, &uri, access_token, Value::Null).await
+    }
+
+    #[tokio::test]
+    async fn get_update_and_delete_record() {
+        let (app, access_token) = test_app(300).await;
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/records",
+                &access_token,
+                json!({"bookId": 1, "startPage": 1, "endPage": 10, "comment": "c"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let record_id = body["record_id"].as_u64().unwrap();
+
+        let (status, body) = get_record(&app, record_id, &access_token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["endPage"], 10);
+        assert_eq!(body["comment"], "c");
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::PUT,
+                &format!("/records/{}", record_id),
+                &access_token,
+                json!({"bookId": 1, "startPage": 1, "endPage": 20}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (_, body) = get_record(&app, record_id, &access_token).await;
+        assert_eq!(body["endPage"], 20);
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::DELETE,
+                &format!("/records/{}", record_id),
+                &access_token,
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, _) = get_record(&app, record_id, &access_token).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn records_of_other_user_are_readable_but_not_writable() {
+        let (app, access_tokens) = test_app_with_users(300).await;
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::POST,
+                "/records",
+                &access_tokens[0],
+                json!({"bookId": 1, "startPage": 1, "endPage": 10, "comment": "c"}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let record_id = body["record_id"].as_u64().unwrap();
+        let uri = format!("/records/{}", record_id);
+
+        // 一覧で`user_ids`を指定した場合と同じく、詳細も他のユーザから見える
+        let (status, body) = get_record(&app, record_id, &access_tokens[1]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["comment"], "c");
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::GET,
+                "/records?user_ids=1",
+                &access_tokens[1],
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["records"][0]["id"], record_id);
+
+        // 更新と削除は所有者だけができる
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::PUT,
+                &uri,
+                &access_tokens[1],
+                json!({"bookId": 1, "startPage": 1, "endPage": 20}),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = app
+            .to_owned()
+            .oneshot(json_request(
+                Method::DELETE,
+                &uri,
+                &access_tokens[1],
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (status, body) = get_record(&app, record_id, &access_tokens[0]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["endPage"], 10);
+    }
+
+    #[tokio::test]
+    async fn list_records_with_invalid_query() {
+        let (app, access_token) = test_app(300).await;
+
+        let response = app
+            .oneshot(json_request(
+                Method::GET,
+                "/records?user_ids=a&book_ids=1&since_datetime=2022-01-01",
+                &access_token,
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "user_ids");
+        assert_eq!(body["fields"][1]["field"], "since_datetime");
+    }
+
+    #[tokio::test]
+    async fn create_record_beyond_page_count() {
+        let (app, access_token) = test_app(300).await;
+
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                "/records",
+                &access_token,
+                json!({"bookId": 1, "startPage": 200, "endPage": 301}),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "endPage");
+    }
+
+    #[tokio::test]
+    async fn create_record_without_access_token() {
+        let (app, _) = test_app(300).await;
+
+        let response = app
+            .oneshot(json_request(
+                Method::POST,
+                "/records",
+                "invalid",
+                json!({"bookId": 1, "startPage": 1, "endPage": 2}),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
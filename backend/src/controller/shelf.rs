@@ -0,0 +1,351 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use crate::domain::entity::shelf::{ReadingEntity, ShelfChange, ShelfError, ShelfStatus};
+use crate::domain::repo_if::RepoError;
+use crate::domain::service::shelf::ShelfService;
+use crate::domain::service::user::UserId;
+
+pub fn shelf_app() -> Router {
+    Router::new()
+        .route("/shelves", get(list_shelves))
+        .route(
+            "/shelves/books/:id",
+            get(list_book_readings).put(change_status),
+        )
+        .route("/shelves/readings/:id", delete(delete_reading))
+}
+
+async fn list_shelves(
+    shelf_service: ShelfService,
+    UserId(user_id): UserId,
+) -> Result<Json<Value>, RepoError> {
+    let shelves = shelf_service.list_shelves(user_id).await?;
+    Ok(Json(json!({
+        "shelves": shelves,
+    })))
+}
+
+async fn list_book_readings(
+    shelf_service: ShelfService,
+    UserId(user_id): UserId,
+    Path(book_id): Path<u32>,
+) -> Result<Json<Value>, RepoError> {
+    let readings = shelf_service.list_book_readings(user_id, book_id).await?;
+    let times_finished = readings
+        .iter()
+        .filter(|reading| reading.status == ShelfStatus::Finished)
+        .count();
+    Ok(Json(json!({
+        "readings": readings,
+        "timesFinished": times_finished,
+    })))
+}
+
+async fn change_status(
+    shelf_service: ShelfService,
+    UserId(user_id): UserId,
+    Path(book_id): Path<u32>,
+    Json(payload): Json<ShelfChange>,
+) -> Result<Json<ReadingEntity>, ShelfError> {
+    shelf_service
+        .change_status(user_id, book_id, payload)
+        .await
+        .map(Json)
+}
+
+async fn delete_reading(
+    shelf_service: ShelfService,
+    UserId(user_id): UserId,
+    Path(reading_id): Path<u32>,
+) -> Result<StatusCode, RepoError> {
+    shelf_service.delete_reading(user_id, reading_id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::Method, AddExtensionLayer};
+
+    use super::*;
+    use crate::controller::{book::book_app, record::record_app};
+    use crate::state::testing::{call, in_memory_with_users};
+
+    /// 300ページの本を一冊登録したアプリと、ユーザのaccess tokenを返す
+    async fn test_app() -> (Router, String) {
+        let (state, mut access_tokens) =
+            in_memory_with_users(envy::from_iter(vec![]).unwrap()).await;
+        let access_token = access_tokens.remove(0);
+
+        let app = book_app()
+            .merge(record_app())
+            .merge(shelf_app())
+            .layer(AddExtensionLayer::new(state));
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/books",
+            &access_token,
+            json!({"title": "t", "pageCount": 300}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        (app, access_token)
+    }
+
+    async fn get(app: &Router, uri: &str, access_token: &str) -> Value {
+        let (status, body) = call(app, Method::GET, uri, access_token, Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+
+    #[tokio::test]
+    async fn change_status() {
+        let (app, access_token) = test_app().await;
+
+        let (status, body) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/1",
+            &access_token,
+            json!({"status": "want_to_read"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "want_to_read");
+        assert_eq!(body["startedAt"], Value::Null);
+
+        let (status, body) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/1",
+            &access_token,
+            json!({"status": "reading"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["startedAt"].is_string());
+
+        let (status, body) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/1",
+            &access_token,
+            json!({"status": "want_to_read"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "cannot change from reading to want_to_read");
+
+        let (status, _) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/1",
+            &access_token,
+            json!({"status": "finished", "finishedAt": "2000-01-01T00:00:00Z"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/1",
+            &access_token,
+            json!({"status": "finished"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = get(&app, "/shelves", &access_token).await;
+        assert_eq!(body["shelves"]["finished"][0]["bookId"], 1);
+        assert_eq!(body["shelves"]["reading"], json!([]));
+
+        // 本棚に入れた本はライブラリにも入る
+        let body = get(&app, "/library", &access_token).await;
+        assert_eq!(body["books"][0]["id"], 1);
+    }
+
+    #[tokio::test]
+    async fn reread_book() {
+        let (app, access_token) = test_app().await;
+
+        for status in ["finished", "reading"] {
+            let (code, _) = call(
+                &app,
+                Method::PUT,
+                "/shelves/books/1",
+                &access_token,
+                json!({ "status": status }),
+            )
+            .await;
+            assert_eq!(code, StatusCode::OK);
+        }
+
+        let body = get(&app, "/shelves/books/1", &access_token).await;
+        assert_eq!(body["timesFinished"], 1);
+        let readings = body["readings"].as_array().unwrap();
+        assert_eq!(readings.len(), 2);
+        assert_ne!(readings[0]["id"], readings[1]["id"]);
+
+        let reading_id = readings
+            .iter()
+            .find(|reading| reading["status"] == "reading")
+            .unwrap()["id"]
+            .to_owned();
+        let (status, _) = call(
+            &app,
+            Method::DELETE,
+            &format!("/shelves/readings/{}", reading_id),
+            &access_token,
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body = get(&app, "/shelves/books/1", &access_token).await;
+        assert_eq!(body["readings"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn change_status_of_missing_book() {
+        let (app, access_token) = test_app().await;
+
+        let (status, _) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/2",
+            &access_token,
+            json!({"status": "reading"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = call(
+            &app,
+            Method::PUT,
+            "/shelves/books/1",
+            &access_token,
+            json!({"status": "abandoned"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn finish_by_records() {
+        let (app, access_token) = test_app().await;
+
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 1, "startPage": 1, "endPage": 100}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = get(&app, "/shelves", &access_token).await;
+        assert_eq!(body["shelves"]["reading"][0]["bookId"], 1);
+
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 1, "startPage": 101, "endPage": 300}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = get(&app, "/shelves", &access_token).await;
+        assert_eq!(body["shelves"]["reading"], json!([]));
+        assert!(body["shelves"]["finished"][0]["finishedAt"].is_string());
+
+        // 読み終えた本への記録で再読は始まらない
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 1, "startPage": 1, "endPage": 10}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = get(&app, "/shelves/books/1", &access_token).await;
+        assert_eq!(body["readings"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn records_catch_up_on_missed_shelf_updates() {
+        let (app, access_token) = test_app().await;
+
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/books",
+            &access_token,
+            json!({"title": "u"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 2, "startPage": 1, "endPage": 300}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = get(&app, "/shelves", &access_token).await;
+        assert_eq!(body["shelves"]["reading"][0]["bookId"], 2);
+
+        // 総ページ数が後から分かり、ライブラリの更新も失われていたとする
+        let (status, _) = call(
+            &app,
+            Method::PUT,
+            "/books/2",
+            &access_token,
+            json!({"title": "u", "pageCount": 300}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = call(
+            &app,
+            Method::DELETE,
+            "/library/2",
+            &access_token,
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // 次の記録で、保存済みの記録から読み終えたことになり、ライブラリにも戻る
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 2, "startPage": 1, "endPage": 10}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let body = get(&app, "/shelves", &access_token).await;
+        assert_eq!(body["shelves"]["reading"], json!([]));
+        assert_eq!(body["shelves"]["finished"][0]["bookId"], 2);
+        let body = get(&app, "/library", &access_token).await;
+        let ids: Vec<_> = body["books"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|book| book["id"].to_owned())
+            .collect();
+        assert!(ids.contains(&json!(2)));
+    }
+}
@@ -0,0 +1,152 @@
+use axum::{routing::get, Json, Router};
+
+use crate::controller::models::StatsExtract;
+use crate::domain::entity::stats::ReadingStats;
+use crate::domain::repo_if::RepoError;
+use crate::domain::service::stats::StatsService;
+use crate::domain::service::user::UserId;
+
+pub fn stats_app() -> Router {
+    Router::new().route("/stats", get(reading_stats))
+}
+
+async fn reading_stats(
+    stats_service: StatsService,
+    UserId(user_id): UserId,
+    StatsExtract(query): StatsExtract,
+) -> Result<Json<ReadingStats>, RepoError> {
+    stats_service.reading_stats(user_id, query).await.map(Json)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::{Method, StatusCode},
+        AddExtensionLayer,
+    };
+    use chrono::Utc;
+    use chrono_tz::Tz;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::controller::{book::book_app, record::record_app};
+    use crate::state::testing::{call, in_memory_with_users};
+
+    /// 100ページの本を一冊登録したアプリと、ユーザのaccess tokenを返す
+    async fn test_app() -> (Router, String) {
+        let (state, mut access_tokens) =
+            in_memory_with_users(envy::from_iter(vec![]).unwrap()).await;
+        let access_token = access_tokens.remove(0);
+
+        let app = book_app()
+            .merge(record_app())
+            .merge(stats_app())
+            .layer(AddExtensionLayer::new(state));
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/books",
+            &access_token,
+            json!({"title": "t", "pageCount": 100}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        (app, access_token)
+    }
+
+    #[tokio::test]
+    async fn reading_stats() {
+        let (app, access_token) = test_app().await;
+
+        for (start_page, end_page) in [(1, 30), (31, 100)] {
+            let (status, _) = call(
+                &app,
+                Method::POST,
+                "/records",
+                &access_token,
+                json!({"bookId": 1, "startPage": start_page, "endPage": end_page}),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let timezone: Tz = "Asia/Tokyo".parse().unwrap();
+        let today = Utc::now().with_timezone(&timezone).naive_local().date();
+        let (status, body) = call(
+            &app,
+            Method::GET,
+            "/stats?timezone=Asia%2FTokyo",
+            &access_token,
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["until"], today.to_string());
+        assert_eq!(body["timezone"], "Asia/Tokyo");
+        assert_eq!(body["totalPages"], 100);
+        assert_eq!(body["readingDays"], 1);
+        assert_eq!(body["averagePagesPerReadingDay"], 100.0);
+        assert_eq!(body["currentStreak"], 1);
+        assert_eq!(body["longestStreak"], 1);
+
+        let pages_per_day = body["pagesPerDay"].as_array().unwrap();
+        assert_eq!(pages_per_day.len(), 30);
+        assert_eq!(pages_per_day[29]["pages"], 100);
+        assert_eq!(pages_per_day[28]["cumulativePages"], 0);
+        let pages_per_month = body["pagesPerMonth"].as_array().unwrap();
+        assert_eq!(pages_per_month.last().unwrap()["cumulativePages"], 100);
+
+        // 最後まで読んだので読了になっている
+        let books = body["booksFinishedPerMonth"].as_array().unwrap();
+        assert_eq!(books.last().unwrap()["books"], 1);
+    }
+
+    #[tokio::test]
+    async fn reading_stats_out_of_range() {
+        let (app, access_token) = test_app().await;
+
+        let (status, _) = call(
+            &app,
+            Method::POST,
+            "/records",
+            &access_token,
+            json!({"bookId": 1, "startPage": 1, "endPage": 30}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call(
+            &app,
+            Method::GET,
+            "/stats?since=2000-01-01&until=2000-01-31",
+            &access_token,
+            Value::Null,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["totalPages"], 0);
+        assert_eq!(body["averagePagesPerDay"], 0.0);
+        assert_eq!(body["currentStreak"], 0);
+        assert_eq!(body["pagesPerWeek"][0]["start"], "1999-12-27");
+    }
+
+    #[tokio::test]
+    async fn reading_stats_with_invalid_query() {
+        let (app, access_token) = test_app().await;
+
+        for (uri, field) in [
+            ("/stats?timezone=Mars%2FOlympus", "timezone"),
+            ("/stats?since=2022-02-30", "since"),
+            ("/stats?since=2022-01-02&until=2022-01-01", "since"),
+            ("/stats?since=2020-01-01&until=2022-01-01", "since"),
+        ] {
+            let (status, body) = call(&app, Method::GET, uri, &access_token, Value::Null).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+            assert_eq!(body["fields"][0]["field"], field, "{}", uri);
+        }
+
+        let (status, _) = call(&app, Method::GET, "/stats", "invalid", Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
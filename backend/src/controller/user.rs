@@ -1,18 +1,20 @@
 use axum::{
-    extract::{Extension, TypedHeader},
+    extract::{Path, Query, TypedHeader},
+    http::StatusCode,
     response::{Headers, IntoResponse},
-    routing::post,
+    routing::{delete, get, post},
     Json, Router,
 };
 use headers::Cookie;
 use serde_json::{json, Value};
 
-use crate::controller::models::{LoginExtract, SignUpExtract};
+use crate::controller::models::{ClientInfoExtract, LoginExtract, LogoutQuery, SignUpExtract};
 use crate::domain::entity::user::{
     AccessToken, LoginError, RefreshToken, RefreshTokenError, RefreshTokenExtract, SignUpError,
 };
-use crate::domain::service::user::UserService;
+use crate::domain::service::user::{UserId, UserService};
 use crate::settings::Settings;
+use crate::state::AppState;
 
 pub fn user_app() -> Router {
     Router::new()
@@ -20,6 +22,9 @@ pub fn user_app() -> Router {
         .route("/login", post(login))
         .route("/signup", post(sign_up))
         .route("/token", post(refresh_tokens))
+        .route("/token/logout", post(logout))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", delete(revoke_session))
 }
 
 async fn make_login_session(user_service: UserService) -> Result<Json<Value>, LoginError> {
@@ -32,216 +37,238 @@ async fn make_login_session(user_service: UserService) -> Result<Json<Value>, Lo
     })
 }
 
+/// 以前のrefresh tokenのクッキーのパス。
+/// 同じ名前のクッキーが`/v1`以下のすべてに送られ続けないよう、発行と削除のたびに削除させる
+const LEGACY_REFRESH_TOKEN_COOKIE_PATH: &str = "/v1";
+
+/// 以前のパスで保存されたrefresh tokenを削除させるクッキー
+fn expired_legacy_cookie(settings: &Settings) -> Option<(&'static str, String)> {
+    (settings.refresh_token_cookie_path != LEGACY_REFRESH_TOKEN_COOKIE_PATH).then(|| {
+        (
+            "Set-Cookie",
+            RefreshToken::expired_cookie_value(
+                &settings.refresh_token_cookie_name,
+                LEGACY_REFRESH_TOKEN_COOKIE_PATH,
+            ),
+        )
+    })
+}
+
 fn response_from_tokens(
-    cookie_name: &str,
+    settings: &Settings,
     refresh_token: RefreshToken,
     access_token: AccessToken,
 ) -> impl IntoResponse {
-    (
-        Headers(vec![(
-            "Set-Cookie",
-            refresh_token.into_cookie_value(cookie_name, "/token"),
-        )]),
-        access_token.0,
-    )
+    let mut headers = vec![(
+        "Set-Cookie",
+        refresh_token.into_cookie_value(
+            &settings.refresh_token_cookie_name,
+            &settings.refresh_token_cookie_path,
+        ),
+    )];
+    headers.extend(expired_legacy_cookie(settings));
+    (Headers(headers), access_token.0)
+}
+
+fn refresh_token_from_cookie(
+    settings: &Settings,
+    cookie: Option<TypedHeader<Cookie>>,
+) -> Result<RefreshTokenExtract, RefreshTokenError> {
+    let TypedHeader(cookie) = cookie.ok_or(RefreshTokenError::InvalidRefreshToken)?;
+    cookie
+        .get(&settings.refresh_token_cookie_name)
+        .map(|value| RefreshTokenExtract(value.to_string()))
+        .ok_or(RefreshTokenError::InvalidRefreshToken)
 }
 
 async fn login(
     user_service: UserService,
+    ClientInfoExtract(client): ClientInfoExtract,
     Json(payload): Json<LoginExtract>,
-    Extension(settings): Extension<Settings>,
+    AppState {
+        settings, metrics, ..
+    }: AppState,
 ) -> Result<impl IntoResponse, LoginError> {
     user_service
-        .login(payload.session_id, payload.code)
+        .login(payload.session_id, payload.code, client)
         .await
-        .map(|ts| response_from_tokens(&settings.refresh_token_cookie_name, ts.0, ts.1))
+        .map(|ts| {
+            metrics.logins.inc();
+            response_from_tokens(&settings, ts.0, ts.1)
+        })
 }
 
 async fn sign_up(
     user_service: UserService,
+    ClientInfoExtract(client): ClientInfoExtract,
     Json(payload): Json<SignUpExtract>,
-    Extension(settings): Extension<Settings>,
+    AppState {
+        settings, metrics, ..
+    }: AppState,
 ) -> Result<impl IntoResponse, SignUpError> {
     user_service
-        .sign_up(payload.code, payload.user)
+        .sign_up(payload.code, payload.user, client)
         .await
-        .map(|ts| response_from_tokens(&settings.refresh_token_cookie_name, ts.0, ts.1))
+        .map(|ts| {
+            metrics.sign_ups.inc();
+            response_from_tokens(&settings, ts.0, ts.1)
+        })
 }
 
 async fn refresh_tokens(
     user_service: UserService,
+    ClientInfoExtract(client): ClientInfoExtract,
     cookie: Option<TypedHeader<Cookie>>,
-    Extension(settings): Extension<Settings>,
+    AppState {
+        settings, metrics, ..
+    }: AppState,
 ) -> Result<impl IntoResponse, RefreshTokenError> {
-    let refresh_token_value = if let Some(TypedHeader(cookie)) = cookie {
-        cookie
-            .get(&settings.refresh_token_cookie_name)
-            .ok_or(RefreshTokenError::InvalidRefreshToken)?
-            .to_owned()
-    } else {
-        return Err(RefreshTokenError::InvalidRefreshToken);
-    };
+    let refresh_token = refresh_token_from_cookie(&settings, cookie)?;
 
     user_service
-        .refresh_tokens(RefreshTokenExtract(refresh_token_value.to_string()))
+        .refresh_tokens(refresh_token, client)
         .await
-        .map(|ts| response_from_tokens(&settings.refresh_token_cookie_name, ts.0, ts.1))
+        .map(|ts| {
+            metrics.refresh_rotations.inc();
+            response_from_tokens(&settings, ts.0, ts.1)
+        })
+}
+
+async fn logout(
+    user_service: UserService,
+    cookie: Option<TypedHeader<Cookie>>,
+    Query(query): Query<LogoutQuery>,
+    AppState { settings, .. }: AppState,
+) -> Result<impl IntoResponse, RefreshTokenError> {
+    let refresh_token = refresh_token_from_cookie(&settings, cookie);
+
+    if query.everywhere {
+        // 持ち主が特定できないと全端末のログアウトはできない
+        user_service.logout_everywhere(refresh_token?).await?;
+    } else if let Ok(refresh_token) = refresh_token {
+        // 既に無効なtokenであってもクッキーは削除させる
+        if let Err(RefreshTokenError::Other) = user_service.logout(refresh_token).await {
+            return Err(RefreshTokenError::Other);
+        }
+    }
+
+    let mut headers = vec![(
+        "Set-Cookie",
+        RefreshToken::expired_cookie_value(
+            &settings.refresh_token_cookie_name,
+            &settings.refresh_token_cookie_path,
+        ),
+    )];
+    headers.extend(expired_legacy_cookie(&settings));
+    Ok(Headers(headers))
+}
+
+async fn list_sessions(
+    user_service: UserService,
+    UserId(user_id): UserId,
+) -> Result<Json<Value>, RefreshTokenError> {
+    user_service.list_sessions(user_id).await.map(|sessions| {
+        Json(json!({
+            "sessions": sessions,
+        }))
+    })
+}
+
+async fn revoke_session(
+    user_service: UserService,
+    UserId(user_id): UserId,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, RefreshTokenError> {
+    let result = user_service.revoke_session(user_id, &session_id).await?;
+    if result {
+        Ok(StatusCode::OK)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::sync::Arc;
 
     use axum::{
         body::Body,
         http::{self, Method, Request, StatusCode},
         AddExtensionLayer,
     };
-    use openidconnect::core::{CoreClient, CoreProviderMetadata};
-    use openidconnect::reqwest::async_http_client;
-    use openidconnect::{ClientId, ClientSecret, IssuerUrl, RedirectUrl};
-    use sqlx::postgres::PgPool;
     use tower::ServiceExt;
 
     use super::*;
-    use crate::settings::Settings;
-
-    async fn test_app() -> Router {
-        let settings = envy::from_env::<Settings>().unwrap();
-
-        assert_eq!(settings.refresh_exp, 20);
+    use crate::infra::repo::memory::user::InMemoryUserRepository;
 
-        // repository層の外部アクセス先の初期化
-        let pg_pool = PgPool::connect(&settings.database_url).await.unwrap();
-        let redis_cli = redis::Client::open(settings.redis_url.to_owned()).unwrap();
-
-        // IdPの設定初期化
-        let provider_metadata = CoreProviderMetadata::discover_async(
-            IssuerUrl::new(settings.id_provider_url.to_owned())
-                .expect("initialization error: failed in parsing the URL of IdP"),
-            async_http_client,
-        )
-        .await
-        .unwrap();
-
-        let id_cli = CoreClient::from_provider_metadata(
-            provider_metadata,
-            ClientId::new(settings.id_provider_client_id.to_owned()),
-            Some(ClientSecret::new(
-                settings.id_provider_client_secret.to_owned(),
-            )),
-        )
-        .set_redirect_uri(RedirectUrl::new(settings.id_provider_redirect_url.to_owned()).unwrap());
+    // IdPを介した成功シナリオはe2eで確認する
 
-        user_app()
-            .layer(AddExtensionLayer::new(settings))
-            .layer(AddExtensionLayer::new(id_cli))
-            .layer(AddExtensionLayer::new(pg_pool))
-            .layer(AddExtensionLayer::new(redis_cli))
-    }
-
-    // ログイン～ユーザ作成の成功シナリオ
+    // /login に無効なsession_idを送る
     #[tokio::test]
-    #[ignore]
-    async fn login() {
-        let app = test_app().await;
+    async fn login_nonexistent_session() {
+        let (app, _) = in_memory_app();
 
+        // 無効なsession_id
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
-                    .uri("/login-session")
-                    .body(Body::empty())
+                    .uri("/login")
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        serde_json::to_vec(
+                            &json!({"session_id": "nonexistent", "code": "invalid"}),
+                        )
+                        .unwrap(),
+                    ))
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        // 403が返ってくる
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let body: Value = serde_json::from_slice(&body).unwrap();
-        assert!(body["code_challenge"].is_string());
-        assert!(body["nonce"].is_string());
-        assert!(body["session_id"].is_string());
 
-        // 後段で使うレスポンス
-        let _code_challenge = body["code_challenge"].as_str().unwrap();
-        let nonce = body["nonce"].as_str().unwrap();
-        let session_id = body["session_id"].as_str().unwrap();
-
-        // IdPモックに依存するログイン処理
-        let state = "dummy";
-        let redirect_uri = "http://localhost:8000";
-        let subject = "testsub";
-        let email = "test@example.jp";
-        let params = [
-            ("nonce", nonce),
-            ("state", state),
-            ("redirect_uri", redirect_uri),
-            ("sub", subject),
-            ("email", email),
-        ];
-
-        let reqwest_cli = reqwest::Client::builder()
-            .redirect(reqwest::redirect::Policy::none())
-            .build()
-            .unwrap();
-        let res = reqwest_cli
-            .post("http://localhost:8001/auth")
-            .form(&params)
-            .send()
-            .await
-            .unwrap();
-        let url = reqwest::Url::parse(res.headers()[reqwest::header::LOCATION].to_str().unwrap())
-            .unwrap();
-        let pairs = url
-            .query_pairs()
-            .into_owned()
-            .collect::<HashMap<String, String>>();
+        // 空文字が返ってくる
+        assert!(body["error"].is_string());
+        assert_eq!(body["error"].as_str().unwrap(), "");
+    }
 
-        // 後段で使うレスポンス
-        let code = &pairs["code"];
+    // /login に有効なsession_idと無効なコードを送る
+    #[tokio::test]
+    async fn login_invalid_code() {
+        let (app, _) = in_memory_app();
 
-        // codeを使ってログイン処理
         let response = app
             .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
-                    .uri("/login")
-                    .header(http::header::CONTENT_TYPE, "application/json")
-                    .body(Body::from(
-                        serde_json::to_vec(&json!({"session_id": session_id, "code": code}))
-                            .unwrap(),
-                    ))
+                    .uri("/login-session")
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // ユーザがいないのでsign upコードが返ってくる
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
-
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let body: Value = serde_json::from_slice(&body).unwrap();
-        assert!(body["error"].is_string());
 
         // 後段で使うレスポンス
-        let code = body["error"].as_str().unwrap();
+        let session_id = body["session_id"].as_str().unwrap();
 
-        // session_idは使い捨てなので、
-        // 同じsession_idを使うことはできない
+        // 無効なコード
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
                     .uri("/login")
                     .header(http::header::CONTENT_TYPE, "application/json")
                     .body(Body::from(
-                        serde_json::to_vec(&json!({"session_id": session_id, "code": code}))
+                        serde_json::to_vec(&json!({"session_id": session_id, "code": "invalid"}))
                             .unwrap(),
                     ))
                     .unwrap(),
@@ -249,275 +276,327 @@ mod tests {
             .await
             .unwrap();
 
+        // 無効なコードでは403が返ってくる
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let body: Value = serde_json::from_slice(&body).unwrap();
+
+        // 無効なコードでは空文字が返ってくる
         assert!(body["error"].is_string());
         assert_eq!(body["error"].as_str().unwrap(), "");
+    }
 
-        let username = "testname";
+    // /signup に無効なcodeを送る
+    #[tokio::test]
+    async fn signup_nonexistent_code() {
+        let (app, _) = in_memory_app();
 
-        // codeを使ってsign up処理
+        // 無効なcode
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
                     .uri("/signup")
                     .header(http::header::CONTENT_TYPE, "application/json")
                     .body(Body::from(
-                        serde_json::to_vec(&json!({"code": code, "user": {"username": username}}))
-                            .unwrap(),
+                        serde_json::to_vec(
+                            &json!({"code": "nonexistent", "user": {"username": "dummy"}}),
+                        )
+                        .unwrap(),
                     ))
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // ユーザ作成成功
-        assert_eq!(response.status(), StatusCode::OK);
-        assert!(response.headers().contains_key(hyper::header::SET_COOKIE));
-        let set_cookie = &response.headers()[hyper::header::SET_COOKIE];
-        let refresh_token = set_cookie
-            .to_str()
-            .unwrap()
-            .split(|a| a == ';')
-            .next()
-            .unwrap()
-            .to_owned();
-        // クッキーを設定していることを確認
-        assert_ne!(refresh_token, "");
+        // 403が返ってくる
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        // アクセストークン
-        // 空文字ではないことだけチェック
-        let access_token = String::from_utf8_lossy(&body);
-        assert_ne!(access_token, "");
+        assert!(body.is_empty());
+    }
 
-        // 同じcodeを使ってsign upすることはできない
+    // /token にクッキーなしでアクセス
+    #[tokio::test]
+    async fn refresh_token_with_no_cookie() {
+        let (app, _) = in_memory_app();
+
+        // クッキーなし
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
-                    .uri("/signup")
-                    .header(http::header::CONTENT_TYPE, "application/json")
-                    .body(Body::from(
-                        serde_json::to_vec(&json!({"code": code, "user": {"username": username}}))
-                            .unwrap(),
-                    ))
+                    .uri("/token")
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // ユーザ作成失敗
+        // 403が返ってくる
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-        // refreshトークンを使って、トークンのリフレッシュ
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+    }
+
+    // /token に無効なクッキーでアクセス
+    #[tokio::test]
+    async fn refresh_token_with_invalid_cookie() {
+        let (app, _) = in_memory_app();
+
+        // 無効なクッキー
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
-                    .header(http::header::COOKIE, refresh_token.to_owned())
                     .uri("/token")
+                    .header(http::header::COOKIE, "refresh_token=invalid")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
-        // 新しいrefreshトークンが届く
-        assert!(response.headers().contains_key(hyper::header::SET_COOKIE));
+        // 403が返ってくる
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        // アクセストークンも届いている
-        assert_ne!(String::from_utf8_lossy(&body), "");
+        assert!(body.is_empty());
+    }
+
+    // /token/logout にクッキーなしでアクセス
+    #[tokio::test]
+    async fn logout_with_no_cookie() {
+        let (app, _) = in_memory_app();
 
-        // 同じrefreshトークンを使うことはできない
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
-                    .header(http::header::COOKIE, refresh_token)
-                    .uri("/token")
+                    .uri("/token/logout")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        // 何も無効にするものがなくても成功し、クッキーを削除させる
+        assert_eq!(response.status(), StatusCode::OK);
+        let set_cookies: Vec<_> = response
+            .headers()
+            .get_all(hyper::header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(set_cookies.len(), 2);
+        assert!(set_cookies[0].starts_with("refresh_token=;"));
+        assert!(set_cookies[0].contains("Max-Age=0"));
+        assert!(set_cookies[0].contains("Path=/v1/token;"));
+        // 以前のパスで保存されたクッキーも削除させる
+        assert!(set_cookies[1].starts_with("refresh_token=;"));
+        assert!(set_cookies[1].contains("Max-Age=0"));
+        assert!(set_cookies[1].contains("Path=/v1;"));
     }
 
-    // /login に無効なsession_idを送る
+    // /token/logout?everywhere=true に無効なクッキーでアクセス
     #[tokio::test]
-    #[ignore]
-    async fn login_nonexistent_session() {
-        let app = test_app().await;
+    async fn logout_everywhere_with_invalid_cookie() {
+        let (app, _) = in_memory_app();
 
-        // 無効なsession_id
         let response = app
             .oneshot(
                 Request::builder()
                     .method(Method::POST)
-                    .uri("/login")
-                    .header(http::header::CONTENT_TYPE, "application/json")
-                    .body(Body::from(
-                        serde_json::to_vec(
-                            &json!({"session_id": "nonexistent", "code": "invalid"}),
-                        )
-                        .unwrap(),
-                    ))
+                    .uri("/token/logout?everywhere=true")
+                    .header(http::header::COOKIE, "refresh_token=invalid")
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // 403が返ってくる
+        // 持ち主が分からないので403が返ってくる
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body: Value = serde_json::from_slice(&body).unwrap();
-
-        // 空文字が返ってくる
-        assert!(body["error"].is_string());
-        assert_eq!(body["error"].as_str().unwrap(), "");
     }
 
-    // /login に有効なsession_idと無効なコードを送る
+    // /sessions にaccess tokenなしでアクセス
     #[tokio::test]
-    #[ignore]
-    async fn login_invalid_code() {
-        let app = test_app().await;
+    async fn list_sessions_without_access_token() {
+        let (app, _) = in_memory_app();
 
         let response = app
-            .to_owned()
             .oneshot(
                 Request::builder()
-                    .method(Method::POST)
-                    .uri("/login-session")
+                    .method(Method::GET)
+                    .uri("/sessions")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
+        // 401が返ってくる
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    /// Postgres, Redis, IdPを使わないアプリ。
+    /// 返却したrepositoryからIdPの代わりに認可コードを発行する。
+    fn in_memory_app() -> (Router, Arc<InMemoryUserRepository>) {
+        let (state, user_repository) = AppState::in_memory(envy::from_iter(vec![]).unwrap());
+        (
+            user_app().layer(AddExtensionLayer::new(state)),
+            user_repository,
+        )
+    }
+
+    fn post(uri: &str, cookie: Option<&str>, body: Option<Value>) -> Request<Body> {
+        let mut builder = Request::builder().method(Method::POST).uri(uri);
+        if let Some(cookie) = cookie {
+            builder = builder.header(http::header::COOKIE, cookie);
+        }
+        match body {
+            Some(body) => builder
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(&body).unwrap())),
+            None => builder.body(Body::empty()),
+        }
+        .unwrap()
+    }
+
+    fn cookie_of(response: &http::Response<axum::body::BoxBody>) -> String {
+        response.headers()[hyper::header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_owned()
+    }
+
+    /// ログインからsign upまでを行い、refresh tokenのクッキーを返す
+    async fn sign_up_in_memory(app: &Router, user_repository: &InMemoryUserRepository) -> String {
+        let response = app
+            .to_owned()
+            .oneshot(post("/login-session", None, None))
+            .await
+            .unwrap();
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let body: Value = serde_json::from_slice(&body).unwrap();
-
-        // 後段で使うレスポンス
         let session_id = body["session_id"].as_str().unwrap();
+        let code = user_repository.issue_authorization_code(body["nonce"].as_str().unwrap(), "sub");
 
-        // 無効なコード
         let response = app
-            .oneshot(
-                Request::builder()
-                    .method(Method::POST)
-                    .uri("/login")
-                    .header(http::header::CONTENT_TYPE, "application/json")
-                    .body(Body::from(
-                        serde_json::to_vec(&json!({"session_id": session_id, "code": "invalid"}))
-                            .unwrap(),
-                    ))
-                    .unwrap(),
-            )
+            .to_owned()
+            .oneshot(post(
+                "/login",
+                None,
+                Some(json!({"session_id": session_id, "code": code})),
+            ))
             .await
             .unwrap();
-
-        // 無効なコードでは403が返ってくる
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
-
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
         let body: Value = serde_json::from_slice(&body).unwrap();
+        let code = body["error"].as_str().unwrap();
 
-        // 無効なコードでは空文字が返ってくる
-        assert!(body["error"].is_string());
-        assert_eq!(body["error"].as_str().unwrap(), "");
+        let response = app
+            .to_owned()
+            .oneshot(post(
+                "/signup",
+                None,
+                Some(json!({"code": code, "user": {"username": "name"}})),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        cookie_of(&response)
     }
 
-    // /signup に無効なcodeを送る
+    // メモリ上のrepositoryでのログイン～リフレッシュの成功シナリオ
     #[tokio::test]
-    #[ignore]
-    async fn signup_nonexistent_code() {
-        let app = test_app().await;
+    async fn login_in_memory() {
+        let (app, user_repository) = in_memory_app();
+        let refresh_token = sign_up_in_memory(&app, &user_repository).await;
 
-        // 無効なcode
         let response = app
-            .oneshot(
-                Request::builder()
-                    .method(Method::POST)
-                    .uri("/signup")
-                    .header(http::header::CONTENT_TYPE, "application/json")
-                    .body(Body::from(
-                        serde_json::to_vec(
-                            &json!({"code": "nonexistent", "user": {"username": "dummy"}}),
-                        )
-                        .unwrap(),
-                    ))
-                    .unwrap(),
-            )
+            .to_owned()
+            .oneshot(post("/token", Some(&refresh_token), None))
             .await
             .unwrap();
-
-        // 403が返ってくる
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
-
+        assert_eq!(response.status(), StatusCode::OK);
+        let rotated = cookie_of(&response);
+        let legacy = response
+            .headers()
+            .get_all(hyper::header::SET_COOKIE)
+            .iter()
+            .nth(1)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(legacy.contains("Max-Age=0; Path=/v1;"));
         let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        assert!(body.is_empty());
-    }
-
-    // /token にクッキーなしでアクセス
-    #[tokio::test]
-    #[ignore]
-    async fn refresh_token_with_no_cookie() {
-        let app = test_app().await;
+        let access_token = String::from_utf8_lossy(&body).to_string();
 
-        // クッキーなし
         let response = app
+            .to_owned()
             .oneshot(
                 Request::builder()
-                    .method(Method::POST)
-                    .uri("/token")
+                    .uri("/sessions")
+                    .header(
+                        http::header::AUTHORIZATION,
+                        format!("Bearer {}", access_token),
+                    )
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["sessions"].as_array().unwrap().len(), 1);
+
+        // ログアウトするとrefresh tokenは使えなくなる
+        let response = app
+            .to_owned()
+            .oneshot(post("/token/logout", Some(&rotated), None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-        // 403が返ってくる
+        let response = app
+            .oneshot(post("/token", Some(&rotated), None))
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
-
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        assert!(body.is_empty());
     }
 
-    // /token に無効なクッキーでアクセス
+    // ローテーション済みのrefresh tokenを再利用すると、セッションごと無効になる
     #[tokio::test]
-    #[ignore]
-    async fn refresh_token_with_invalid_cookie() {
-        let app = test_app().await;
+    async fn refresh_token_reuse_in_memory() {
+        let (app, user_repository) = in_memory_app();
+        let refresh_token = sign_up_in_memory(&app, &user_repository).await;
 
-        // 無効なクッキー
         let response = app
-            .oneshot(
-                Request::builder()
-                    .method(Method::POST)
-                    .uri("/token")
-                    .header(http::header::COOKIE, "refresh_token=invalid")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
+            .to_owned()
+            .oneshot(post("/token", Some(&refresh_token), None))
             .await
             .unwrap();
+        let rotated = cookie_of(&response);
 
-        // 403が返ってくる
+        let response = app
+            .to_owned()
+            .oneshot(post("/token", Some(&refresh_token), None))
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        assert!(body.is_empty());
+        let response = app
+            .oneshot(post("/token", Some(&rotated), None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 }
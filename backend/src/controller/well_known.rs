@@ -0,0 +1,13 @@
+use axum::{routing::get, Json, Router};
+
+use crate::infra::keys::JsonWebKeySet;
+use crate::state::AppState;
+
+/// `/v1`の外に公開する、他のサービス向けのエンドポイント
+pub fn well_known_app() -> Router {
+    Router::new().route("/.well-known/jwks.json", get(jwks))
+}
+
+async fn jwks(state: AppState) -> Json<JsonWebKeySet> {
+    Json(state.keys.jwks().clone())
+}
@@ -1,5 +1,10 @@
 pub mod book;
+pub mod goal;
+pub mod record;
+pub mod shelf;
+pub mod stats;
 pub mod user;
+pub mod validation;
 
 use axum::{
     http::{
@@ -14,10 +19,9 @@ pub type Pid = u32;
 
 #[derive(Debug)]
 pub enum AxumError {
-    PgConnectionError,
-    RedisConnectionError,
     MissingAccessToken,
     InvalidAccessToken,
+    ExpiredAccessToken,
     OtherError(String),
 }
 
@@ -27,16 +31,6 @@ impl IntoResponse for AxumError {
 
     fn into_response(self) -> Response<Self::Body> {
         let (status, headers, error_message) = match self {
-            AxumError::PgConnectionError => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                HeaderMap::new(),
-                "Connecting Postgres error".to_string(),
-            ),
-            AxumError::RedisConnectionError => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                HeaderMap::new(),
-                "Connecting Redis error".to_string(),
-            ),
             AxumError::MissingAccessToken => {
                 let mut headers = HeaderMap::new();
                 headers.insert(
@@ -53,6 +47,16 @@ impl IntoResponse for AxumError {
                 );
                 (StatusCode::UNAUTHORIZED, headers, String::new())
             }
+            AxumError::ExpiredAccessToken => {
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    HeaderName::from_static("www-authenticate"),
+                    HeaderValue::from_static(
+                        "Bearer error=\"invalid_token\", error_description=\"expired\"",
+                    ),
+                );
+                (StatusCode::UNAUTHORIZED, headers, String::new())
+            }
             AxumError::OtherError(message) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new(), message)
             }
@@ -1,23 +1,564 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use axum::{
+    http::{Response, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-use super::Pid;
+use super::{validation::ValidationError, Pid};
+use crate::domain::repo_if::RepoError;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BookEntity {
     pub id: Pid,
     pub title: String,
+    pub authors: Vec<String>,
+    pub isbn: Option<Isbn>,
+    pub publisher: Option<String>,
+    pub page_count: Option<i32>,
+    /// BCP 47の言語タグ（"ja", "en"など）
+    pub language: Option<String>,
+    pub published_date: Option<NaiveDate>,
+    /// 登録日時。更新では変わらない。
+    pub created_at: DateTime<Utc>,
+    /// 登録したユーザ。このユーザだけが編集・削除できる。登録者の分からない古い本では`None`。
+    pub created_by: Option<Pid>,
+}
+
+impl BookEntity {
+    /// 登録者の分からない古い本は、ログインしているユーザなら誰でも編集・削除できる。
+    /// 他のユーザの読書記録などが消えないよう、削除はrepositoryでも確認する。
+    pub fn can_be_edited_by(&self, user_id: Pid) -> bool {
+        self.created_by
+            .is_none_or(|created_by| created_by == user_id)
+    }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BookEntityForCreation {
     pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// `validate`でチェックディジットを検証し、ISBN-13に正規化する
+    pub isbn: Option<String>,
+    pub publisher: Option<String>,
+    pub page_count: Option<i32>,
+    pub language: Option<String>,
+    pub published_date: Option<NaiveDate>,
+}
+
+impl BookEntityForCreation {
+    /// 不正な値をフィールドごとにまとめて返す。ISBNは正規化した値に置き換える。
+    pub fn validate(&mut self) -> Result<(), ValidationError> {
+        let mut error = ValidationError::new();
+        if self.title.trim().is_empty() {
+            error.add("title", "must not be empty");
+        }
+        if let Some(isbn) = &self.isbn {
+            match Isbn::parse(isbn) {
+                Ok(isbn) => self.isbn = Some(isbn.into()),
+                Err(err) => error.add("isbn", err.to_string()),
+            }
+        }
+        if matches!(self.page_count, Some(page_count) if page_count < 1) {
+            error.add("pageCount", "must be 1 or more");
+        }
+        error.into_result()
+    }
+}
+
+pub enum BookError {
+    Invalid(ValidationError),
+    /// 他のユーザが登録した本を編集・削除しようとした
+    Forbidden,
+    Repo(RepoError),
+}
+
+impl From<ValidationError> for BookError {
+    fn from(error: ValidationError) -> Self {
+        BookError::Invalid(error)
+    }
+}
+
+impl From<RepoError> for BookError {
+    fn from(error: RepoError) -> Self {
+        BookError::Repo(error)
+    }
 }
 
+impl IntoResponse for BookError {
+    type Body = <(StatusCode, Json<Value>) as IntoResponse>::Body;
+    type BodyError = <(StatusCode, Json<Value>) as IntoResponse>::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        match self {
+            BookError::Invalid(error) => error.into_response(),
+            BookError::Forbidden => {
+                let body = Json(json!({
+                    "error": "forbidden",
+                }));
+                (StatusCode::FORBIDDEN, body).into_response()
+            }
+            BookError::Repo(error) => error.into_response(),
+        }
+    }
+}
+
+/// `created_at`には現在時刻が入り、`created_by`は空になる。
+/// 更新に使う場合、repositoryはこれらの値を無視する。
 impl From<(Pid, BookEntityForCreation)> for BookEntity {
     fn from((id, book): (u32, BookEntityForCreation)) -> BookEntity {
         Self {
             id,
             title: book.title,
+            authors: book.authors,
+            isbn: book.isbn.and_then(|isbn| Isbn::parse(&isbn).ok()),
+            publisher: book.publisher,
+            page_count: book.page_count,
+            language: book.language,
+            published_date: book.published_date,
+            created_at: Utc::now(),
+            created_by: None,
+        }
+    }
+}
+
+/// 本一覧の並び替えのキー。同じ値の本はIDの順に並べる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSortKey {
+    Id,
+    Title,
+    CreatedAt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// 本一覧の取得条件
+#[derive(Debug, Clone)]
+pub struct BookListQuery {
+    pub limit: u32,
+    pub sort: BookSortKey,
+    pub order: SortOrder,
+    /// 前のページの最後の本。これより後ろの本から返す。
+    pub after: Option<BookCursor>,
+    /// 条件に合う本の総数も数える
+    pub with_total: bool,
+    /// このユーザの本棚にある本だけに絞り込む
+    pub library_of: Option<Pid>,
+}
+
+impl Default for BookListQuery {
+    fn default() -> Self {
+        Self {
+            limit: 20,
+            sort: BookSortKey::Id,
+            order: SortOrder::Asc,
+            after: None,
+            with_total: false,
+            library_of: None,
+        }
+    }
+}
+
+/// ページの境界にある本の、並び替えのキーの値
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookCursorKey {
+    Id,
+    Title(String),
+    CreatedAt(DateTime<Utc>),
+}
+
+/// ページの境界にある本の位置。
+/// クライアントには`encode`した不透明な文字列として渡す。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookCursor {
+    pub key: BookCursorKey,
+    pub order: SortOrder,
+    pub id: Pid,
+}
+
+impl BookCursor {
+    /// `book`の直後から続きを取得するためのカーソル
+    pub fn after(book: &BookEntity, sort: BookSortKey, order: SortOrder) -> Self {
+        let key = match sort {
+            BookSortKey::Id => BookCursorKey::Id,
+            BookSortKey::Title => BookCursorKey::Title(book.title.to_owned()),
+            BookSortKey::CreatedAt => BookCursorKey::CreatedAt(book.created_at),
+        };
+        Self {
+            key,
+            order,
+            id: book.id,
         }
     }
+
+    pub fn sort(&self) -> BookSortKey {
+        match self.key {
+            BookCursorKey::Id => BookSortKey::Id,
+            BookCursorKey::Title(_) => BookSortKey::Title,
+            BookCursorKey::CreatedAt(_) => BookSortKey::CreatedAt,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        base64::encode_config(
+            serde_json::to_vec(self).expect("a cursor is always serializable"),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let bytes = base64::decode_config(value, base64::URL_SAFE_NO_PAD).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// 本一覧の1ページ
+#[derive(Debug)]
+pub struct BookPage {
+    pub books: Vec<BookEntity>,
+    /// 続きがあるときだけ返す
+    pub next: Option<BookCursor>,
+    pub total: Option<i64>,
+}
+
+/// 一度に検索できる語の数
+const MAX_SEARCH_TERMS: usize = 5;
+
+/// 本の検索条件。
+/// 空白で区切った語をすべて含む本を、タイトル・著者名・ISBNから探す。
+#[derive(Debug, Clone)]
+pub struct BookSearchQuery {
+    /// 重複を除いた、空でない語
+    pub terms: Vec<String>,
+    /// 検索文字列全体がISBNとして読める場合のISBN
+    pub isbn: Option<Isbn>,
+    pub limit: u32,
+}
+
+impl BookSearchQuery {
+    pub fn parse(q: &str, limit: u32) -> Result<Self, ValidationError> {
+        let mut terms: Vec<String> = Vec::new();
+        for term in q.split_whitespace() {
+            if !terms.iter().any(|other| other == term) {
+                terms.push(term.to_string());
+            }
+        }
+
+        let mut error = ValidationError::new();
+        if terms.is_empty() {
+            error.add("q", "must not be empty");
+        }
+        if terms.len() > MAX_SEARCH_TERMS {
+            error.add("q", format!("must have at most {} terms", MAX_SEARCH_TERMS));
+        }
+        error.into_result()?;
+
+        Ok(Self {
+            terms,
+            isbn: Isbn::parse(q).ok(),
+            limit,
+        })
+    }
+}
+
+/// 検索語に一致した範囲。値は文字（Unicodeのスカラー値）単位の`[開始, 終了)`。
+pub type Spans = Vec<(usize, usize)>;
+
+#[derive(Debug, Serialize)]
+pub struct BookHighlights {
+    pub title: Spans,
+    /// `BookEntity::authors`と同じ順序
+    pub authors: Vec<Spans>,
+}
+
+/// 検索結果の1件。関連度の高い順に並べて返す。
+#[derive(Debug, Serialize)]
+pub struct BookSearchHit {
+    pub book: BookEntity,
+    pub highlights: BookHighlights,
+}
+
+impl BookSearchHit {
+    pub fn new(book: BookEntity, terms: &[String]) -> Self {
+        let highlights = BookHighlights {
+            title: highlight(&book.title, terms),
+            authors: book
+                .authors
+                .iter()
+                .map(|author| highlight(author, terms))
+                .collect(),
+        };
+        Self { book, highlights }
+    }
+}
+
+/// `text`の中で`terms`のいずれかに大文字小文字を区別せず一致する範囲を、重なりや隣接をまとめて返す
+pub fn highlight(text: &str, terms: &[String]) -> Spans {
+    let fold = |s: &str| -> Vec<char> {
+        s.chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect()
+    };
+    let text = fold(text);
+
+    let mut spans: Spans = Vec::new();
+    for term in terms.iter().map(|term| fold(term)) {
+        if term.is_empty() || term.len() > text.len() {
+            continue;
+        }
+        for start in 0..=(text.len() - term.len()) {
+            if text[start..start + term.len()] == term[..] {
+                spans.push((start, start + term.len()));
+            }
+        }
+    }
+
+    spans.sort_unstable();
+    let mut merged: Spans = Vec::with_capacity(spans.len());
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// チェックディジットを検証済みのISBN。
+/// ISBN-10で与えられた場合もISBN-13に正規化して保持する。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Isbn(String);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IsbnError {
+    InvalidFormat,
+    InvalidCheckDigit,
+}
+
+impl fmt::Display for IsbnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsbnError::InvalidFormat => write!(f, "ISBN must have 10 or 13 digits"),
+            IsbnError::InvalidCheckDigit => write!(f, "ISBN check digit does not match"),
+        }
+    }
+}
+
+impl Isbn {
+    /// ハイフンや空白を含んでもよい。
+    pub fn parse(value: &str) -> Result<Self, IsbnError> {
+        let chars: Vec<char> = value
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .collect();
+
+        match chars.len() {
+            10 => Self::parse_isbn10(&chars),
+            13 => Self::parse_isbn13(&chars),
+            _ => Err(IsbnError::InvalidFormat),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    fn parse_isbn10(chars: &[char]) -> Result<Self, IsbnError> {
+        let mut digits = Vec::with_capacity(10);
+        for (i, c) in chars.iter().enumerate() {
+            match (c.to_digit(10), c) {
+                (Some(d), _) => digits.push(d),
+                // 末尾のチェックディジットだけは10をXで表す
+                (None, 'X') | (None, 'x') if i == 9 => digits.push(10),
+                _ => return Err(IsbnError::InvalidFormat),
+            }
+        }
+
+        let sum: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(i, d)| (10 - i as u32) * d)
+            .sum();
+        if sum % 11 != 0 {
+            return Err(IsbnError::InvalidCheckDigit);
+        }
+
+        // 978を前に付け、チェックディジットを計算し直す
+        let mut isbn13: Vec<u32> = vec![9, 7, 8];
+        isbn13.extend_from_slice(&digits[..9]);
+        isbn13.push(Self::isbn13_check_digit(&isbn13));
+        Ok(Self(isbn13.iter().map(|d| d.to_string()).collect()))
+    }
+
+    fn parse_isbn13(chars: &[char]) -> Result<Self, IsbnError> {
+        let digits = chars
+            .iter()
+            .map(|c| c.to_digit(10))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(IsbnError::InvalidFormat)?;
+
+        if Self::isbn13_check_digit(&digits[..12]) != digits[12] {
+            return Err(IsbnError::InvalidCheckDigit);
+        }
+        Ok(Self(digits.iter().map(|d| d.to_string()).collect()))
+    }
+
+    fn isbn13_check_digit(digits: &[u32]) -> u32 {
+        let sum: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+            .sum();
+        (10 - sum % 10) % 10
+    }
+}
+
+impl TryFrom<String> for Isbn {
+    type Error = IsbnError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value)
+    }
+}
+
+impl From<Isbn> for String {
+    fn from(isbn: Isbn) -> String {
+        isbn.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isbn13() {
+        let isbn = Isbn::parse("978-4-87311-565-8").unwrap();
+        assert_eq!(isbn.as_str(), "9784873115658");
+    }
+
+    #[test]
+    fn test_isbn10_is_normalized() {
+        let isbn = Isbn::parse("4-87311-565-X").unwrap_err();
+        assert_eq!(isbn, IsbnError::InvalidCheckDigit);
+
+        let isbn = Isbn::parse("4873115655").unwrap();
+        assert_eq!(isbn.as_str(), "9784873115658");
+
+        let isbn = Isbn::parse("0-8044-2957-X").unwrap();
+        assert_eq!(isbn.as_str(), "9780804429573");
+    }
+
+    #[test]
+    fn test_invalid_isbn() {
+        assert_eq!(
+            Isbn::parse("978-4-87311-565-9"),
+            Err(IsbnError::InvalidCheckDigit)
+        );
+        assert_eq!(Isbn::parse("978487311565"), Err(IsbnError::InvalidFormat));
+        assert_eq!(Isbn::parse("97848731156X8"), Err(IsbnError::InvalidFormat));
+    }
+
+    #[test]
+    fn test_can_be_edited_by() {
+        let book: BookEntityForCreation = serde_json::from_str(r#"{"title": "t"}"#).unwrap();
+        let mut book = BookEntity::from((1, book));
+        assert!(book.can_be_edited_by(2));
+
+        book.created_by = Some(1);
+        assert!(book.can_be_edited_by(1));
+        assert!(!book.can_be_edited_by(2));
+    }
+
+    #[test]
+    fn test_validate_book() {
+        let mut book: BookEntityForCreation =
+            serde_json::from_str(r#"{"title": "t", "pageCount": 300}"#).unwrap();
+        assert!(book.validate().is_ok());
+
+        book.title = " ".to_string();
+        book.isbn = Some("978-4-87311-565-9".to_string());
+        book.page_count = Some(0);
+        let fields: Vec<_> = book
+            .validate()
+            .unwrap_err()
+            .errors
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, vec!["title", "isbn", "pageCount"]);
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let book: BookEntity = (
+            3,
+            serde_json::from_str::<BookEntityForCreation>(r#"{"title": "t"}"#).unwrap(),
+        )
+            .into();
+
+        for sort in [BookSortKey::Id, BookSortKey::Title, BookSortKey::CreatedAt] {
+            let cursor = BookCursor::after(&book, sort, SortOrder::Desc);
+            assert_eq!(cursor.sort(), sort);
+            assert_eq!(BookCursor::decode(&cursor.encode()), Some(cursor));
+        }
+        assert_eq!(BookCursor::decode("invalid"), None);
+    }
+
+    #[test]
+    fn test_parse_search_query() {
+        let query = BookSearchQuery::parse(" rust  プログラミング rust ", 20).unwrap();
+        assert_eq!(query.terms, vec!["rust", "プログラミング"]);
+        assert!(query.isbn.is_none());
+
+        let query = BookSearchQuery::parse("978-4-87311-565-8", 20).unwrap();
+        assert_eq!(query.isbn.unwrap().as_str(), "9784873115658");
+
+        assert!(BookSearchQuery::parse("  ", 20).is_err());
+        assert!(BookSearchQuery::parse("a b c d e f", 20).is_err());
+    }
+
+    #[test]
+    fn test_highlight() {
+        let terms = vec!["rust".to_string(), "プログラミング".to_string()];
+        assert_eq!(
+            highlight("Rust入門とプログラミングRust", &terms),
+            vec![(0, 4), (7, 18)]
+        );
+
+        // 重なったり隣り合ったりする一致はまとめる
+        let terms = vec!["abc".to_string(), "bcd".to_string()];
+        assert_eq!(highlight("xabcde", &terms), vec![(1, 5)]);
+        assert!(highlight("xyz", &terms).is_empty());
+    }
+
+    #[test]
+    fn test_isbn_is_normalized_on_validation() {
+        let mut book: BookEntityForCreation =
+            serde_json::from_str(r#"{"title": "t", "isbn": "4873115655"}"#).unwrap();
+        assert!(book.validate().is_ok());
+        assert_eq!(book.isbn.as_deref(), Some("9784873115658"));
+        assert!(book.authors.is_empty());
+
+        // 不正なISBNでもデシリアライズはでき、検証でフィールドのエラーになる
+        let mut book: BookEntityForCreation =
+            serde_json::from_str(r#"{"title": "t", "isbn": "1"}"#).unwrap();
+        let error = book.validate().unwrap_err();
+        assert_eq!(error.errors[0].field, "isbn");
+    }
 }
@@ -0,0 +1,415 @@
+use axum::{
+    http::{Response, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+
+use super::{validation::ValidationError, Pid};
+use crate::domain::repo_if::RepoError;
+
+/// 目標で数えるもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalMetric {
+    /// 読み終えた本の数
+    Books,
+    /// 読書記録のページ数
+    Pages,
+}
+
+impl GoalMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalMetric::Books => "books",
+            GoalMetric::Pages => "pages",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [GoalMetric::Books, GoalMetric::Pages]
+            .into_iter()
+            .find(|metric| metric.as_str() == value)
+    }
+}
+
+/// 目標の期間
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalPeriod {
+    /// 1月1日から始まる1年
+    Year,
+    /// 1日から始まる1か月
+    Month,
+    /// 開始日から毎日。終わりはない
+    Day,
+}
+
+impl GoalPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GoalPeriod::Year => "year",
+            GoalPeriod::Month => "month",
+            GoalPeriod::Day => "day",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [GoalPeriod::Year, GoalPeriod::Month, GoalPeriod::Day]
+            .into_iter()
+            .find(|period| period.as_str() == value)
+    }
+
+    /// `date`を含む期間の初日
+    fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            GoalPeriod::Year => NaiveDate::from_ymd(date.year(), 1, 1),
+            GoalPeriod::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+            GoalPeriod::Day => date,
+        }
+    }
+}
+
+/// 「2026年に24冊」「毎日30ページ」のような読書の目標
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalEntity {
+    pub id: Pid,
+    pub user_id: Pid,
+    pub metric: GoalMetric,
+    pub period: GoalPeriod,
+    /// 期間あたりの目標の量
+    pub target: i32,
+    /// 期間の初日
+    pub starts_on: NaiveDate,
+    /// 日付の区切りに使うタイムゾーン
+    #[serde(serialize_with = "serialize_timezone")]
+    pub timezone: Tz,
+}
+
+fn serialize_timezone<S: Serializer>(timezone: &Tz, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(timezone.name())
+}
+
+impl GoalEntity {
+    /// 期間の最終日。毎日の目標には終わりがないので`None`になる。
+    pub fn ends_on(&self) -> Option<NaiveDate> {
+        let next = match self.period {
+            GoalPeriod::Year => NaiveDate::from_ymd(self.starts_on.year() + 1, 1, 1),
+            GoalPeriod::Month if self.starts_on.month() == 12 => {
+                NaiveDate::from_ymd(self.starts_on.year() + 1, 1, 1)
+            }
+            GoalPeriod::Month => {
+                NaiveDate::from_ymd(self.starts_on.year(), self.starts_on.month() + 1, 1)
+            }
+            GoalPeriod::Day => return None,
+        };
+        Some(next - Duration::days(1))
+    }
+
+    /// 目標のタイムゾーンでの今日の日付
+    pub fn today(&self, now: DateTime<Utc>) -> NaiveDate {
+        now.with_timezone(&self.timezone).naive_local().date()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalEntityForCreation {
+    pub metric: GoalMetric,
+    pub period: GoalPeriod,
+    pub target: i32,
+    /// 省略すると今日を含む期間の初日になる
+    pub starts_on: Option<NaiveDate>,
+    /// IANAのタイムゾーン名。省略するとUTC
+    pub timezone: Option<String>,
+}
+
+impl GoalEntityForCreation {
+    /// 検証して、省略された開始日とタイムゾーンを埋める。`id`はrepositoryが振るので0にしておく。
+    pub fn into_goal(
+        self,
+        user_id: Pid,
+        now: DateTime<Utc>,
+    ) -> Result<GoalEntity, ValidationError> {
+        let mut error = ValidationError::new();
+        if self.target < 1 {
+            error.add("target", "must be 1 or more");
+        }
+        let timezone = match self.timezone.as_deref().map(str::parse::<Tz>) {
+            None => Tz::UTC,
+            Some(Ok(timezone)) => timezone,
+            Some(Err(_)) => {
+                error.add("timezone", "must be an IANA time zone name");
+                Tz::UTC
+            }
+        };
+        let starts_on = match self.starts_on {
+            Some(starts_on) => {
+                if self.period.start_of(starts_on) != starts_on {
+                    error.add(
+                        "startsOn",
+                        format!("must be the first day of the {}", self.period.as_str()),
+                    );
+                }
+                starts_on
+            }
+            None => self
+                .period
+                .start_of(now.with_timezone(&timezone).naive_local().date()),
+        };
+        error.into_result()?;
+
+        Ok(GoalEntity {
+            id: 0,
+            user_id,
+            metric: self.metric,
+            period: self.period,
+            target: self.target,
+            starts_on,
+            timezone,
+        })
+    }
+}
+
+/// 目標に対して、今日までに達成しているべき量との比較
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalPace {
+    Ahead,
+    OnTrack,
+    Behind,
+}
+
+/// 目標の進み具合
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GoalProgress {
+    pub goal: GoalEntity,
+    /// 集計した期間の初日
+    pub since: NaiveDate,
+    /// 集計した期間の最終日。毎日の目標では今日になる
+    pub until: NaiveDate,
+    /// 期間内に達成した量
+    pub current: i64,
+    /// 期間全体での目標の量。毎日の目標では今日までの日数分になる
+    pub target: i64,
+    /// 今日までのペースで達成しているべき量
+    pub expected: f64,
+    /// 今日達成した量
+    pub today: i64,
+    pub percent: f64,
+    pub pace: GoalPace,
+    /// 目標を達成したか。毎日の目標では今日の分を達成したか
+    pub completed: bool,
+    /// 今のペースで期間の終わりまでに達成する量
+    pub projected_total: Option<i64>,
+    /// 今のペースで目標を達成する日。達成済みか、まだ何も達成していなければ`None`
+    pub projected_finish: Option<NaiveDate>,
+}
+
+impl GoalProgress {
+    /// `current`は`since`から今日までに達成した量、`today`は今日達成した量。
+    pub fn new(goal: GoalEntity, current: i64, today: i64, now: DateTime<Utc>) -> Self {
+        let today_date = goal.today(now);
+        let since = goal.starts_on;
+        let until = goal.ends_on().unwrap_or_else(|| today_date.max(since));
+        let total_days = (until - since).num_days() + 1;
+        let elapsed_days = ((today_date - since).num_days() + 1).clamp(0, total_days);
+
+        let (target, expected) = match goal.period {
+            GoalPeriod::Day => {
+                let target = goal.target as i64 * elapsed_days;
+                (target, target as f64)
+            }
+            GoalPeriod::Year | GoalPeriod::Month => {
+                let target = goal.target as i64;
+                (
+                    target,
+                    target as f64 * elapsed_days as f64 / total_days as f64,
+                )
+            }
+        };
+        // 数えるものは整数なので、期待値の前後の整数の間ならペース通りとする
+        let pace = if (current as f64) < expected.floor() {
+            GoalPace::Behind
+        } else if (current as f64) > expected.ceil() {
+            GoalPace::Ahead
+        } else {
+            GoalPace::OnTrack
+        };
+        let completed = match goal.period {
+            GoalPeriod::Day => elapsed_days > 0 && today >= goal.target as i64,
+            GoalPeriod::Year | GoalPeriod::Month => current >= target,
+        };
+
+        let rate = if elapsed_days > 0 {
+            current as f64 / elapsed_days as f64
+        } else {
+            0.0
+        };
+        let (projected_total, projected_finish) = match goal.period {
+            GoalPeriod::Day => (None, None),
+            GoalPeriod::Year | GoalPeriod::Month => {
+                let projected_total =
+                    (elapsed_days > 0).then_some((rate * total_days as f64) as i64);
+                let projected_finish = (!completed && rate > 0.0)
+                    .then(|| since + Duration::days((target as f64 / rate).ceil() as i64 - 1));
+                (projected_total, projected_finish)
+            }
+        };
+
+        Self {
+            goal,
+            since,
+            until,
+            current,
+            target,
+            expected,
+            today,
+            percent: if target > 0 {
+                current as f64 * 100.0 / target as f64
+            } else {
+                0.0
+            },
+            pace,
+            completed,
+            projected_total,
+            projected_finish,
+        }
+    }
+}
+
+pub enum GoalError {
+    Invalid(ValidationError),
+    Repo(RepoError),
+}
+
+impl From<ValidationError> for GoalError {
+    fn from(error: ValidationError) -> Self {
+        GoalError::Invalid(error)
+    }
+}
+
+impl From<RepoError> for GoalError {
+    fn from(error: RepoError) -> Self {
+        GoalError::Repo(error)
+    }
+}
+
+impl IntoResponse for GoalError {
+    type Body = <(StatusCode, Json<Value>) as IntoResponse>::Body;
+    type BodyError = <(StatusCode, Json<Value>) as IntoResponse>::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        match self {
+            GoalError::Invalid(error) => error.into_response(),
+            GoalError::Repo(error) => error.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn creation(period: GoalPeriod, target: i32) -> GoalEntityForCreation {
+        GoalEntityForCreation {
+            metric: GoalMetric::Books,
+            period,
+            target,
+            starts_on: None,
+            timezone: None,
+        }
+    }
+
+    /// 2026年4月1日の正午（UTC）
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2026, 4, 1).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn test_into_goal() {
+        let goal = creation(GoalPeriod::Year, 24).into_goal(1, now()).unwrap();
+        assert_eq!(goal.starts_on, NaiveDate::from_ymd(2026, 1, 1));
+        assert_eq!(goal.ends_on(), Some(NaiveDate::from_ymd(2026, 12, 31)));
+
+        let goal = GoalEntityForCreation {
+            starts_on: Some(NaiveDate::from_ymd(2026, 12, 1)),
+            ..creation(GoalPeriod::Month, 2)
+        }
+        .into_goal(1, now())
+        .unwrap();
+        assert_eq!(goal.ends_on(), Some(NaiveDate::from_ymd(2026, 12, 31)));
+
+        let error = GoalEntityForCreation {
+            starts_on: Some(NaiveDate::from_ymd(2026, 3, 2)),
+            timezone: Some("Mars/Olympus".to_string()),
+            ..creation(GoalPeriod::Year, 0)
+        }
+        .into_goal(1, now())
+        .unwrap_err();
+        let fields: Vec<&str> = error.errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["target", "timezone", "startsOn"]);
+    }
+
+    #[test]
+    fn test_yearly_progress() {
+        let goal = creation(GoalPeriod::Year, 24).into_goal(1, now()).unwrap();
+
+        // 4月1日は91日目なので、約6冊読んでいるはず
+        let progress = GoalProgress::new(goal.clone(), 6, 0, now());
+        assert_eq!(progress.pace, GoalPace::OnTrack);
+        assert!((progress.expected - 24.0 * 91.0 / 365.0).abs() < 1e-9);
+        assert_eq!(progress.projected_total, Some(24));
+
+        let progress = GoalProgress::new(goal.clone(), 12, 0, now());
+        assert_eq!(progress.pace, GoalPace::Ahead);
+        assert_eq!(progress.percent, 50.0);
+        // 1冊あたり約7.6日なので、182日目に達成する
+        assert_eq!(
+            progress.projected_finish,
+            Some(NaiveDate::from_ymd(2026, 7, 1))
+        );
+
+        let progress = GoalProgress::new(goal.clone(), 0, 0, now());
+        assert_eq!(progress.pace, GoalPace::Behind);
+        assert_eq!(progress.projected_finish, None);
+
+        let progress = GoalProgress::new(goal, 24, 1, now());
+        assert!(progress.completed);
+        assert_eq!(progress.projected_finish, None);
+    }
+
+    #[test]
+    fn test_daily_progress() {
+        let goal = GoalEntityForCreation {
+            metric: GoalMetric::Pages,
+            starts_on: Some(NaiveDate::from_ymd(2026, 3, 30)),
+            ..creation(GoalPeriod::Day, 30)
+        }
+        .into_goal(1, now())
+        .unwrap();
+
+        let progress = GoalProgress::new(goal.clone(), 80, 20, now());
+        assert_eq!(progress.until, NaiveDate::from_ymd(2026, 4, 1));
+        assert_eq!(progress.target, 90);
+        assert_eq!(progress.pace, GoalPace::Behind);
+        assert!(!progress.completed);
+        assert_eq!(progress.projected_total, None);
+
+        let progress = GoalProgress::new(goal.clone(), 95, 30, now());
+        assert_eq!(progress.pace, GoalPace::Ahead);
+        assert!(progress.completed);
+
+        // 始まる前の目標
+        let progress = GoalProgress::new(goal, 0, 0, Utc.ymd(2026, 3, 1).and_hms(0, 0, 0));
+        assert_eq!(progress.target, 0);
+        assert_eq!(progress.pace, GoalPace::OnTrack);
+        assert!(!progress.completed);
+    }
+}
@@ -0,0 +1,144 @@
+use axum::{
+    http::{Response, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{validation::ValidationError, Pid};
+use crate::domain::repo_if::RepoError;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordEntity {
+    pub id: Pid,
+    pub user_id: Pid,
+    pub book_id: Pid,
+    pub start_page: i32,
+    pub end_page: i32,
+    pub registered_datetime: DateTime<Utc>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordEntityForCreation {
+    pub book_id: Pid,
+    pub start_page: i32,
+    pub end_page: i32,
+    pub comment: Option<String>,
+}
+
+impl RecordEntityForCreation {
+    /// ページ範囲を検証する。`page_count`は本の総ページ数で、不明ならば上限は検証しない。
+    pub fn validate(&self, page_count: Option<i32>) -> Result<(), ValidationError> {
+        let mut error = ValidationError::new();
+        if self.start_page < 1 {
+            error.add("startPage", "must be 1 or more");
+        }
+        if self.end_page < 1 {
+            error.add("endPage", "must be 1 or more");
+        }
+        if self.start_page > self.end_page {
+            error.add("endPage", "must not be less than startPage");
+        }
+        if let Some(page_count) = page_count {
+            if self.end_page > page_count {
+                error.add(
+                    "endPage",
+                    format!(
+                        "must not exceed the page count of the book ({})",
+                        page_count
+                    ),
+                );
+            }
+        }
+        error.into_result()
+    }
+}
+
+pub enum RecordError {
+    Invalid(ValidationError),
+    Repo(RepoError),
+}
+
+impl From<ValidationError> for RecordError {
+    fn from(error: ValidationError) -> Self {
+        RecordError::Invalid(error)
+    }
+}
+
+impl From<RepoError> for RecordError {
+    fn from(error: RepoError) -> Self {
+        RecordError::Repo(error)
+    }
+}
+
+impl IntoResponse for RecordError {
+    type Body = <(StatusCode, Json<Value>) as IntoResponse>::Body;
+    type BodyError = <(StatusCode, Json<Value>) as IntoResponse>::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        match self {
+            RecordError::Invalid(error) => error.into_response(),
+            RecordError::Repo(error) => error.into_response(),
+        }
+    }
+}
+
+/// 読書記録一覧の絞り込み条件。
+/// 空の配列は絞り込みを行わないことを表す。
+#[derive(Debug, Default)]
+pub struct RecordFilter {
+    pub user_ids: Vec<Pid>,
+    pub book_ids: Vec<Pid>,
+    /// この時刻以降（等号を含む）
+    pub since: Option<DateTime<Utc>>,
+    /// この時刻より前（等号を含まない）
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(start_page: i32, end_page: i32) -> RecordEntityForCreation {
+        RecordEntityForCreation {
+            book_id: 1,
+            start_page,
+            end_page,
+            comment: None,
+        }
+    }
+
+    fn fields(result: Result<(), ValidationError>) -> Vec<&'static str> {
+        result
+            .unwrap_err()
+            .errors
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_valid_range() {
+        assert!(record(1, 1).validate(None).is_ok());
+        assert!(record(10, 300).validate(Some(300)).is_ok());
+    }
+
+    #[test]
+    fn test_validate_invalid_range() {
+        assert_eq!(fields(record(0, 10).validate(None)), vec!["startPage"]);
+        assert_eq!(fields(record(20, 10).validate(None)), vec!["endPage"]);
+        assert_eq!(
+            fields(record(0, -1).validate(None)),
+            vec!["startPage", "endPage", "endPage"]
+        );
+    }
+
+    #[test]
+    fn test_validate_page_count() {
+        assert_eq!(fields(record(10, 301).validate(Some(300))), vec!["endPage"]);
+    }
+}
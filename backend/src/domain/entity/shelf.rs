@@ -0,0 +1,368 @@
+use std::fmt;
+
+use axum::{
+    http::{Response, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{validation::ValidationError, Pid};
+use crate::domain::repo_if::RepoError;
+
+/// 本棚での読書の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShelfStatus {
+    WantToRead,
+    Reading,
+    Finished,
+    Abandoned,
+}
+
+impl ShelfStatus {
+    pub const ALL: [ShelfStatus; 4] = [
+        ShelfStatus::WantToRead,
+        ShelfStatus::Reading,
+        ShelfStatus::Finished,
+        ShelfStatus::Abandoned,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShelfStatus::WantToRead => "want_to_read",
+            ShelfStatus::Reading => "reading",
+            ShelfStatus::Finished => "finished",
+            ShelfStatus::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|status| status.as_str() == value)
+    }
+
+    /// 読み終えたか途中でやめた読書は、それ以上状態が変わらない。
+    /// もう一度読むときは新しい読書になる。
+    pub fn is_closed(&self) -> bool {
+        matches!(self, ShelfStatus::Finished | ShelfStatus::Abandoned)
+    }
+
+    fn can_become(&self, next: ShelfStatus) -> bool {
+        use ShelfStatus::*;
+        matches!(
+            (self, next),
+            (WantToRead, Reading)
+                | (WantToRead, Finished)
+                | (Reading, Finished)
+                | (Reading, Abandoned)
+        )
+    }
+}
+
+impl fmt::Display for ShelfStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// ユーザが1冊の本を1回読むこと。再読は別の読書として数える。
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingEntity {
+    pub id: Pid,
+    pub user_id: Pid,
+    pub book_id: Pid,
+    pub status: ShelfStatus,
+    pub started_at: Option<DateTime<Utc>>,
+    /// 読み終えた、または読むのをやめた日時
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 本棚での状態の変更。日時を省略すると、その状態になった時点の時刻を使う。
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShelfChange {
+    pub status: ShelfStatus,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl ReadingEntity {
+    /// 新しい読書を始める。途中でやめた状態から始めることはできない。
+    /// `id`はrepositoryが振るので0にしておく。
+    pub fn start(
+        user_id: Pid,
+        book_id: Pid,
+        change: &ShelfChange,
+        now: DateTime<Utc>,
+    ) -> Result<Self, ShelfError> {
+        if change.status == ShelfStatus::Abandoned {
+            return Err(ShelfError::InvalidTransition(None, change.status));
+        }
+        let mut reading = Self {
+            id: 0,
+            user_id,
+            book_id,
+            status: ShelfStatus::WantToRead,
+            started_at: None,
+            finished_at: None,
+            updated_at: now,
+        };
+        reading.apply(change, now)?;
+        Ok(reading)
+    }
+
+    /// 状態を変更する。同じ状態への変更では、指定された日時だけを更新する。
+    pub fn apply(&mut self, change: &ShelfChange, now: DateTime<Utc>) -> Result<(), ShelfError> {
+        if change.status != self.status && !self.status.can_become(change.status) {
+            return Err(ShelfError::InvalidTransition(
+                Some(self.status),
+                change.status,
+            ));
+        }
+
+        let mut started_at = change.started_at.or(self.started_at);
+        let mut finished_at = change.finished_at.or(self.finished_at);
+        match change.status {
+            ShelfStatus::WantToRead => {}
+            ShelfStatus::Reading => started_at = started_at.or(Some(now)),
+            // 読み始めを記録していなかった本は、読み終えた日時だけを持つ
+            ShelfStatus::Finished | ShelfStatus::Abandoned => {
+                finished_at = finished_at.or(Some(now))
+            }
+        }
+
+        let mut error = ValidationError::new();
+        if change.status == ShelfStatus::WantToRead && change.started_at.is_some() {
+            error.add("startedAt", "must not be set before reading");
+        }
+        if !change.status.is_closed() && change.finished_at.is_some() {
+            error.add("finishedAt", "must not be set before finishing");
+        }
+        if let (Some(started_at), Some(finished_at)) = (started_at, finished_at) {
+            if finished_at < started_at {
+                error.add("finishedAt", "must not be before startedAt");
+            }
+        }
+        error.into_result()?;
+
+        self.status = change.status;
+        self.started_at = started_at;
+        self.finished_at = finished_at;
+        self.updated_at = now;
+        Ok(())
+    }
+
+    /// 読書記録から状態を進める。
+    /// 読みたい本は読み始めたことにし、`finished`なら読み終えたことにする。
+    /// 状態が変わった場合は`true`を返す。
+    pub fn record_progress(&mut self, finished: bool, at: DateTime<Utc>) -> bool {
+        let before = self.status;
+        if self.status == ShelfStatus::WantToRead {
+            self.status = ShelfStatus::Reading;
+            self.started_at = Some(at);
+        }
+        if finished && self.status == ShelfStatus::Reading {
+            self.status = ShelfStatus::Finished;
+            self.finished_at = Some(at);
+        }
+        if self.status != before {
+            self.updated_at = at;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// ユーザの本棚。状態ごとに、更新の新しい順に並べる。
+#[derive(Debug, Default, Serialize)]
+pub struct Shelves {
+    pub want_to_read: Vec<ReadingEntity>,
+    pub reading: Vec<ReadingEntity>,
+    pub finished: Vec<ReadingEntity>,
+    pub abandoned: Vec<ReadingEntity>,
+}
+
+impl From<Vec<ReadingEntity>> for Shelves {
+    fn from(mut readings: Vec<ReadingEntity>) -> Self {
+        readings.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then(b.id.cmp(&a.id)));
+
+        let mut shelves = Self::default();
+        for reading in readings {
+            match reading.status {
+                ShelfStatus::WantToRead => shelves.want_to_read.push(reading),
+                ShelfStatus::Reading => shelves.reading.push(reading),
+                ShelfStatus::Finished => shelves.finished.push(reading),
+                ShelfStatus::Abandoned => shelves.abandoned.push(reading),
+            }
+        }
+        shelves
+    }
+}
+
+#[derive(Debug)]
+pub enum ShelfError {
+    Invalid(ValidationError),
+    /// 許されない状態の変更。変更前の状態が`None`なのは新しい読書の場合。
+    InvalidTransition(Option<ShelfStatus>, ShelfStatus),
+    Repo(RepoError),
+}
+
+impl From<ValidationError> for ShelfError {
+    fn from(error: ValidationError) -> Self {
+        ShelfError::Invalid(error)
+    }
+}
+
+impl From<RepoError> for ShelfError {
+    fn from(error: RepoError) -> Self {
+        ShelfError::Repo(error)
+    }
+}
+
+impl IntoResponse for ShelfError {
+    type Body = <(StatusCode, Json<Value>) as IntoResponse>::Body;
+    type BodyError = <(StatusCode, Json<Value>) as IntoResponse>::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        match self {
+            ShelfError::Invalid(error) => error.into_response(),
+            ShelfError::InvalidTransition(from, to) => {
+                let from = from.map_or("nothing", |from| from.as_str());
+                let body = Json(json!({
+                    "error": format!("cannot change from {} to {}", from, to),
+                }));
+                (StatusCode::CONFLICT, body).into_response()
+            }
+            ShelfError::Repo(error) => error.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    fn change(status: ShelfStatus) -> ShelfChange {
+        ShelfChange {
+            status,
+            started_at: None,
+            finished_at: None,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2022, 1, 9).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn test_read_through() {
+        let mut reading =
+            ReadingEntity::start(1, 2, &change(ShelfStatus::WantToRead), now()).unwrap();
+        assert_eq!(reading.started_at, None);
+
+        let later = now() + Duration::days(1);
+        reading.apply(&change(ShelfStatus::Reading), later).unwrap();
+        assert_eq!(reading.started_at, Some(later));
+
+        let finished = now() + Duration::days(3);
+        reading
+            .apply(&change(ShelfStatus::Finished), finished)
+            .unwrap();
+        assert_eq!(reading.status, ShelfStatus::Finished);
+        assert_eq!(reading.started_at, Some(later));
+        assert_eq!(reading.finished_at, Some(finished));
+    }
+
+    #[test]
+    fn test_invalid_transitions() {
+        let mut reading = ReadingEntity::start(1, 2, &change(ShelfStatus::Reading), now()).unwrap();
+        assert!(matches!(
+            reading.apply(&change(ShelfStatus::WantToRead), now()),
+            Err(ShelfError::InvalidTransition(Some(ShelfStatus::Reading), _))
+        ));
+
+        reading
+            .apply(&change(ShelfStatus::Abandoned), now())
+            .unwrap();
+        for status in ShelfStatus::ALL {
+            if status != ShelfStatus::Abandoned {
+                assert!(reading.apply(&change(status), now()).is_err());
+            }
+        }
+
+        assert!(matches!(
+            ReadingEntity::start(1, 2, &change(ShelfStatus::Abandoned), now()),
+            Err(ShelfError::InvalidTransition(None, ShelfStatus::Abandoned))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        let result = ReadingEntity::start(
+            1,
+            2,
+            &ShelfChange {
+                status: ShelfStatus::Finished,
+                started_at: Some(now()),
+                finished_at: Some(now() - Duration::days(1)),
+            },
+            now(),
+        );
+        assert!(matches!(result, Err(ShelfError::Invalid(_))));
+
+        let result = ReadingEntity::start(
+            1,
+            2,
+            &ShelfChange {
+                status: ShelfStatus::Reading,
+                started_at: None,
+                finished_at: Some(now()),
+            },
+            now(),
+        );
+        assert!(matches!(result, Err(ShelfError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_record_progress() {
+        let mut reading =
+            ReadingEntity::start(1, 2, &change(ShelfStatus::WantToRead), now()).unwrap();
+        assert!(reading.record_progress(false, now()));
+        assert_eq!(reading.status, ShelfStatus::Reading);
+        assert!(!reading.record_progress(false, now()));
+
+        assert!(reading.record_progress(true, now()));
+        assert_eq!(reading.status, ShelfStatus::Finished);
+        assert_eq!(reading.finished_at, Some(now()));
+        assert!(!reading.record_progress(true, now()));
+    }
+
+    #[test]
+    fn test_shelves() {
+        let mut readings = vec![];
+        for (id, status) in [
+            (1, ShelfStatus::Finished),
+            (2, ShelfStatus::Reading),
+            (3, ShelfStatus::Finished),
+        ] {
+            let mut reading = ReadingEntity::start(1, id, &change(status), now()).unwrap();
+            reading.id = id;
+            readings.push(reading);
+        }
+
+        let shelves = Shelves::from(readings);
+        assert_eq!(shelves.reading.len(), 1);
+        assert!(shelves.want_to_read.is_empty());
+        let finished: Vec<Pid> = shelves.finished.iter().map(|r| r.id).collect();
+        assert_eq!(finished, vec![3, 1]);
+    }
+}
@@ -0,0 +1,251 @@
+#[cfg(test)]
+use chrono::Datelike;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+
+use super::validation::ValidationError;
+
+/// 一度に集計できる最大の日数
+pub const MAX_STATS_DAYS: i64 = 366;
+
+/// 期間を省略したときに集計する日数
+const DEFAULT_STATS_DAYS: i64 = 30;
+
+/// 統計の対象期間。日付は`timezone`での暦日で、両端を含む。
+#[derive(Debug, Clone)]
+pub struct StatsQuery {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub timezone: Tz,
+}
+
+impl StatsQuery {
+    /// `until`を省略すると`now`の日付、`since`を省略すると`until`までの30日間になる。
+    pub fn new(
+        since: Option<NaiveDate>,
+        until: Option<NaiveDate>,
+        timezone: Tz,
+        now: DateTime<Utc>,
+    ) -> Result<Self, ValidationError> {
+        let until = until.unwrap_or_else(|| now.with_timezone(&timezone).naive_local().date());
+        let since = since.unwrap_or(until - Duration::days(DEFAULT_STATS_DAYS - 1));
+
+        let mut error = ValidationError::new();
+        if since > until {
+            error.add("since", "must not be after until");
+        } else if (until - since).num_days() >= MAX_STATS_DAYS {
+            error.add(
+                "since",
+                format!("must be within {} days before until", MAX_STATS_DAYS),
+            );
+        }
+        error.into_result().map(|_| Self {
+            since,
+            until,
+            timezone,
+        })
+    }
+
+    /// 期間の日数
+    pub fn days(&self) -> i64 {
+        (self.until - self.since).num_days() + 1
+    }
+
+    /// 期間にかかる全ての区間の始まりの日付。最初の区間は`since`より前から始まることがある。
+    /// Postgresでは`generate_series`で求めるので、メモリ上のrepositoryだけで使う。
+    #[cfg(test)]
+    pub fn periods(&self, interval: StatsInterval) -> Vec<NaiveDate> {
+        let mut periods = vec![];
+        let mut start = interval.start_of(self.since);
+        while start <= self.until {
+            periods.push(start);
+            start = interval.next(start);
+        }
+        periods
+    }
+}
+
+/// 時系列の区間の長さ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsInterval {
+    Day,
+    /// 月曜日から始まる週
+    Week,
+    Month,
+}
+
+impl StatsInterval {
+    /// Postgresの`date_trunc`に渡す単位
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatsInterval::Day => "day",
+            StatsInterval::Week => "week",
+            StatsInterval::Month => "month",
+        }
+    }
+
+    /// `date`を含む区間の始まりの日付
+    #[cfg(test)]
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            StatsInterval::Day => date,
+            StatsInterval::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            StatsInterval::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+        }
+    }
+
+    /// 区間の始まり`start`の次の区間の始まり
+    #[cfg(test)]
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            StatsInterval::Day => start + Duration::days(1),
+            StatsInterval::Week => start + Duration::days(7),
+            StatsInterval::Month if start.month() == 12 => {
+                NaiveDate::from_ymd(start.year() + 1, 1, 1)
+            }
+            StatsInterval::Month => NaiveDate::from_ymd(start.year(), start.month() + 1, 1),
+        }
+    }
+}
+
+/// 区間ごとの読んだページ数
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PagesPoint {
+    /// 区間の始まりの日付
+    pub start: NaiveDate,
+    pub pages: i64,
+    /// 期間の始まりからこの区間までの合計
+    pub cumulative_pages: i64,
+}
+
+/// 区間ごとの読み終えた本の数
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BooksPoint {
+    /// 区間の始まりの日付
+    pub start: NaiveDate,
+    pub books: i64,
+    /// 期間の始まりからこの区間までの合計
+    pub cumulative_books: i64,
+}
+
+/// 毎日記録を付けた連続日数
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Streaks {
+    /// 期間の最終日かその前日まで続いている連続日数。
+    /// 最終日にまだ記録がなくても途切れたことにはしない。
+    pub current: i64,
+    pub longest: i64,
+}
+
+impl Streaks {
+    /// 記録を付けた日付から求める。`days`は昇順で重複がないこと。
+    /// Postgresではwindow関数で求めるので、メモリ上のrepositoryだけで使う。
+    #[cfg(test)]
+    pub fn from_days(days: &[NaiveDate], until: NaiveDate) -> Self {
+        let mut streaks = Self::default();
+        let mut length = 0;
+        let mut last_day: Option<NaiveDate> = None;
+        for &day in days {
+            length = match last_day {
+                Some(last_day) if day - last_day == Duration::days(1) => length + 1,
+                _ => 1,
+            };
+            streaks.longest = streaks.longest.max(length);
+            last_day = Some(day);
+        }
+        if last_day.is_some_and(|last_day| last_day >= until - Duration::days(1)) {
+            streaks.current = length;
+        }
+        streaks
+    }
+}
+
+/// ユーザの読書の統計
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadingStats {
+    pub since: NaiveDate,
+    pub until: NaiveDate,
+    pub timezone: String,
+    pub total_pages: i64,
+    /// 記録を付けた日数
+    pub reading_days: i64,
+    /// 期間の全ての日で均したページ数
+    pub average_pages_per_day: f64,
+    /// 記録を付けた日だけで均したページ数
+    pub average_pages_per_reading_day: f64,
+    pub current_streak: i64,
+    pub longest_streak: i64,
+    pub pages_per_day: Vec<PagesPoint>,
+    pub pages_per_week: Vec<PagesPoint>,
+    pub pages_per_month: Vec<PagesPoint>,
+    pub books_finished_per_month: Vec<BooksPoint>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2022, month, day)
+    }
+
+    #[test]
+    fn test_query_defaults() {
+        // 東京では既に1月10日になっている
+        let now = Utc.ymd(2022, 1, 9).and_hms(20, 0, 0);
+        let query = StatsQuery::new(None, None, chrono_tz::Asia::Tokyo, now).unwrap();
+        assert_eq!(query.until, date(1, 10));
+        assert_eq!(query.since, date(1, 10) - Duration::days(29));
+        assert_eq!(query.days(), 30);
+
+        assert!(StatsQuery::new(Some(date(1, 11)), Some(date(1, 10)), Tz::UTC, now).is_err());
+        let since = date(1, 10) - Duration::days(MAX_STATS_DAYS);
+        assert!(StatsQuery::new(Some(since), Some(date(1, 10)), Tz::UTC, now).is_err());
+        let since = since + Duration::days(1);
+        assert!(StatsQuery::new(Some(since), Some(date(1, 10)), Tz::UTC, now).is_ok());
+    }
+
+    #[test]
+    fn test_periods() {
+        let query = StatsQuery::new(
+            Some(NaiveDate::from_ymd(2021, 12, 29)),
+            Some(date(2, 1)),
+            Tz::UTC,
+            Utc::now(),
+        )
+        .unwrap();
+
+        let weeks = query.periods(StatsInterval::Week);
+        assert_eq!(weeks[0], NaiveDate::from_ymd(2021, 12, 27));
+        assert_eq!(weeks.last(), Some(&date(1, 31)));
+
+        let months = query.periods(StatsInterval::Month);
+        assert_eq!(
+            months,
+            vec![NaiveDate::from_ymd(2021, 12, 1), date(1, 1), date(2, 1)]
+        );
+        assert_eq!(query.periods(StatsInterval::Day).len() as i64, query.days());
+    }
+
+    #[test]
+    fn test_streaks() {
+        let days = [date(1, 1), date(1, 2), date(1, 3), date(1, 5), date(1, 6)];
+        assert_eq!(
+            Streaks::from_days(&days, date(1, 7)),
+            Streaks {
+                current: 2,
+                longest: 3
+            }
+        );
+        assert_eq!(Streaks::from_days(&days, date(1, 8)).current, 0);
+        assert_eq!(Streaks::from_days(&[], date(1, 8)), Streaks::default());
+    }
+}
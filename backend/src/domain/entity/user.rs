@@ -9,25 +9,11 @@ use serde_json::{json, Value};
 
 use super::Pid;
 
-#[derive(Debug, Serialize)]
-pub struct UserEntity {
-    pub id: Pid,
-    pub subject: String,
-    pub username: String,
-}
-
 #[derive(Debug, Deserialize)]
 pub struct UserEntityForCreation {
     pub username: String,
 }
 
-#[derive(Debug)]
-pub enum UserError {
-    Nonexistent,
-    Duplicated,
-    Other,
-}
-
 #[derive(Debug)]
 pub struct LoginSession {
     pub session_id: String,
@@ -107,6 +93,11 @@ impl RefreshToken {
         Self { token, expires_at }
     }
 
+    #[cfg(test)]
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+
     pub fn into_cookie_value(self, cookie_name: &str, path: &str) -> String {
         format!(
             "{}={}; Expires={}; Path={}; HttpOnly",
@@ -116,11 +107,37 @@ impl RefreshToken {
             path,
         )
     }
+
+    /// ブラウザに保存されたrefresh tokenを削除させるクッキー
+    pub fn expired_cookie_value(cookie_name: &str, path: &str) -> String {
+        format!(
+            "{}=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0; Path={}; HttpOnly",
+            cookie_name, path,
+        )
+    }
 }
 
 #[derive(Debug)]
 pub struct RefreshTokenExtract(pub String);
 
+/// Refresh tokenを要求してきたクライアントの情報
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// ログイン中の端末ごとのセッション。
+/// Refresh tokenはローテーションされるが、セッションIDは変わらない。
+#[derive(Debug, Serialize)]
+pub struct RefreshSession {
+    pub id: String,
+    pub issued_at: DateTime<Utc>,
+    pub last_refreshed_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct AccessToken(pub String);
 
@@ -176,4 +193,15 @@ mod tests {
             expected
         );
     }
+
+    #[test]
+    fn test_expired_refresh_token_cookie() {
+        let expected =
+            "refresh_token=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0; Path=/; HttpOnly";
+
+        assert_eq!(
+            RefreshToken::expired_cookie_value("refresh_token", "/"),
+            expected
+        );
+    }
 }
@@ -0,0 +1,56 @@
+use axum::{
+    http::{Response, StatusCode},
+    response::{IntoResponse, Json},
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// 入力値の検証で見つかった、一つのフィールドについての問題
+#[derive(Debug, PartialEq, Serialize)]
+pub struct FieldError {
+    /// JSONでのフィールド名（camelCase）
+    pub field: &'static str,
+    pub message: String,
+}
+
+/// 入力値の検証エラー。問題のあったフィールドを全て保持する。
+/// レスポンスは422で、`fields`にフィールドごとの詳細が入る。
+#[derive(Debug, Default, PartialEq)]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationError {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field,
+            message: message.into(),
+        });
+    }
+
+    /// 問題が一つも追加されていなければ`Ok`になる。
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl IntoResponse for ValidationError {
+    type Body = <(StatusCode, Json<Value>) as IntoResponse>::Body;
+    type BodyError = <(StatusCode, Json<Value>) as IntoResponse>::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        let body = Json(json!({
+            "error": "invalid input",
+            "fields": self.errors,
+        }));
+        (StatusCode::UNPROCESSABLE_ENTITY, body).into_response()
+    }
+}
@@ -1,2 +1,42 @@
 pub mod book;
+pub mod goal;
+pub mod record;
+pub mod shelf;
+pub mod stats;
 pub mod user;
+
+use axum::{
+    http::{Response, StatusCode},
+    response::{IntoResponse, Json},
+};
+use serde_json::{json, Value};
+
+/// リポジトリ操作の失敗理由
+#[derive(Debug, PartialEq)]
+pub enum RepoError {
+    /// 対象が存在しない
+    NotFound,
+    /// 一意制約や外部キー制約に反する
+    Conflict,
+    /// データベースなどに接続できない
+    Unavailable,
+    Other,
+}
+
+impl IntoResponse for RepoError {
+    type Body = <(StatusCode, Json<Value>) as IntoResponse>::Body;
+    type BodyError = <(StatusCode, Json<Value>) as IntoResponse>::BodyError;
+
+    fn into_response(self) -> Response<Self::Body> {
+        let (status, error_message) = match self {
+            RepoError::NotFound => (StatusCode::NOT_FOUND, "not found"),
+            RepoError::Conflict => (StatusCode::CONFLICT, "conflict"),
+            RepoError::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, "service unavailable"),
+            RepoError::Other => (StatusCode::INTERNAL_SERVER_ERROR, ""),
+        };
+        let body = Json(json!({
+            "error": error_message,
+        }));
+        (status, body).into_response()
+    }
+}
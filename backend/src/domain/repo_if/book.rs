@@ -1,18 +1,37 @@
 use axum::async_trait;
 
-use super::super::entity::book::{BookEntity, BookEntityForCreation};
+use super::super::entity::book::{
+    BookEntity, BookEntityForCreation, BookListQuery, BookPage, BookSearchQuery,
+};
+use super::RepoError;
 
 #[async_trait]
-pub trait BookRepository {
-    async fn list_books(&self) -> Vec<BookEntity>;
+pub trait BookRepository: Send + Sync {
+    /// `query.sort`と`query.order`の順に、`query.after`より後ろの本を最大`query.limit`件返す。
+    async fn list_books(&self, query: BookListQuery) -> Result<BookPage, RepoError>;
 
-    async fn get_book(&self, book_id: u32) -> Option<BookEntity>;
+    /// `query`に一致する本を、関連度の高い順に最大`query.limit`件返す。
+    async fn search_books(&self, query: &BookSearchQuery) -> Result<Vec<BookEntity>, RepoError>;
 
-    async fn create_book(&self, book: BookEntityForCreation) -> Result<u32, ()>;
+    async fn get_book(&self, book_id: u32) -> Result<BookEntity, RepoError>;
 
-    /// Return: true indicates that the update operation was succeeded.
-    async fn update_book(&self, book: BookEntity) -> bool;
+    /// `created_by`を登録者とし、そのユーザの本棚にも入れる。
+    /// 同じISBNの本が既にある場合は`RepoError::Conflict`になる。
+    async fn create_book(
+        &self,
+        created_by: u32,
+        book: BookEntityForCreation,
+    ) -> Result<u32, RepoError>;
 
-    /// Return: true indicates that the delete operation was succeeded.
-    async fn delete_book(&self, book_id: u32) -> bool;
+    async fn update_book(&self, book: BookEntity) -> Result<(), RepoError>;
+
+    /// 本の情報は全ユーザで共有しているので、`user_id`以外のユーザが本棚に入れていたり
+    /// 読書記録や読書があったりする場合は削除せず、`RepoError::Conflict`になる。
+    async fn delete_book(&self, user_id: u32, book_id: u32) -> Result<(), RepoError>;
+
+    /// 既に本棚にある場合は何もしない。本が存在しない場合は`RepoError::NotFound`になる。
+    async fn add_to_library(&self, user_id: u32, book_id: u32) -> Result<(), RepoError>;
+
+    /// 本棚にない場合は`RepoError::NotFound`になる。
+    async fn remove_from_library(&self, user_id: u32, book_id: u32) -> Result<(), RepoError>;
 }
@@ -0,0 +1,23 @@
+use axum::async_trait;
+
+use super::super::entity::{goal::GoalEntity, Pid};
+use super::RepoError;
+
+#[async_trait]
+pub trait GoalRepository: Send + Sync {
+    /// ユーザの全ての目標を、期間の初日の新しい順に返す。
+    async fn list_goals(&self, user_id: Pid) -> Result<Vec<GoalEntity>, RepoError>;
+
+    /// 目標の所有者が`user_id`でなければ`RepoError::NotFound`になる。
+    async fn get_goal(&self, user_id: Pid, goal_id: Pid) -> Result<GoalEntity, RepoError>;
+
+    /// `goal.id`は無視して新しいIDを振る。
+    /// 同じ期間に同じものを数える目標が既にある場合は`RepoError::Conflict`になる。
+    async fn create_goal(&self, goal: GoalEntity) -> Result<Pid, RepoError>;
+
+    /// 目標の所有者が`goal.user_id`でなければ更新せず、`RepoError::NotFound`になる。
+    async fn update_goal(&self, goal: GoalEntity) -> Result<(), RepoError>;
+
+    /// 目標の所有者が`user_id`でなければ削除せず、`RepoError::NotFound`になる。
+    async fn delete_goal(&self, user_id: Pid, goal_id: Pid) -> Result<(), RepoError>;
+}
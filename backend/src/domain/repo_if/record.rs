@@ -0,0 +1,32 @@
+use axum::async_trait;
+
+use super::super::entity::{
+    record::{RecordEntity, RecordEntityForCreation, RecordFilter},
+    Pid,
+};
+use super::RepoError;
+
+#[async_trait]
+pub trait RecordRepository: Send + Sync {
+    /// 条件に合う読書記録を登録日時の昇順に返す。
+    async fn list_records(&self, filter: RecordFilter) -> Result<Vec<RecordEntity>, RepoError>;
+
+    async fn get_record(&self, record_id: Pid) -> Result<RecordEntity, RepoError>;
+
+    async fn create_record(
+        &self,
+        user_id: Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<Pid, RepoError>;
+
+    /// 記録の所有者が`user_id`でなければ更新せず、`RepoError::NotFound`になる。
+    async fn update_record(
+        &self,
+        user_id: Pid,
+        record_id: Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<(), RepoError>;
+
+    /// 記録の所有者が`user_id`でなければ削除せず、`RepoError::NotFound`になる。
+    async fn delete_record(&self, user_id: Pid, record_id: Pid) -> Result<(), RepoError>;
+}
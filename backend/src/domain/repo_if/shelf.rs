@@ -0,0 +1,34 @@
+use axum::async_trait;
+
+use super::super::entity::{shelf::ReadingEntity, Pid};
+use super::RepoError;
+
+#[async_trait]
+pub trait ShelfRepository: Send + Sync {
+    /// ユーザの全ての読書を返す。
+    async fn list_readings(&self, user_id: Pid) -> Result<Vec<ReadingEntity>, RepoError>;
+
+    /// ユーザがある本を読んだ全ての読書を、始めた順に返す。
+    async fn list_book_readings(
+        &self,
+        user_id: Pid,
+        book_id: Pid,
+    ) -> Result<Vec<ReadingEntity>, RepoError>;
+
+    /// まだ読み終えていない読書。本ごとに高々一つしかない。
+    async fn current_reading(
+        &self,
+        user_id: Pid,
+        book_id: Pid,
+    ) -> Result<Option<ReadingEntity>, RepoError>;
+
+    /// `reading.id`は無視して新しいIDを振る。
+    /// 同じ本の読み終えていない読書が既にある場合は`RepoError::Conflict`になる。
+    async fn create_reading(&self, reading: ReadingEntity) -> Result<Pid, RepoError>;
+
+    /// 状態と日時だけを更新する。
+    async fn update_reading(&self, reading: ReadingEntity) -> Result<(), RepoError>;
+
+    /// 読書の所有者が`user_id`でなければ削除せず、`RepoError::NotFound`になる。
+    async fn delete_reading(&self, user_id: Pid, reading_id: Pid) -> Result<(), RepoError>;
+}
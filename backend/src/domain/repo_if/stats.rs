@@ -0,0 +1,30 @@
+use axum::async_trait;
+
+use super::super::entity::{
+    stats::{BooksPoint, PagesPoint, StatsInterval, StatsQuery, Streaks},
+    Pid,
+};
+use super::RepoError;
+
+/// 読書記録と本棚から統計を集計する。
+#[async_trait]
+pub trait StatsRepository: Send + Sync {
+    /// 期間にかかる全ての区間について、読んだページ数を古い順に返す。
+    /// 記録のない区間も0として含む。
+    async fn pages_per_interval(
+        &self,
+        user_id: Pid,
+        query: &StatsQuery,
+        interval: StatsInterval,
+    ) -> Result<Vec<PagesPoint>, RepoError>;
+
+    /// 期間にかかる全ての月について、読み終えた本の数を古い順に返す。
+    async fn books_finished_per_month(
+        &self,
+        user_id: Pid,
+        query: &StatsQuery,
+    ) -> Result<Vec<BooksPoint>, RepoError>;
+
+    /// 期間内で記録を付けた日の連続日数
+    async fn streaks(&self, user_id: Pid, query: &StatsQuery) -> Result<Streaks, RepoError>;
+}
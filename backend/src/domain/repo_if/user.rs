@@ -2,27 +2,24 @@ use axum::async_trait;
 
 use super::super::entity::{
     user::{
-        LoginError, LoginSession, RefreshToken, RefreshTokenError, RefreshTokenExtract, SignUpCode,
-        SignUpError, UserEntity, UserEntityForCreation, UserError,
+        ClientInfo, LoginError, LoginSession, RefreshSession, RefreshToken, RefreshTokenError,
+        RefreshTokenExtract, SignUpCode, SignUpError, UserEntityForCreation,
     },
     Pid,
 };
+use super::RepoError;
 
 #[async_trait]
-pub trait UserRepository {
-    async fn get_user(&self, id: Pid) -> Result<UserEntity, UserError>;
-
-    async fn get_user_from_subject(&self, subject: &str) -> Result<UserEntity, UserError>;
-
-    async fn get_user_id_from_subject(&self, subject: &str) -> Result<Pid, UserError>;
+pub trait UserRepository: Send + Sync {
+    async fn get_user_id_from_subject(&self, subject: &str) -> Result<Pid, RepoError>;
 
     async fn create_user(
         &self,
         subject: String,
         user: UserEntityForCreation,
-    ) -> Result<Pid, UserError>;
+    ) -> Result<Pid, RepoError>;
 
-    async fn does_exist_user_id(&self, user_id: Pid) -> Result<bool, UserError>;
+    async fn does_exist_user_id(&self, user_id: Pid) -> Result<bool, RepoError>;
 
     async fn make_login_session(&self) -> Result<LoginSession, LoginError>;
 
@@ -42,14 +39,44 @@ pub trait UserRepository {
     /// IdP提供のsubjectを返却する。
     async fn verify_sign_up_code(&self, code: SignUpCode) -> Result<String, SignUpError>;
 
-    /// 新しいrefresh tokenを発行する。
-    /// 古いrefresh tokenがある場合は無効になる。
-    async fn issue_refresh_token(&self, userid: Pid) -> Result<RefreshToken, RefreshTokenError>;
+    /// 新しいセッションを開始し、そのrefresh tokenを発行する。
+    async fn issue_refresh_token(
+        &self,
+        userid: Pid,
+        client: ClientInfo,
+    ) -> Result<RefreshToken, RefreshTokenError>;
 
-    /// Refresh tokenを検証する。
+    /// Refresh tokenを検証し、同じセッションで新しいrefresh tokenを発行する。
+    /// 古いrefresh tokenは無効になる。
+    /// ローテーション済みのtokenが再利用された場合は、セッションごと無効にする。
     /// 紐づけられたuser idを返す。
-    async fn verify_refresh_token(
+    async fn rotate_refresh_token(
+        &self,
+        token: RefreshTokenExtract,
+        client: ClientInfo,
+    ) -> Result<(Pid, RefreshToken), RefreshTokenError>;
+
+    /// Refresh tokenの属するセッションを無効にする。
+    /// 紐づけられていたuser idを返す。
+    async fn revoke_refresh_token(
         &self,
         token: RefreshTokenExtract,
     ) -> Result<Pid, RefreshTokenError>;
+
+    /// ユーザの有効なセッションを発行日時の昇順に返す。
+    async fn list_refresh_sessions(
+        &self,
+        userid: Pid,
+    ) -> Result<Vec<RefreshSession>, RefreshTokenError>;
+
+    /// ユーザのセッションを一つ無効にする。
+    /// Return: true indicates that the session existed and was revoked.
+    async fn revoke_refresh_session(
+        &self,
+        userid: Pid,
+        session_id: &str,
+    ) -> Result<bool, RefreshTokenError>;
+
+    /// ユーザの全てのセッションを無効にする。
+    async fn revoke_all_refresh_tokens(&self, userid: Pid) -> Result<(), RefreshTokenError>;
 }
@@ -1,2 +1,6 @@
 pub mod book;
+pub mod goal;
+pub mod record;
+pub mod shelf;
+pub mod stats;
 pub mod user;
@@ -1,17 +1,22 @@
+use std::sync::Arc;
+
 use axum::{
     async_trait,
     extract::{FromRequest, RequestParts},
 };
 
 use super::super::entity::{
-    book::{BookEntity, BookEntityForCreation},
+    book::{
+        BookEntity, BookEntityForCreation, BookError, BookListQuery, BookPage, BookSearchHit,
+        BookSearchQuery,
+    },
     AxumError,
 };
-use super::super::repo_if::book::BookRepository;
-use crate::infra::repo::book::BookRepositoryImpl;
+use super::super::repo_if::{book::BookRepository, RepoError};
+use crate::state::AppState;
 
-pub struct BookService {
-    book_repository: BookRepositoryImpl,
+pub struct BookService<R: ?Sized = dyn BookRepository> {
+    book_repository: Arc<R>,
 }
 
 #[async_trait]
@@ -22,31 +27,98 @@ where
     type Rejection = AxumError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let book_repository = BookRepositoryImpl::from_request(req).await?;
-        Ok(Self { book_repository })
+        let state = AppState::from_request(req).await?;
+        Ok(Self::new(state.book_repository))
     }
 }
 
-impl BookService {
-    pub async fn list_books(&self) -> Vec<BookEntity> {
-        self.book_repository.list_books().await
+impl<R: BookRepository + ?Sized> BookService<R> {
+    pub fn new(book_repository: Arc<R>) -> Self {
+        Self { book_repository }
+    }
+
+    pub async fn list_books(&self, query: BookListQuery) -> Result<BookPage, RepoError> {
+        self.book_repository.list_books(query).await
     }
 
-    pub async fn get_book(&self, book_id: u32) -> Option<BookEntity> {
+    /// 関連度の高い順に、一致した箇所を添えて返す
+    pub async fn search_books(
+        &self,
+        query: BookSearchQuery,
+    ) -> Result<Vec<BookSearchHit>, RepoError> {
+        let books = self.book_repository.search_books(&query).await?;
+        Ok(books
+            .into_iter()
+            .map(|book| BookSearchHit::new(book, &query.terms))
+            .collect())
+    }
+
+    pub async fn get_book(&self, book_id: u32) -> Result<BookEntity, RepoError> {
         self.book_repository.get_book(book_id).await
     }
 
-    pub async fn create_book(&self, book: BookEntityForCreation) -> Result<u32, ()> {
-        self.book_repository.create_book(book).await
+    /// 登録したユーザの本棚にも入れる
+    pub async fn create_book(
+        &self,
+        user_id: u32,
+        mut book: BookEntityForCreation,
+    ) -> Result<u32, BookError> {
+        book.validate()?;
+        Ok(self.book_repository.create_book(user_id, book).await?)
+    }
+
+    /// 本を登録したユーザでなければ`BookError::Forbidden`になる。登録者の分からない本は誰でもよい
+    async fn check_owner(&self, user_id: u32, book_id: u32) -> Result<(), BookError> {
+        let book = self.book_repository.get_book(book_id).await?;
+        if book.can_be_edited_by(user_id) {
+            Ok(())
+        } else {
+            Err(BookError::Forbidden)
+        }
+    }
+
+    pub async fn update_book(
+        &self,
+        user_id: u32,
+        book_id: u32,
+        mut book: BookEntityForCreation,
+    ) -> Result<(), BookError> {
+        book.validate()?;
+        self.check_owner(user_id, book_id).await?;
+        Ok(self
+            .book_repository
+            .update_book((book_id, book).into())
+            .await?)
+    }
+
+    /// 他のユーザが本棚に入れていたり読書記録があったりする本は削除できない
+    pub async fn delete_book(&self, user_id: u32, book_id: u32) -> Result<(), BookError> {
+        self.check_owner(user_id, book_id).await?;
+        Ok(self.book_repository.delete_book(user_id, book_id).await?)
+    }
+
+    /// `user_id`のユーザの本棚にある本を`list_books`と同じ条件で返す
+    pub async fn list_library(
+        &self,
+        user_id: u32,
+        query: BookListQuery,
+    ) -> Result<BookPage, RepoError> {
+        self.book_repository
+            .list_books(BookListQuery {
+                library_of: Some(user_id),
+                ..query
+            })
+            .await
     }
 
-    /// Return: true indicates that the update operation was succeeded.
-    pub async fn update_book(&self, book: BookEntity) -> bool {
-        self.book_repository.update_book(book).await
+    /// 他のユーザが登録した本も本棚に入れられる
+    pub async fn add_to_library(&self, user_id: u32, book_id: u32) -> Result<(), RepoError> {
+        self.book_repository.add_to_library(user_id, book_id).await
     }
 
-    /// Return: true indicates that the delete operation was succeeded.
-    pub async fn delete_book(&self, book_id: u32) -> bool {
-        self.book_repository.delete_book(book_id).await
+    pub async fn remove_from_library(&self, user_id: u32, book_id: u32) -> Result<(), RepoError> {
+        self.book_repository
+            .remove_from_library(user_id, book_id)
+            .await
     }
 }
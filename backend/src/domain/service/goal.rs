@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::super::entity::{
+    goal::{GoalEntity, GoalEntityForCreation, GoalError, GoalMetric, GoalProgress},
+    stats::{StatsInterval, StatsQuery},
+    AxumError, Pid,
+};
+use super::super::repo_if::{goal::GoalRepository, stats::StatsRepository, RepoError};
+use crate::state::AppState;
+
+pub struct GoalService<R: ?Sized = dyn GoalRepository, SR: ?Sized = dyn StatsRepository> {
+    goal_repository: Arc<R>,
+    stats_repository: Arc<SR>,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for GoalService
+where
+    B: Send,
+{
+    type Rejection = AxumError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_request(req).await?;
+        Ok(Self::new(state.goal_repository, state.stats_repository))
+    }
+}
+
+impl<R, SR> GoalService<R, SR>
+where
+    R: GoalRepository + ?Sized,
+    SR: StatsRepository + ?Sized,
+{
+    pub fn new(goal_repository: Arc<R>, stats_repository: Arc<SR>) -> Self {
+        Self {
+            goal_repository,
+            stats_repository,
+        }
+    }
+
+    pub async fn list_goals(&self, user_id: Pid) -> Result<Vec<GoalProgress>, RepoError> {
+        let now = Utc::now();
+        let mut progresses = vec![];
+        for goal in self.goal_repository.list_goals(user_id).await? {
+            progresses.push(self.progress(goal, now).await?);
+        }
+        Ok(progresses)
+    }
+
+    pub async fn get_goal(&self, user_id: Pid, goal_id: Pid) -> Result<GoalProgress, RepoError> {
+        let goal = self.goal_repository.get_goal(user_id, goal_id).await?;
+        self.progress(goal, Utc::now()).await
+    }
+
+    pub async fn create_goal(
+        &self,
+        user_id: Pid,
+        goal: GoalEntityForCreation,
+    ) -> Result<Pid, GoalError> {
+        let goal = goal.into_goal(user_id, Utc::now())?;
+        Ok(self.goal_repository.create_goal(goal).await?)
+    }
+
+    pub async fn update_goal(
+        &self,
+        user_id: Pid,
+        goal_id: Pid,
+        goal: GoalEntityForCreation,
+    ) -> Result<(), GoalError> {
+        let goal = GoalEntity {
+            id: goal_id,
+            ..goal.into_goal(user_id, Utc::now())?
+        };
+        Ok(self.goal_repository.update_goal(goal).await?)
+    }
+
+    pub async fn delete_goal(&self, user_id: Pid, goal_id: Pid) -> Result<(), RepoError> {
+        self.goal_repository.delete_goal(user_id, goal_id).await
+    }
+
+    /// 期間の初日から今日までと、今日だけの達成量を集計して進み具合を求める
+    async fn progress(
+        &self,
+        goal: GoalEntity,
+        now: DateTime<Utc>,
+    ) -> Result<GoalProgress, RepoError> {
+        let today = goal.today(now);
+        let until = goal.ends_on().map_or(today, |ends_on| ends_on.min(today));
+
+        let current = self.achieved(&goal, goal.starts_on, until).await?;
+        let achieved_today =
+            if goal.starts_on <= today && goal.ends_on().is_none_or(|ends_on| today <= ends_on) {
+                self.achieved(&goal, today, today).await?
+            } else {
+                0
+            };
+        Ok(GoalProgress::new(goal, current, achieved_today, now))
+    }
+
+    /// `since`から`until`までに達成した量。期間が空なら0になる。
+    async fn achieved(
+        &self,
+        goal: &GoalEntity,
+        since: NaiveDate,
+        until: NaiveDate,
+    ) -> Result<i64, RepoError> {
+        if since > until {
+            return Ok(0);
+        }
+        // 毎日の目標は期間が長くなりうるので、月ごとの累計を使う
+        let query = StatsQuery {
+            since,
+            until,
+            timezone: goal.timezone,
+        };
+        let total = match goal.metric {
+            GoalMetric::Pages => self
+                .stats_repository
+                .pages_per_interval(goal.user_id, &query, StatsInterval::Month)
+                .await?
+                .last()
+                .map_or(0, |point| point.cumulative_pages),
+            GoalMetric::Books => self
+                .stats_repository
+                .books_finished_per_month(goal.user_id, &query)
+                .await?
+                .last()
+                .map_or(0, |point| point.cumulative_books),
+        };
+        Ok(total)
+    }
+}
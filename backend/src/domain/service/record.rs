@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+};
+use chrono::Utc;
+
+use super::super::entity::{
+    book::BookEntity,
+    record::{RecordEntity, RecordEntityForCreation, RecordError, RecordFilter},
+    shelf::{ReadingEntity, ShelfChange, ShelfError, ShelfStatus},
+    validation::ValidationError,
+    AxumError, Pid,
+};
+use super::super::repo_if::{
+    book::BookRepository, record::RecordRepository, shelf::ShelfRepository, RepoError,
+};
+use crate::state::AppState;
+
+pub struct RecordService<
+    R: ?Sized = dyn RecordRepository,
+    BR: ?Sized = dyn BookRepository,
+    SR: ?Sized = dyn ShelfRepository,
+> {
+    record_repository: Arc<R>,
+    book_repository: Arc<BR>,
+    shelf_repository: Arc<SR>,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for RecordService
+where
+    B: Send,
+{
+    type Rejection = AxumError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_request(req).await?;
+        Ok(Self::new(
+            state.record_repository,
+            state.book_repository,
+            state.shelf_repository,
+        ))
+    }
+}
+
+impl<R, BR, SR> RecordService<R, BR, SR>
+where
+    R: RecordRepository + ?Sized,
+    BR: BookRepository + ?Sized,
+    SR: ShelfRepository + ?Sized,
+{
+    pub fn new(
+        record_repository: Arc<R>,
+        book_repository: Arc<BR>,
+        shelf_repository: Arc<SR>,
+    ) -> Self {
+        Self {
+            record_repository,
+            book_repository,
+            shelf_repository,
+        }
+    }
+
+    /// ユーザIDの指定がない場合は、`user_id`のユーザ自身の記録に絞り込む。
+    pub async fn list_records(
+        &self,
+        user_id: Pid,
+        mut filter: RecordFilter,
+    ) -> Result<Vec<RecordEntity>, RepoError> {
+        if filter.user_ids.is_empty() {
+            filter.user_ids.push(user_id);
+        }
+        self.record_repository.list_records(filter).await
+    }
+
+    /// 一覧で`user_ids`を指定できるのと同じく、他のユーザの記録も取得できる
+    pub async fn get_record(&self, record_id: Pid) -> Result<RecordEntity, RepoError> {
+        self.record_repository.get_record(record_id).await
+    }
+
+    /// 記録する本の総ページ数を使ってページ範囲を検証する。
+    async fn validate_record(
+        &self,
+        record: &RecordEntityForCreation,
+    ) -> Result<BookEntity, RecordError> {
+        let book = match self.book_repository.get_book(record.book_id).await {
+            Ok(book) => book,
+            Err(RepoError::NotFound) => {
+                let mut error = ValidationError::new();
+                error.add("bookId", "the book does not exist");
+                return Err(error.into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        record.validate(book.page_count)?;
+        Ok(book)
+    }
+
+    /// 保存した記録に合わせて本棚の状態を進める。
+    /// 記録は既に保存しているので、失敗してもログに残すだけにする。
+    /// エラーを返すと、クライアントが再送したときに記録が重複してしまう。
+    /// 取りこぼした更新は、同じ本への次の記録のときに取り戻される。
+    async fn record_progress(&self, user_id: Pid, book: &BookEntity, end_page: i32) {
+        if let Err(err) = self.advance_reading(user_id, book, end_page).await {
+            tracing::warn!(
+                "cannot update the reading of book {} after recording: {:?}",
+                book.id,
+                err
+            );
+        }
+    }
+
+    /// 本をライブラリに入れ、最後のページまで読んだ記録があれば読み終えたことにする。
+    /// 今回の記録だけでなく保存済みの記録からも判断するので、何度実行しても同じ結果になる。
+    /// 読み終えた本への記録は、再読を始めたことにはしない。
+    async fn advance_reading(
+        &self,
+        user_id: Pid,
+        book: &BookEntity,
+        end_page: i32,
+    ) -> Result<(), ShelfError> {
+        self.book_repository
+            .add_to_library(user_id, book.id)
+            .await?;
+
+        let current = self
+            .shelf_repository
+            .current_reading(user_id, book.id)
+            .await?;
+        let readings = self
+            .shelf_repository
+            .list_book_readings(user_id, book.id)
+            .await?;
+        if current.is_none() && !readings.is_empty() {
+            return Ok(());
+        }
+
+        // 前回までの読書を終えた後の記録だけを見る
+        let since = readings
+            .iter()
+            .filter(|reading| current.as_ref().map(|current| current.id) != Some(reading.id))
+            .filter_map(|reading| reading.finished_at)
+            .max();
+        let finished = match book.page_count {
+            Some(page_count) => {
+                end_page >= page_count
+                    || self
+                        .record_repository
+                        .list_records(RecordFilter {
+                            user_ids: vec![user_id],
+                            book_ids: vec![book.id],
+                            since,
+                            until: None,
+                        })
+                        .await?
+                        .iter()
+                        .any(|record| record.end_page >= page_count)
+            }
+            None => false,
+        };
+        let now = Utc::now();
+
+        match current {
+            Some(mut reading) => {
+                if reading.record_progress(finished, now) {
+                    self.shelf_repository.update_reading(reading).await?;
+                }
+            }
+            None => {
+                let change = ShelfChange {
+                    status: ShelfStatus::Reading,
+                    started_at: None,
+                    finished_at: None,
+                };
+                let mut reading = ReadingEntity::start(user_id, book.id, &change, now)?;
+                reading.record_progress(finished, now);
+                self.shelf_repository.create_reading(reading).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn create_record(
+        &self,
+        user_id: Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<Pid, RecordError> {
+        let book = self.validate_record(&record).await?;
+        let end_page = record.end_page;
+        let record_id = self
+            .record_repository
+            .create_record(user_id, record)
+            .await?;
+        self.record_progress(user_id, &book, end_page).await;
+        Ok(record_id)
+    }
+
+    pub async fn update_record(
+        &self,
+        user_id: Pid,
+        record_id: Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<(), RecordError> {
+        let book = self.validate_record(&record).await?;
+        let end_page = record.end_page;
+        self.record_repository
+            .update_record(user_id, record_id, record)
+            .await?;
+        self.record_progress(user_id, &book, end_page).await;
+        Ok(())
+    }
+
+    pub async fn delete_record(&self, user_id: Pid, record_id: Pid) -> Result<(), RepoError> {
+        self.record_repository
+            .delete_record(user_id, record_id)
+            .await
+    }
+}
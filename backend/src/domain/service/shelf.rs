@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+};
+use chrono::Utc;
+
+use super::super::entity::{
+    shelf::{ReadingEntity, ShelfChange, ShelfError, Shelves},
+    AxumError, Pid,
+};
+use super::super::repo_if::{book::BookRepository, shelf::ShelfRepository, RepoError};
+use crate::state::AppState;
+
+pub struct ShelfService<R: ?Sized = dyn ShelfRepository, BR: ?Sized = dyn BookRepository> {
+    shelf_repository: Arc<R>,
+    book_repository: Arc<BR>,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for ShelfService
+where
+    B: Send,
+{
+    type Rejection = AxumError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_request(req).await?;
+        Ok(Self::new(state.shelf_repository, state.book_repository))
+    }
+}
+
+impl<R, BR> ShelfService<R, BR>
+where
+    R: ShelfRepository + ?Sized,
+    BR: BookRepository + ?Sized,
+{
+    pub fn new(shelf_repository: Arc<R>, book_repository: Arc<BR>) -> Self {
+        Self {
+            shelf_repository,
+            book_repository,
+        }
+    }
+
+    pub async fn list_shelves(&self, user_id: Pid) -> Result<Shelves, RepoError> {
+        let readings = self.shelf_repository.list_readings(user_id).await?;
+        Ok(Shelves::from(readings))
+    }
+
+    /// ある本を読んだ履歴。再読はそれぞれ別の読書になる。
+    pub async fn list_book_readings(
+        &self,
+        user_id: Pid,
+        book_id: Pid,
+    ) -> Result<Vec<ReadingEntity>, RepoError> {
+        self.shelf_repository
+            .list_book_readings(user_id, book_id)
+            .await
+    }
+
+    /// 読み終えていない読書があればその状態を変え、なければ新しい読書を始める。
+    /// 新しく始めた本は本棚にも入れる。
+    pub async fn change_status(
+        &self,
+        user_id: Pid,
+        book_id: Pid,
+        change: ShelfChange,
+    ) -> Result<ReadingEntity, ShelfError> {
+        let now = Utc::now();
+        match self
+            .shelf_repository
+            .current_reading(user_id, book_id)
+            .await?
+        {
+            Some(mut reading) => {
+                reading.apply(&change, now)?;
+                self.shelf_repository
+                    .update_reading(reading.clone())
+                    .await?;
+                Ok(reading)
+            }
+            None => {
+                let mut reading = ReadingEntity::start(user_id, book_id, &change, now)?;
+                self.book_repository
+                    .add_to_library(user_id, book_id)
+                    .await?;
+                reading.id = self
+                    .shelf_repository
+                    .create_reading(reading.clone())
+                    .await?;
+                Ok(reading)
+            }
+        }
+    }
+
+    pub async fn delete_reading(&self, user_id: Pid, reading_id: Pid) -> Result<(), RepoError> {
+        self.shelf_repository
+            .delete_reading(user_id, reading_id)
+            .await
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+};
+
+use super::super::entity::{
+    stats::{ReadingStats, StatsInterval, StatsQuery},
+    AxumError, Pid,
+};
+use super::super::repo_if::{stats::StatsRepository, RepoError};
+use crate::state::AppState;
+
+pub struct StatsService<R: ?Sized = dyn StatsRepository> {
+    stats_repository: Arc<R>,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for StatsService
+where
+    B: Send,
+{
+    type Rejection = AxumError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let state = AppState::from_request(req).await?;
+        Ok(Self::new(state.stats_repository))
+    }
+}
+
+impl<R> StatsService<R>
+where
+    R: StatsRepository + ?Sized,
+{
+    pub fn new(stats_repository: Arc<R>) -> Self {
+        Self { stats_repository }
+    }
+
+    pub async fn reading_stats(
+        &self,
+        user_id: Pid,
+        query: StatsQuery,
+    ) -> Result<ReadingStats, RepoError> {
+        let pages_per_day = self
+            .stats_repository
+            .pages_per_interval(user_id, &query, StatsInterval::Day)
+            .await?;
+        let pages_per_week = self
+            .stats_repository
+            .pages_per_interval(user_id, &query, StatsInterval::Week)
+            .await?;
+        let pages_per_month = self
+            .stats_repository
+            .pages_per_interval(user_id, &query, StatsInterval::Month)
+            .await?;
+        let books_finished_per_month = self
+            .stats_repository
+            .books_finished_per_month(user_id, &query)
+            .await?;
+        let streaks = self.stats_repository.streaks(user_id, &query).await?;
+
+        let total_pages = pages_per_day
+            .last()
+            .map_or(0, |point| point.cumulative_pages);
+        let reading_days = pages_per_day.iter().filter(|point| point.pages > 0).count() as i64;
+        let average_pages_per_reading_day = if reading_days > 0 {
+            total_pages as f64 / reading_days as f64
+        } else {
+            0.0
+        };
+
+        Ok(ReadingStats {
+            since: query.since,
+            until: query.until,
+            timezone: query.timezone.name().to_string(),
+            total_pages,
+            reading_days,
+            average_pages_per_day: total_pages as f64 / query.days() as f64,
+            average_pages_per_reading_day,
+            current_streak: streaks.current,
+            longest_streak: streaks.longest,
+            pages_per_day,
+            pages_per_week,
+            pages_per_month,
+            books_finished_per_month,
+        })
+    }
+}
@@ -1,25 +1,32 @@
+use std::sync::Arc;
+
 use axum::{
     async_trait,
-    extract::{Extension, FromRequest, RequestParts, TypedHeader},
+    extract::{FromRequest, RequestParts, TypedHeader},
+    http::header::AUTHORIZATION,
 };
 use chrono::{Duration, Utc};
 use headers::{authorization::Bearer, Authorization};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, errors::ErrorKind, Validation};
 
 use crate::domain::entity::{
     user::{
-        AccessToken, AccessTokenClaims, LoginError, LoginSession, RefreshToken, RefreshTokenError,
-        RefreshTokenExtract, SignUpCode, SignUpError, UserEntityForCreation, UserError,
+        AccessToken, AccessTokenClaims, ClientInfo, LoginError, LoginSession, RefreshSession,
+        RefreshToken, RefreshTokenError, RefreshTokenExtract, SignUpCode, SignUpError,
+        UserEntityForCreation,
     },
     AxumError, Pid,
 };
-use crate::domain::repo_if::user::UserRepository;
-use crate::infra::repo::user::UserRepositoryImpl;
+use crate::domain::repo_if::{user::UserRepository, RepoError};
+use crate::infra::keys::AccessTokenKeys;
+use crate::infra::request_id::record_user_id;
 use crate::settings::Settings;
+use crate::state::AppState;
 
-pub struct UserService {
+pub struct UserService<R: ?Sized = dyn UserRepository> {
     settings: Settings,
-    user_repository: UserRepositoryImpl,
+    keys: Arc<AccessTokenKeys>,
+    user_repository: Arc<R>,
 }
 
 #[async_trait]
@@ -30,19 +37,20 @@ where
     type Rejection = AxumError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let Extension(settings) = Extension::<Settings>::from_request(req)
-            .await
-            .map_err(|_| AxumError::OtherError("Settings extension error".to_string()))?;
-        let user_repository = UserRepositoryImpl::from_request(req).await?;
+        let state = AppState::from_request(req).await?;
+        Ok(Self::new(state.settings, state.keys, state.user_repository))
+    }
+}
 
-        Ok(Self {
+impl<R: UserRepository + ?Sized> UserService<R> {
+    pub fn new(settings: Settings, keys: Arc<AccessTokenKeys>, user_repository: Arc<R>) -> Self {
+        Self {
             settings,
+            keys,
             user_repository,
-        })
+        }
     }
-}
 
-impl UserService {
     pub async fn make_login_session(&self) -> Result<LoginSession, LoginError> {
         self.user_repository.make_login_session().await
     }
@@ -50,8 +58,13 @@ impl UserService {
     async fn issue_tokens(
         &self,
         uid: Pid,
+        client: ClientInfo,
     ) -> Result<(RefreshToken, AccessToken), RefreshTokenError> {
-        let refresh_token = self.user_repository.issue_refresh_token(uid).await?;
+        record_user_id(uid);
+        let refresh_token = self
+            .user_repository
+            .issue_refresh_token(uid, client)
+            .await?;
         let access_token = self.issue_access_token(uid)?;
         Ok((refresh_token, access_token))
     }
@@ -60,6 +73,7 @@ impl UserService {
         &self,
         session_id: String,
         code: String,
+        client: ClientInfo,
     ) -> Result<(RefreshToken, AccessToken), LoginError> {
         let subject = self
             .user_repository
@@ -72,8 +86,11 @@ impl UserService {
             .get_user_id_from_subject(&subject)
             .await
         {
-            Ok(uid) => self.issue_tokens(uid).await.map_err(|_| LoginError::Other),
-            Err(UserError::Nonexistent) => {
+            Ok(uid) => self
+                .issue_tokens(uid, client)
+                .await
+                .map_err(|_| LoginError::Other),
+            Err(RepoError::NotFound) => {
                 let code = self
                     .user_repository
                     .issue_sign_up_code(subject)
@@ -89,6 +106,7 @@ impl UserService {
         &self,
         code: SignUpCode,
         user: UserEntityForCreation,
+        client: ClientInfo,
     ) -> Result<(RefreshToken, AccessToken), SignUpError> {
         let subject = self.user_repository.verify_sign_up_code(code).await?;
 
@@ -96,20 +114,65 @@ impl UserService {
             .user_repository
             .create_user(subject, user)
             .await
-            .map_err(|_| SignUpError::DuplicatedUser)?;
-        self.issue_tokens(uid).await.map_err(|_| SignUpError::Other)
+            .map_err(|err| match err {
+                RepoError::Conflict => SignUpError::DuplicatedUser,
+                _ => SignUpError::Other,
+            })?;
+        self.issue_tokens(uid, client)
+            .await
+            .map_err(|_| SignUpError::Other)
     }
 
     pub async fn refresh_tokens(
         &self,
         refresh_token: RefreshTokenExtract,
+        client: ClientInfo,
     ) -> Result<(RefreshToken, AccessToken), RefreshTokenError> {
+        let (uid, refresh_token) = self
+            .user_repository
+            .rotate_refresh_token(refresh_token, client)
+            .await?;
+        record_user_id(uid);
+        let access_token = self.issue_access_token(uid)?;
+        Ok((refresh_token, access_token))
+    }
+
+    /// 提示されたrefresh tokenだけを無効にする。
+    pub async fn logout(
+        &self,
+        refresh_token: RefreshTokenExtract,
+    ) -> Result<(), RefreshTokenError> {
+        self.user_repository
+            .revoke_refresh_token(refresh_token)
+            .await
+            .map(|_| ())
+    }
+
+    /// 提示されたrefresh tokenの持ち主に発行した全てのrefresh tokenを無効にする。
+    pub async fn logout_everywhere(
+        &self,
+        refresh_token: RefreshTokenExtract,
+    ) -> Result<(), RefreshTokenError> {
         let uid = self
             .user_repository
-            .verify_refresh_token(refresh_token)
+            .revoke_refresh_token(refresh_token)
             .await?;
+        self.user_repository.revoke_all_refresh_tokens(uid).await
+    }
+
+    pub async fn list_sessions(&self, uid: Pid) -> Result<Vec<RefreshSession>, RefreshTokenError> {
+        self.user_repository.list_refresh_sessions(uid).await
+    }
 
-        self.issue_tokens(uid).await
+    /// Return: true indicates that the session existed and was revoked.
+    pub async fn revoke_session(
+        &self,
+        uid: Pid,
+        session_id: &str,
+    ) -> Result<bool, RefreshTokenError> {
+        self.user_repository
+            .revoke_refresh_session(uid, session_id)
+            .await
     }
 
     fn issue_access_token(&self, uid: Pid) -> Result<AccessToken, RefreshTokenError> {
@@ -120,13 +183,7 @@ impl UserService {
             expires_at.timestamp() as usize,
         );
 
-        let secret =
-            EncodingKey::from_base64_secret(&self.settings.access_secret).map_err(|err| {
-                tracing::error!("in issue_access_token: secret key decoding error: {}", err);
-                RefreshTokenError::Other
-            })?;
-
-        encode(&Header::default(), &claims, &secret)
+        encode(&self.keys.header(), &claims, self.keys.encoding_key())
             .map(AccessToken)
             .map_err(|err| {
                 tracing::error!("in issue_access_token: encoding error: {}", err);
@@ -135,6 +192,48 @@ impl UserService {
     }
 }
 
+/// リクエストのaccess tokenを検証してユーザIDを返す。
+/// `Authorization`ヘッダがなければ`None`を返し、あっても読めない場合はエラーにする。
+async fn authenticate<B>(req: &mut RequestParts<B>) -> Result<Option<Pid>, AxumError>
+where
+    B: Send,
+{
+    let state = AppState::from_request(req).await?;
+
+    let has_authorization = req
+        .headers()
+        .is_some_and(|headers| headers.contains_key(AUTHORIZATION));
+    if !has_authorization {
+        return Ok(None);
+    }
+    let TypedHeader(Authorization(bearer)) =
+        TypedHeader::<Authorization<Bearer>>::from_request(req)
+            .await
+            .map_err(|_| AxumError::MissingAccessToken)?;
+
+    let id = verify_access_token(&state.settings, &state.keys, bearer.token()).map_err(|err| {
+        // 期限切れは通常の動作なので数えない
+        if matches!(err, AxumError::InvalidAccessToken) {
+            state.metrics.invalid_access_tokens.inc();
+        }
+        err
+    })?;
+
+    if state
+        .user_repository
+        .does_exist_user_id(id)
+        .await
+        .map_err(|_| AxumError::OtherError(String::new()))?
+    {
+        record_user_id(id);
+        Ok(Some(id))
+    } else {
+        state.metrics.invalid_access_tokens.inc();
+        Err(AxumError::InvalidAccessToken)
+    }
+}
+
+/// ログインしているユーザ。access tokenがなければ401になる。
 pub struct UserId(pub Pid);
 
 #[async_trait]
@@ -145,42 +244,154 @@ where
     type Rejection = AxumError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let Extension(settings) = Extension::<Settings>::from_request(req)
-            .await
-            .map_err(|_| AxumError::OtherError("Settings extension error".to_string()))?;
-        let secret = DecodingKey::from_base64_secret(&settings.access_secret).map_err(|err| {
-            tracing::error!("in UserId: secret key decoding error: {}", err);
-            AxumError::OtherError("secret key decoding error".to_string())
-        })?;
-        let user_repository = UserRepositoryImpl::from_request(req)
-            .await
-            .map_err(|_| AxumError::OtherError(String::new()))?;
+        authenticate(req)
+            .await?
+            .map(Self)
+            .ok_or(AxumError::MissingAccessToken)
+    }
+}
 
-        let TypedHeader(Authorization(bearer)) =
-            TypedHeader::<Authorization<Bearer>>::from_request(req)
-                .await
-                .map_err(|_| AxumError::MissingAccessToken)?;
+/// 読み取り専用のAPIを呼び出せること。
+/// `ALLOW_ANONYMOUS_READ`が有効な場合に限り、access tokenなしでも受け付ける。
+/// access tokenを付けた場合は、`UserId`と同じく検証する。
+pub struct ReadAccess;
 
-        let mut validation = Validation::new(Algorithm::HS256);
-        validation.validate_exp = false;
-        validation.iss = Some(settings.access_iss);
+#[async_trait]
+impl<B> FromRequest<B> for ReadAccess
+where
+    B: Send,
+{
+    type Rejection = AxumError;
 
-        let id = decode::<AccessTokenClaims>(bearer.token(), &secret, &validation)
-            .map_err(|err| {
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        match authenticate(req).await? {
+            Some(_) => Ok(Self),
+            None if AppState::from_request(req)
+                .await?
+                .settings
+                .allow_anonymous_read =>
+            {
+                Ok(Self)
+            }
+            None => Err(AxumError::MissingAccessToken),
+        }
+    }
+}
+
+/// Access tokenの署名・発行者・有効期限を検証し、ユーザIDを返す。
+/// 署名はヘッダのkidに対応する鍵で検証する。
+/// 有効期限の判定には`Settings::access_leeway`秒の猶予を持たせる。
+fn verify_access_token(
+    settings: &Settings,
+    keys: &AccessTokenKeys,
+    token: &str,
+) -> Result<Pid, AxumError> {
+    let header = decode_header(token).map_err(|err| {
+        tracing::info!("in UserId: invalid token header: {}", err);
+        AxumError::InvalidAccessToken
+    })?;
+    let (key, alg) = keys.decoding_key(&header).ok_or_else(|| {
+        tracing::info!("in UserId: unknown key ID: {:?}", header.kid);
+        AxumError::InvalidAccessToken
+    })?;
+
+    let mut validation = Validation::new(alg);
+    validation.leeway = settings.access_leeway;
+    validation.iss = Some(settings.access_iss.to_owned());
+
+    decode::<AccessTokenClaims>(token, key, &validation)
+        .map(|data| data.claims.user_id())
+        .map_err(|err| match err.kind() {
+            ErrorKind::ExpiredSignature => {
+                tracing::info!("in UserId: expired token");
+                AxumError::ExpiredAccessToken
+            }
+            _ => {
                 tracing::info!("in UserId: invalid token: {}", err);
                 AxumError::InvalidAccessToken
-            })?
-            .claims
-            .user_id();
+            }
+        })
+}
 
-        if user_repository
-            .does_exist_user_id(id)
-            .await
-            .map_err(|_| AxumError::OtherError(String::new()))?
-        {
-            Ok(Self(id))
-        } else {
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ES256_PEM: &str = include_str!("../../../testdata/es256.pem");
+
+    fn settings() -> Settings {
+        envy::from_iter::<_, Settings>(vec![]).unwrap()
+    }
+
+    fn keys(settings: &Settings) -> AccessTokenKeys {
+        AccessTokenKeys::from_secret(&settings.access_secret).unwrap()
+    }
+
+    fn token(settings: &Settings, keys: &AccessTokenKeys, exp: i64) -> String {
+        let claims = AccessTokenClaims::new(settings.access_iss.to_owned(), 1, exp as usize);
+        encode(&keys.header(), &claims, keys.encoding_key()).unwrap()
+    }
+
+    #[test]
+    fn test_verify_access_token() {
+        let settings = settings();
+        let keys = keys(&settings);
+        let token = token(&settings, &keys, Utc::now().timestamp() + 60);
+
+        assert!(matches!(
+            verify_access_token(&settings, &keys, &token),
+            Ok(1)
+        ));
+    }
+
+    #[test]
+    fn test_verify_access_token_within_leeway() {
+        let settings = settings();
+        let keys = keys(&settings);
+        let exp = Utc::now().timestamp() - settings.access_leeway as i64 / 2;
+        let token = token(&settings, &keys, exp);
+
+        assert!(matches!(
+            verify_access_token(&settings, &keys, &token),
+            Ok(1)
+        ));
+    }
+
+    #[test]
+    fn test_verify_access_token_expired() {
+        let settings = settings();
+        let keys = keys(&settings);
+        let exp = Utc::now().timestamp() - settings.access_leeway as i64 - 60;
+        let token = token(&settings, &keys, exp);
+
+        assert!(matches!(
+            verify_access_token(&settings, &keys, &token),
+            Err(AxumError::ExpiredAccessToken)
+        ));
+    }
+
+    #[test]
+    fn test_verify_access_token_invalid() {
+        let settings = settings();
+        let keys = keys(&settings);
+
+        assert!(matches!(
+            verify_access_token(&settings, &keys, "invalid"),
             Err(AxumError::InvalidAccessToken)
-        }
+        ));
+    }
+
+    #[test]
+    fn test_verify_access_token_signed_by_another_key() {
+        let settings = settings();
+        let keys = keys(&settings);
+        let other =
+            AccessTokenKeys::from_pems(&[("other".to_string(), ES256_PEM.to_string())]).unwrap();
+        let token = token(&settings, &other, Utc::now().timestamp() + 60);
+
+        assert!(matches!(
+            verify_access_token(&settings, &keys, &token),
+            Err(AxumError::InvalidAccessToken)
+        ));
     }
 }
@@ -1 +1,7 @@
+pub mod health;
+pub mod id_provider;
+pub mod keys;
+pub mod metrics;
+pub mod migration;
 pub mod repo;
+pub mod request_id;
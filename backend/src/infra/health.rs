@@ -0,0 +1,67 @@
+use axum::async_trait;
+use redis::Client as RedisClient;
+use sqlx::postgres::PgPool;
+
+use super::id_provider::discover_client;
+use crate::settings::Settings;
+
+/// readinessの判定に使う、外部の依存先への疎通確認
+#[async_trait]
+pub trait Probe: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn check(&self) -> Result<(), String>;
+}
+
+pub struct PostgresProbe(pub PgPool);
+
+#[async_trait]
+impl Probe for PostgresProbe {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        sqlx::query("SELECT 1")
+            .execute(&self.0)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+pub struct RedisProbe(pub RedisClient);
+
+#[async_trait]
+impl Probe for RedisProbe {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        let mut con = self
+            .0
+            .get_async_connection()
+            .await
+            .map_err(|err| err.to_string())?;
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut con)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// IdPのメタデータを取得し直す
+pub struct IdProviderProbe(pub Settings);
+
+#[async_trait]
+impl Probe for IdProviderProbe {
+    fn name(&self) -> &'static str {
+        "id_provider"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        discover_client(&self.0).await.map(|_| ())
+    }
+}
@@ -0,0 +1,30 @@
+use openidconnect::core::{CoreClient, CoreProviderMetadata};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{ClientId, ClientSecret, IssuerUrl, RedirectUrl};
+
+use crate::settings::Settings;
+
+/// IdPのメタデータを取得し、OpenID Connectのクライアントを作る
+pub async fn discover_client(settings: &Settings) -> Result<CoreClient, String> {
+    let issuer_url = IssuerUrl::new(settings.id_provider_url.to_owned())
+        .map_err(|err| format!("failed in parsing the URL of IdP: {}", err))?;
+    let provider_metadata = CoreProviderMetadata::discover_async(issuer_url, async_http_client)
+        .await
+        .map_err(|err| format!("failed in discovering the IdP's metadata: {}", err))?;
+    let redirect_url =
+        RedirectUrl::new(settings.id_provider_redirect_url.to_owned()).map_err(|err| {
+            format!(
+                "failed in parsing the redirect URL which be passed to IdP: {}",
+                err
+            )
+        })?;
+
+    Ok(CoreClient::from_provider_metadata(
+        provider_metadata,
+        ClientId::new(settings.id_provider_client_id.to_owned()),
+        Some(ClientSecret::new(
+            settings.id_provider_client_secret.to_owned(),
+        )),
+    )
+    .set_redirect_uri(redirect_url))
+}
@@ -0,0 +1,346 @@
+use std::fs;
+use std::path::Path;
+
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header};
+use ring::signature::{EcdsaKeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::Serialize;
+
+use crate::settings::Settings;
+
+/// Access tokenの署名鍵と検証鍵。
+///
+/// `Settings::access_key_files`にPEM形式の秘密鍵（RSAまたはP-256のEC）を並べると、
+/// 先頭の鍵で署名し、全ての鍵で検証する。鍵IDはファイル名から拡張子を除いたものになる。
+/// 鍵をローテーションするときは、新しい鍵を先頭に追加し、
+/// 古い鍵で署名されたaccess tokenが期限切れになってから古い鍵を取り除く。
+///
+/// 鍵ファイルの指定がない場合は`Settings::access_secret`を使ったHS256にフォールバックする。
+pub struct AccessTokenKeys {
+    signing: SigningKey,
+    verifying: Vec<VerifyingKey>,
+    jwks: JsonWebKeySet,
+}
+
+struct SigningKey {
+    kid: Option<String>,
+    alg: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerifyingKey {
+    kid: Option<String>,
+    alg: Algorithm,
+    key: DecodingKey<'static>,
+}
+
+/// `/.well-known/jwks.json`で公開する公開鍵の一覧
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonWebKeySet {
+    pub keys: Vec<JsonWebKey>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonWebKey {
+    kty: &'static str,
+    #[serde(rename = "use")]
+    use_: &'static str,
+    alg: &'static str,
+    kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    e: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crv: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    y: Option<String>,
+}
+
+/// 一つの秘密鍵から作られる署名鍵・検証鍵・公開用JWKの組
+struct KeyMaterial {
+    signing: SigningKey,
+    verifying: VerifyingKey,
+    jwk: JsonWebKey,
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+impl KeyMaterial {
+    fn from_pem(kid: &str, pem: &str) -> Result<Self, String> {
+        let parsed = pem::parse(pem).map_err(|err| format!("invalid PEM: {}", err))?;
+
+        // PKCS#8はRSAとECの両方がありうるので、中身を解釈して判定する
+        let ec_key_pair = match parsed.tag.as_str() {
+            "PRIVATE KEY" => {
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &parsed.contents).ok()
+            }
+            _ => None,
+        };
+
+        if let Some(key_pair) = ec_key_pair {
+            // 非圧縮形式 (0x04 || x || y)
+            let point = key_pair.public_key().as_ref();
+            let (x, y) = point[1..].split_at((point.len() - 1) / 2);
+
+            return Ok(Self {
+                signing: SigningKey {
+                    kid: Some(kid.to_string()),
+                    alg: Algorithm::ES256,
+                    key: EncodingKey::from_ec_pem(pem.as_bytes())
+                        .map_err(|err| format!("invalid EC key: {}", err))?,
+                },
+                verifying: VerifyingKey {
+                    kid: Some(kid.to_string()),
+                    alg: Algorithm::ES256,
+                    key: DecodingKey::from_ec_der(point).into_static(),
+                },
+                jwk: JsonWebKey {
+                    kty: "EC",
+                    use_: "sig",
+                    alg: "ES256",
+                    kid: kid.to_string(),
+                    n: None,
+                    e: None,
+                    crv: Some("P-256"),
+                    x: Some(base64url(x)),
+                    y: Some(base64url(y)),
+                },
+            });
+        }
+
+        let key_pair = match parsed.tag.as_str() {
+            "RSA PRIVATE KEY" => RsaKeyPair::from_der(&parsed.contents),
+            "PRIVATE KEY" => RsaKeyPair::from_pkcs8(&parsed.contents),
+            tag => return Err(format!("unsupported PEM type: {}", tag)),
+        }
+        .map_err(|err| format!("invalid RSA key: {}", err))?;
+        let public_key = key_pair.public_key();
+
+        Ok(Self {
+            signing: SigningKey {
+                kid: Some(kid.to_string()),
+                alg: Algorithm::RS256,
+                key: EncodingKey::from_rsa_pem(pem.as_bytes())
+                    .map_err(|err| format!("invalid RSA key: {}", err))?,
+            },
+            verifying: VerifyingKey {
+                kid: Some(kid.to_string()),
+                alg: Algorithm::RS256,
+                key: DecodingKey::from_rsa_der(public_key.as_ref()).into_static(),
+            },
+            jwk: JsonWebKey {
+                kty: "RSA",
+                use_: "sig",
+                alg: "RS256",
+                kid: kid.to_string(),
+                n: Some(base64url(
+                    public_key.modulus().big_endian_without_leading_zero(),
+                )),
+                e: Some(base64url(
+                    public_key.exponent().big_endian_without_leading_zero(),
+                )),
+                crv: None,
+                x: None,
+                y: None,
+            },
+        })
+    }
+}
+
+impl AccessTokenKeys {
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        if settings.access_key_files.is_empty() {
+            return Self::from_secret(&settings.access_secret);
+        }
+
+        let mut pems = Vec::with_capacity(settings.access_key_files.len());
+        for file in settings.access_key_files.iter() {
+            let kid = Path::new(file)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| format!("cannot derive a key ID from {}", file))?;
+            let pem = fs::read_to_string(file)
+                .map_err(|err| format!("cannot read the key file {}: {}", file, err))?;
+            pems.push((kid.to_string(), pem));
+        }
+
+        Self::from_pems(&pems)
+    }
+
+    /// 鍵IDとPEMの組から作る。先頭の鍵が署名に使われる。
+    pub fn from_pems(pems: &[(String, String)]) -> Result<Self, String> {
+        let mut materials = Vec::with_capacity(pems.len());
+        for (kid, pem) in pems.iter() {
+            if materials
+                .iter()
+                .any(|material: &KeyMaterial| material.jwk.kid == *kid)
+            {
+                return Err(format!("duplicated key ID: {}", kid));
+            }
+            let material =
+                KeyMaterial::from_pem(kid, pem).map_err(|err| format!("key {}: {}", kid, err))?;
+            materials.push(material);
+        }
+
+        let mut materials = materials.into_iter();
+        let first = materials
+            .next()
+            .ok_or_else(|| "no access token key is given".to_string())?;
+
+        let mut verifying = vec![first.verifying];
+        let mut keys = vec![first.jwk];
+        for material in materials {
+            verifying.push(material.verifying);
+            keys.push(material.jwk);
+        }
+
+        Ok(Self {
+            signing: first.signing,
+            verifying,
+            jwks: JsonWebKeySet { keys },
+        })
+    }
+
+    /// 共有鍵によるHS256。共有鍵は外部に公開できないので、JWKSは空になる。
+    pub fn from_secret(secret: &str) -> Result<Self, String> {
+        Ok(Self {
+            signing: SigningKey {
+                kid: None,
+                alg: Algorithm::HS256,
+                key: EncodingKey::from_base64_secret(secret)
+                    .map_err(|err| format!("secret key decoding error: {}", err))?,
+            },
+            verifying: vec![VerifyingKey {
+                kid: None,
+                alg: Algorithm::HS256,
+                key: DecodingKey::from_base64_secret(secret)
+                    .map_err(|err| format!("secret key decoding error: {}", err))?
+                    .into_static(),
+            }],
+            jwks: JsonWebKeySet { keys: vec![] },
+        })
+    }
+
+    /// 署名に使うJWTヘッダ
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.signing.alg);
+        header.kid = self.signing.kid.to_owned();
+        header
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.signing.key
+    }
+
+    /// JWTヘッダのkidに対応する検証鍵とそのアルゴリズムを返す。
+    /// ヘッダのalgは信用せず、鍵に紐づくアルゴリズムで検証させる。
+    pub fn decoding_key(&self, header: &Header) -> Option<(&DecodingKey<'static>, Algorithm)> {
+        self.verifying
+            .iter()
+            .find(|key| key.kid == header.kid)
+            .map(|key| (&key.key, key.alg))
+    }
+
+    pub fn jwks(&self) -> &JsonWebKeySet {
+        &self.jwks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, decode_header, encode, Validation};
+    use serde::Deserialize;
+
+    use super::*;
+
+    const ES256_PEM: &str = include_str!("../../testdata/es256.pem");
+    const RS256_PEM: &str = include_str!("../../testdata/rs256.pem");
+
+    #[derive(Serialize, Deserialize)]
+    struct Claims {
+        sub: u32,
+        exp: usize,
+    }
+
+    fn round_trip(keys: &AccessTokenKeys) -> u32 {
+        let claims = Claims {
+            sub: 1,
+            exp: 32503680000,
+        };
+        let token = encode(&keys.header(), &claims, keys.encoding_key()).unwrap();
+        let header = decode_header(&token).unwrap();
+        let (key, alg) = keys.decoding_key(&header).unwrap();
+        decode::<Claims>(&token, key, &Validation::new(alg))
+            .unwrap()
+            .claims
+            .sub
+    }
+
+    #[test]
+    fn test_es256_keys() {
+        let keys =
+            AccessTokenKeys::from_pems(&[("ec".to_string(), ES256_PEM.to_string())]).unwrap();
+
+        assert_eq!(keys.header().alg, Algorithm::ES256);
+        assert_eq!(keys.header().kid.as_deref(), Some("ec"));
+        assert_eq!(round_trip(&keys), 1);
+
+        let jwks = serde_json::to_value(keys.jwks()).unwrap();
+        assert_eq!(jwks["keys"][0]["kty"], "EC");
+        assert_eq!(jwks["keys"][0]["crv"], "P-256");
+        assert!(jwks["keys"][0]["x"].is_string());
+        assert!(jwks["keys"][0]["y"].is_string());
+    }
+
+    #[test]
+    fn test_rs256_keys() {
+        let keys =
+            AccessTokenKeys::from_pems(&[("rsa".to_string(), RS256_PEM.to_string())]).unwrap();
+
+        assert_eq!(keys.header().alg, Algorithm::RS256);
+        assert_eq!(round_trip(&keys), 1);
+
+        let jwks = serde_json::to_value(keys.jwks()).unwrap();
+        assert_eq!(jwks["keys"][0]["kty"], "RSA");
+        assert_eq!(jwks["keys"][0]["e"], "AQAB");
+        assert!(jwks["keys"][0]["n"].is_string());
+    }
+
+    #[test]
+    fn test_rotated_keys() {
+        let old =
+            AccessTokenKeys::from_pems(&[("old".to_string(), RS256_PEM.to_string())]).unwrap();
+        let claims = Claims {
+            sub: 1,
+            exp: 32503680000,
+        };
+        let token = encode(&old.header(), &claims, old.encoding_key()).unwrap();
+
+        // 新しい鍵で署名しつつ、古い鍵で署名されたtokenも検証できる
+        let keys = AccessTokenKeys::from_pems(&[
+            ("new".to_string(), ES256_PEM.to_string()),
+            ("old".to_string(), RS256_PEM.to_string()),
+        ])
+        .unwrap();
+        assert_eq!(keys.header().kid.as_deref(), Some("new"));
+        assert_eq!(keys.jwks().keys.len(), 2);
+
+        let header = decode_header(&token).unwrap();
+        let (key, alg) = keys.decoding_key(&header).unwrap();
+        assert_eq!(alg, Algorithm::RS256);
+        assert!(decode::<Claims>(&token, key, &Validation::new(alg)).is_ok());
+    }
+
+    #[test]
+    fn test_duplicated_key_id() {
+        let result = AccessTokenKeys::from_pems(&[
+            ("key".to_string(), ES256_PEM.to_string()),
+            ("key".to_string(), RS256_PEM.to_string()),
+        ]);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::{
+    extract::MatchedPath,
+    http::{Method, Request, Response},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::postgres::PgPool;
+use tower::{Layer, Service};
+
+const NAMESPACE: &str = "book_record";
+
+/// `/metrics`で公開するPrometheusのメトリクス
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    pg_pool: Option<PgPool>,
+    pg_pool_connections: IntGauge,
+    pg_pool_idle_connections: IntGauge,
+
+    pub logins: IntCounter,
+    pub sign_ups: IntCounter,
+    pub refresh_rotations: IntCounter,
+    pub invalid_access_tokens: IntCounter,
+    pub books_created: IntCounter,
+    pub records_created: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)
+            .expect("the metrics namespace is valid");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by matched route"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by matched route",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let pg_pool_connections = IntGauge::new(
+            "pg_pool_connections",
+            "Connections currently held by the Postgres pool",
+        )
+        .unwrap();
+        let pg_pool_idle_connections = IntGauge::new(
+            "pg_pool_idle_connections",
+            "Idle connections in the Postgres pool",
+        )
+        .unwrap();
+
+        let counter = |name: &str, help: &str| {
+            let counter = IntCounter::new(name, help).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+        let logins = counter("logins_total", "Successful logins");
+        let sign_ups = counter("sign_ups_total", "Successful sign ups");
+        let refresh_rotations = counter(
+            "refresh_rotations_total",
+            "Refresh tokens rotated by /token",
+        );
+        let invalid_access_tokens = counter(
+            "invalid_access_tokens_total",
+            "Requests rejected because of an invalid access token",
+        );
+        let books_created = counter("books_created_total", "Books created");
+        let records_created = counter("records_created_total", "Records created");
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pg_pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(pg_pool_idle_connections.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            pg_pool: None,
+            pg_pool_connections,
+            pg_pool_idle_connections,
+            logins,
+            sign_ups,
+            refresh_rotations,
+            invalid_access_tokens,
+            books_created,
+            records_created,
+        }
+    }
+
+    /// 出力のたびにコネクションプールの状態を読み取る
+    pub fn with_pg_pool(mut self, pool: PgPool) -> Self {
+        self.pg_pool = Some(pool);
+        self
+    }
+
+    /// Prometheusのテキスト形式で出力する
+    pub fn render(&self) -> String {
+        if let Some(pool) = &self.pg_pool {
+            self.pg_pool_connections.set(pool.size() as i64);
+            self.pg_pool_idle_connections.set(pool.num_idle() as i64);
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("encoding metrics into a Vec never fails");
+        String::from_utf8(buffer).expect("the text format is UTF-8")
+    }
+
+    fn observe_request(&self, method: &str, route: &str, status: &str, seconds: f64) {
+        let labels = [method, route, status];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_request_duration
+            .with_label_values(&labels)
+            .observe(seconds);
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 任意の拡張メソッドで系列が増えないよう、標準のメソッド以外は`other`にまとめる
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::PATCH => "PATCH",
+        Method::TRACE => "TRACE",
+        _ => "other",
+    }
+}
+
+/// リクエスト数とレイテンシをルーティングされたパスごとに記録するlayer。
+/// `MatchedPath`を使うので、`Router::layer`で各ルートに被せる。
+#[derive(Clone)]
+pub struct MetricsLayer(pub Arc<Metrics>);
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            metrics: self.0.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for MetricsService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        // パスパラメータごとに系列が増えないよう、実際のパスではなくルートの定義を使う
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let method = method_label(req.method());
+        let metrics = self.metrics.clone();
+        let start = Instant::now();
+
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let response = future.await?;
+            metrics.observe_request(
+                method,
+                &route,
+                response.status().as_str(),
+                start.elapsed().as_secs_f64(),
+            );
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.logins.inc();
+        metrics.observe_request("GET", "/v1/books/:id", "200", 0.01);
+
+        let text = metrics.render();
+        assert!(text.contains("book_record_logins_total 1"));
+        assert!(text.contains(
+            r#"book_record_http_requests_total{method="GET",route="/v1/books/:id",status="200"} 1"#
+        ));
+        assert!(text.contains("book_record_http_request_duration_seconds_bucket"));
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label(&Method::PATCH), "PATCH");
+        assert_eq!(
+            method_label(&Method::from_bytes(b"PROPFIND").unwrap()),
+            "other"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::postgres::PgPool;
+
+/// `migrations`ディレクトリのSQLをバイナリに埋め込んだもの
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// 未適用のmigrationをすべて適用する
+pub async fn up(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// 最後に適用したmigrationを1つだけ取り消す。
+/// 取り消したmigrationのバージョンを返し、適用済みのものがなければ`None`を返す。
+pub async fn down(pool: &PgPool) -> Result<Option<i64>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+
+    // 途中で失敗しても、ロックは必ず解放してから返す
+    let result: Result<_, MigrateError> = async {
+        conn.ensure_migrations_table().await?;
+
+        if let Some(version) = conn.dirty_version().await? {
+            return Err(MigrateError::Dirty(version));
+        }
+
+        let latest = match conn.list_applied_migrations().await?.last() {
+            Some(applied) => applied.version,
+            None => return Ok(None),
+        };
+        let migration = MIGRATOR
+            .iter()
+            .find(|migration| {
+                migration.version == latest && migration.migration_type.is_down_migration()
+            })
+            .ok_or(MigrateError::VersionMissing(latest))?;
+        conn.revert(migration).await?;
+        Ok(Some(latest))
+    }
+    .await;
+
+    let unlocked = conn.unlock().await;
+    let reverted = result?;
+    unlocked?;
+    Ok(reverted)
+}
+
+/// 埋め込まれたmigrationそれぞれの適用状況
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let applied: HashMap<_, _> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|applied| (applied.version, applied.checksum))
+        .collect();
+    let dirty = conn.dirty_version().await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| {
+            let state = match applied.get(&migration.version) {
+                _ if dirty == Some(migration.version) => MigrationState::Dirty,
+                Some(checksum) if *checksum != migration.checksum => {
+                    MigrationState::ChecksumMismatch
+                }
+                Some(_) => MigrationState::Applied,
+                None => MigrationState::Pending,
+            };
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// 適用後にSQLファイルが書き換えられた
+    ChecksumMismatch,
+    /// 適用の途中で失敗した
+    Dirty,
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+impl fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.state {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::ChecksumMismatch => "applied (checksum mismatch)",
+            MigrationState::Dirty => "dirty",
+        };
+        write!(f, "{}/{} {}", self.version, state, self.description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_embedded_migrations_are_reversible() {
+        let ups: Vec<_> = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .collect();
+        let downs: Vec<_> = MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .collect();
+
+        assert!(!ups.is_empty());
+        assert_eq!(ups, downs);
+    }
+
+    // `sqlx migrate run`で適用済みのDBに対して、埋め込んだmigrationが一致していることを確認する。
+    // 他のmedium testと並行して走るので、取り消しは行わない。
+    #[tokio::test]
+    #[ignore]
+    async fn test_up_is_idempotent() {
+        let settings = envy::from_env::<Settings>().unwrap();
+        let pool = PgPool::connect(&settings.database_url).await.unwrap();
+
+        up(&pool).await.unwrap();
+        assert!(status(&pool)
+            .await
+            .unwrap()
+            .iter()
+            .all(|status| status.state == MigrationState::Applied));
+    }
+}
@@ -1,6 +1,56 @@
 pub mod book;
+pub mod goal;
+#[cfg(test)]
+pub mod memory;
+pub mod record;
 pub mod schema;
 mod session;
+pub mod shelf;
+pub mod stats;
 pub mod user;
 
+use sqlx::Error as SqlxError;
+
+use crate::domain::repo_if::RepoError;
+
 type Pid = i32;
+
+/// Postgresのエラーを`RepoError`に分類する。
+/// 存在しないことは正常系でも起こるので、それ以外の場合だけ`context`を付けてログに出す。
+fn pg_error(context: &'static str) -> impl FnOnce(SqlxError) -> RepoError {
+    move |err| {
+        let repo_error = match &err {
+            SqlxError::RowNotFound => return RepoError::NotFound,
+            // unique_violation, foreign_key_violation
+            SqlxError::Database(db_err) => match db_err.code().as_deref() {
+                Some("23505") | Some("23503") => RepoError::Conflict,
+                _ => RepoError::Other,
+            },
+            SqlxError::Io(_)
+            | SqlxError::Tls(_)
+            | SqlxError::PoolTimedOut
+            | SqlxError::PoolClosed => RepoError::Unavailable,
+            _ => RepoError::Other,
+        };
+        tracing::info!("{}: {}", context, err);
+        repo_error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pg_error() {
+        assert_eq!(pg_error("")(SqlxError::RowNotFound), RepoError::NotFound);
+        assert_eq!(
+            pg_error("")(SqlxError::PoolTimedOut),
+            RepoError::Unavailable
+        );
+        assert_eq!(
+            pg_error("")(SqlxError::ColumnNotFound("id".to_string())),
+            RepoError::Other
+        );
+    }
+}
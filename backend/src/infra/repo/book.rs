@@ -1,144 +1,605 @@
-use axum::{
-    async_trait,
-    extract::{Extension, FromRequest, RequestParts},
+use axum::async_trait;
+use sqlx::{
+    postgres::{PgPool, Postgres},
+    Row, Transaction,
 };
-use sqlx::{postgres::PgPool, Row};
 
-use crate::domain::entity::book::{BookEntity, BookEntityForCreation};
-use crate::domain::entity::AxumError;
-use crate::domain::repo_if::book::BookRepository;
-use crate::infra::repo::schema::BookRow;
+use crate::domain::entity::book::{
+    BookCursor, BookCursorKey, BookEntity, BookEntityForCreation, BookListQuery, BookPage,
+    BookSearchQuery, BookSortKey, SortOrder,
+};
+use crate::domain::repo_if::{book::BookRepository, RepoError};
+use crate::infra::repo::{pg_error, schema::BookRow};
 
 pub struct BookRepositoryImpl {
     pool: PgPool,
 }
 
-#[async_trait]
-impl<B> FromRequest<B> for BookRepositoryImpl
-where
-    B: Send,
-{
-    type Rejection = AxumError;
+impl BookRepositoryImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// 著者名を`book_authors.position`順に集約して`authors`列として返すSELECT文
+const SELECT_BOOKS: &str = "SELECT b.*, \
+     COALESCE(array_agg(a.name::TEXT ORDER BY ba.position) FILTER (WHERE a.id IS NOT NULL), '{}') \
+     AS authors \
+     FROM books b \
+     LEFT JOIN book_authors ba ON ba.book_id = b.id \
+     LEFT JOIN authors a ON a.id = ba.author_id";
+
+/// 本棚の絞り込みの条件
+const IN_LIBRARY: &str = "b.id IN (SELECT book_id FROM user_books WHERE user_id = ";
+
+/// 本一覧のSELECT文を組み立てる。
+/// `(並び替えのキー, id)`の組で比較して、カーソルより後ろの行だけを取得する。
+/// インデックスを使って読み進められるよう、ページに入る本のidは`books`だけで絞り込んでから著者を集約する。
+/// プレースホルダはカーソルの値、id、本棚のユーザ、LIMITの順に振られる。
+fn build_list_books_query(query: &BookListQuery) -> String {
+    let column = match query.sort {
+        BookSortKey::Id => None,
+        BookSortKey::Title => Some("b.title"),
+        BookSortKey::CreatedAt => Some("b.created_at"),
+    };
+    let (comparison, direction) = match query.order {
+        SortOrder::Asc => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+
+    let mut conditions = vec![];
+    let mut placeholder = 1;
+    if query.after.is_some() {
+        match column {
+            Some(column) => {
+                conditions.push(format!("({}, b.id) {} ($1, $2)", column, comparison));
+                placeholder = 3;
+            }
+            None => {
+                conditions.push(format!("b.id {} $1", comparison));
+                placeholder = 2;
+            }
+        }
+    }
+    if query.library_of.is_some() {
+        conditions.push(format!("{}${})", IN_LIBRARY, placeholder));
+        placeholder += 1;
+    }
+
+    let order_by = match column {
+        Some(column) => format!("{} {}, b.id {}", column, direction, direction),
+        None => format!("b.id {}", direction),
+    };
+
+    let mut page = "SELECT b.id FROM books b".to_string();
+    if !conditions.is_empty() {
+        page.push_str(" WHERE ");
+        page.push_str(&conditions.join(" AND "));
+    }
+    page.push_str(&format!(" ORDER BY {} LIMIT ${}", order_by, placeholder));
+
+    format!(
+        "{} WHERE b.id IN ({}) GROUP BY b.id ORDER BY {}",
+        SELECT_BOOKS, page, order_by
+    )
+}
+
+/// 本検索のSELECT文を組み立てる。
+/// 語ごとに、タイトルか著者名への部分一致、またはタイトルとのtrigramの類似を求め、すべての語を満たす本を返す。
+/// 一致した箇所とタイトルとの類似度の合計で並べ、ISBNが一致した本は先頭に来るようにする。
+/// プレースホルダは語ごとにLIKEのパターンと語そのもの、続いてISBN、LIMITの順に振られる。
+fn build_search_books_query(term_count: usize, with_isbn: bool) -> String {
+    let mut conditions = Vec::with_capacity(term_count);
+    let mut scores = Vec::with_capacity(term_count + 1);
+    for i in 0..term_count {
+        let pattern = 2 * i + 1;
+        let term = 2 * i + 2;
+        let title_matches = format!("b.title ILIKE ${} ESCAPE '\\'", pattern);
+        let author_matches = format!(
+            "EXISTS (SELECT 1 FROM book_authors sba JOIN authors sa ON sa.id = sba.author_id \
+             WHERE sba.book_id = b.id AND sa.name ILIKE ${} ESCAPE '\\')",
+            pattern
+        );
+        conditions.push(format!(
+            "({} OR {} OR ${} <% b.title)",
+            title_matches, author_matches, term
+        ));
+        scores.push(format!(
+            "CASE WHEN {} THEN 1.0 ELSE 0.0 END + CASE WHEN {} THEN 0.5 ELSE 0.0 END \
+             + word_similarity(${}, b.title)",
+            title_matches, author_matches, term
+        ));
+    }
+
+    let mut condition = conditions.join(" AND ");
+    let mut placeholder = 2 * term_count + 1;
+    if with_isbn {
+        condition = format!("(({}) OR b.isbn = ${})", condition, placeholder);
+        scores.push(format!(
+            "CASE WHEN b.isbn = ${} THEN 3.0 ELSE 0.0 END",
+            placeholder
+        ));
+        placeholder += 1;
+    }
+
+    format!(
+        "{} WHERE {} GROUP BY b.id ORDER BY ({}) DESC, b.id LIMIT ${}",
+        SELECT_BOOKS,
+        condition,
+        scores.join(" + "),
+        placeholder
+    )
+}
+
+/// LIKEの特殊文字をエスケープし、部分一致のパターンにする
+fn like_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
 
-    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let Extension(pool) = Extension::<PgPool>::from_request(req)
+/// 本の著者を与えられた順序で登録する。
+/// 未登録の著者は作成し、同じ名前が重複していれば最初のものだけを使う。
+async fn set_authors(
+    transaction: &mut Transaction<'_, Postgres>,
+    book_id: super::Pid,
+    authors: &[String],
+) -> Result<(), RepoError> {
+    let mut names: Vec<&str> = Vec::with_capacity(authors.len());
+    for author in authors.iter().map(|author| author.trim()) {
+        if !author.is_empty() && !names.contains(&author) {
+            names.push(author);
+        }
+    }
+
+    for (position, name) in names.into_iter().enumerate() {
+        // DO NOTHINGだと既存行のidが返らないので、同じ値で更新する
+        let row = sqlx::query(
+            "INSERT INTO authors (name) VALUES ($1) \
+             ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id",
+        )
+        .bind(name)
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(pg_error("insert was failed"))?;
+        let author_id = row
+            .try_get::<super::Pid, _>("id")
+            .map_err(pg_error("parsing inserted id was failed"))?;
+
+        sqlx::query("INSERT INTO book_authors (book_id, author_id, position) VALUES ($1, $2, $3)")
+            .bind(book_id)
+            .bind(author_id)
+            .bind(position as i32)
+            .execute(&mut *transaction)
             .await
-            .map_err(|_| AxumError::PgConnectionError)?;
-        Ok(Self { pool })
+            .map_err(pg_error("insert was failed"))?;
     }
+
+    Ok(())
 }
 
 #[async_trait]
 impl BookRepository for BookRepositoryImpl {
-    async fn list_books(&self) -> Vec<BookEntity> {
-        let rows = sqlx::query_as::<_, BookRow>("SELECT * FROM books ORDER BY id ASC")
+    async fn list_books(&self, query: BookListQuery) -> Result<BookPage, RepoError> {
+        let sql = build_list_books_query(&query);
+        let mut select = sqlx::query_as::<_, BookRow>(&sql);
+        if let Some(cursor) = &query.after {
+            select = match &cursor.key {
+                BookCursorKey::Id => select,
+                BookCursorKey::Title(title) => select.bind(title),
+                BookCursorKey::CreatedAt(created_at) => select.bind(created_at),
+            }
+            .bind(cursor.id as super::Pid);
+        }
+        if let Some(user_id) = query.library_of {
+            select = select.bind(user_id as super::Pid);
+        }
+        // 続きがあるかを知るために1件多く取得する
+        let rows = select
+            .bind(query.limit as i64 + 1)
             .fetch_all(&self.pool)
             .await
-            .map_err(|err| {
-                tracing::info!("cannot establish transaction: {}", err);
-            });
+            .map_err(pg_error("cannot fetch books"))?;
+
+        let mut books: Vec<BookEntity> = rows.into_iter().map(BookEntity::from).collect();
+        let next = if books.len() > query.limit as usize {
+            books.truncate(query.limit as usize);
+            books
+                .last()
+                .map(|book| BookCursor::after(book, query.sort, query.order))
+        } else {
+            None
+        };
+
+        let total = if query.with_total {
+            let row = match query.library_of {
+                Some(user_id) => {
+                    sqlx::query("SELECT COUNT(*) AS total FROM user_books WHERE user_id = $1")
+                        .bind(user_id as super::Pid)
+                }
+                None => sqlx::query("SELECT COUNT(*) AS total FROM books"),
+            }
+            .fetch_one(&self.pool)
+            .await
+            .map_err(pg_error("cannot count books"))?;
+            Some(
+                row.try_get::<i64, _>("total")
+                    .map_err(pg_error("parsing the count was failed"))?,
+            )
+        } else {
+            None
+        };
 
-        match rows {
-            Ok(rows) => rows.into_iter().map(BookEntity::from).collect(),
-            Err(_) => vec![],
+        Ok(BookPage { books, next, total })
+    }
+
+    async fn search_books(&self, query: &BookSearchQuery) -> Result<Vec<BookEntity>, RepoError> {
+        let sql = build_search_books_query(query.terms.len(), query.isbn.is_some());
+        let mut select = sqlx::query_as::<_, BookRow>(&sql);
+        for term in &query.terms {
+            select = select.bind(like_pattern(term)).bind(term);
+        }
+        if let Some(isbn) = &query.isbn {
+            select = select.bind(isbn.as_str());
         }
+        select
+            .bind(query.limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map(|rows| rows.into_iter().map(BookEntity::from).collect())
+            .map_err(pg_error("cannot search books"))
     }
 
-    async fn get_book(&self, book_id: u32) -> Option<BookEntity> {
-        sqlx::query_as::<_, BookRow>("SELECT * FROM books WHERE id = $1")
-            .bind(book_id)
+    async fn get_book(&self, book_id: u32) -> Result<BookEntity, RepoError> {
+        sqlx::query_as::<_, BookRow>(&format!("{} WHERE b.id = $1 GROUP BY b.id", SELECT_BOOKS))
+            .bind(book_id as super::Pid)
             .fetch_one(&self.pool)
             .await
-            .map_err(|err| {
-                // TODO
-                // 存在しないIDをいれたときにもここでエラーログが出るのをどうにかしたい
-                tracing::info!("cannot establish transaction: {}", err);
-            })
             .map(BookEntity::from)
-            .ok()
+            .map_err(pg_error("cannot fetch the book"))
     }
 
-    async fn create_book(&self, book: BookEntityForCreation) -> Result<u32, ()> {
-        let mut transaction = self.pool.begin().await.map_err(|err| {
-            tracing::info!("cannot establish transaction: {}", err);
-        })?;
+    async fn create_book(
+        &self,
+        created_by: u32,
+        book: BookEntityForCreation,
+    ) -> Result<u32, RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("cannot establish transaction"))?;
+
+        let row = sqlx::query(
+            "INSERT INTO books \
+             (title, isbn, publisher, page_count, language, published_date, created_by) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id",
+        )
+        .bind(book.title)
+        .bind(book.isbn)
+        .bind(book.publisher)
+        .bind(book.page_count)
+        .bind(book.language)
+        .bind(book.published_date)
+        .bind(created_by as super::Pid)
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(pg_error("insert was failed"))?;
+
+        let id = row
+            .try_get::<i32, _>("id")
+            .map_err(pg_error("parsing inserted id was failed"))?;
+
+        set_authors(&mut transaction, id, &book.authors).await?;
 
-        let row = sqlx::query("INSERT INTO books (title) VALUES ($1) RETURNING id")
-            .bind(book.title)
+        sqlx::query("INSERT INTO user_books (user_id, book_id) VALUES ($1, $2)")
+            .bind(created_by as super::Pid)
+            .bind(id)
+            .execute(&mut transaction)
+            .await
+            .map_err(pg_error("insert was failed"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))?;
+
+        // SQLの仕様ではsignedだが、値は0以上のものが返ってくる
+        Ok(id as u32)
+    }
+
+    async fn update_book(&self, book: BookEntity) -> Result<(), RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("cannot establish transaction"))?;
+
+        let result = sqlx::query(
+            "UPDATE books SET title = $1, isbn = $2, publisher = $3, page_count = $4, \
+             language = $5, published_date = $6 WHERE id = $7",
+        )
+        .bind(book.title)
+        .bind(book.isbn.map(String::from))
+        .bind(book.publisher)
+        .bind(book.page_count)
+        .bind(book.language)
+        .bind(book.published_date)
+        .bind(book.id as super::Pid)
+        .execute(&mut transaction)
+        .await
+        .map_err(pg_error("update was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+
+        sqlx::query("DELETE FROM book_authors WHERE book_id = $1")
+            .bind(book.id as super::Pid)
+            .execute(&mut transaction)
+            .await
+            .map_err(pg_error("delete was failed"))?;
+        set_authors(&mut transaction, book.id as super::Pid, &book.authors).await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))
+    }
+
+    async fn delete_book(&self, user_id: u32, book_id: u32) -> Result<(), RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("cannot establish transaction"))?;
+
+        // 行をロックして、確認してから削除するまでに他のユーザが参照を追加できないようにする
+        sqlx::query("SELECT id FROM books WHERE id = $1 FOR UPDATE")
+            .bind(book_id as super::Pid)
             .fetch_one(&mut transaction)
             .await
-            .map_err(|err| {
-                tracing::info!("insert was failed: {}", err);
-            })?;
+            .map_err(pg_error("cannot fetch the book"))?;
 
-        transaction.commit().await.map_err(|err| {
-            tracing::info!("commiting was failed: {}", err);
-        })?;
+        // 削除すると参照している行もCASCADEで消えるので、他のユーザのものがあれば拒む
+        let referenced: bool = sqlx::query(
+            "SELECT EXISTS (SELECT 1 FROM user_books WHERE book_id = $1 AND user_id <> $2) \
+             OR EXISTS (SELECT 1 FROM records WHERE book_id = $1 AND user_id <> $2) \
+             OR EXISTS (SELECT 1 FROM readings WHERE book_id = $1 AND user_id <> $2) \
+             AS referenced",
+        )
+        .bind(book_id as super::Pid)
+        .bind(user_id as super::Pid)
+        .fetch_one(&mut transaction)
+        .await
+        .and_then(|row| row.try_get("referenced"))
+        .map_err(pg_error("cannot check references to the book"))?;
+        if referenced {
+            return Err(RepoError::Conflict);
+        }
 
-        row.try_get::<i32, _>("id")
-            // SQLの仕様ではsignedだが、値は0以上のものが返ってくる
-            .map(|id| id as u32)
-            .map_err(|err| {
-                tracing::info!("parsing inserted id was failed: {}", err);
-            })
+        sqlx::query("DELETE FROM books WHERE id = $1")
+            .bind(book_id as super::Pid)
+            .execute(&mut transaction)
+            .await
+            .map_err(pg_error("delete was failed"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))
     }
 
-    async fn update_book(&self, book: BookEntity) -> bool {
-        let inner = || async move {
-            let mut transaction = self.pool.begin().await.map_err(|err| {
-                tracing::info!("cannot establish transaction: {}", err);
-            })?;
+    async fn add_to_library(&self, user_id: u32, book_id: u32) -> Result<(), RepoError> {
+        sqlx::query(
+            "INSERT INTO user_books (user_id, book_id) VALUES ($1, $2) \
+             ON CONFLICT (user_id, book_id) DO NOTHING",
+        )
+        .bind(user_id as super::Pid)
+        .bind(book_id as super::Pid)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|err| match pg_error("insert was failed")(err) {
+            // 外部キー制約に反するのは本が存在しない場合だけ
+            RepoError::Conflict => RepoError::NotFound,
+            err => err,
+        })
+    }
 
-            let result = sqlx::query("UPDATE books SET title = $1 WHERE id = $2")
-                .bind(book.title)
-                .bind(book.id)
-                .execute(&mut transaction)
-                .await
-                .map_err(|err| {
-                    tracing::info!("update was failed: {}", err);
-                })?;
+    async fn remove_from_library(&self, user_id: u32, book_id: u32) -> Result<(), RepoError> {
+        let result = sqlx::query("DELETE FROM user_books WHERE user_id = $1 AND book_id = $2")
+            .bind(user_id as super::Pid)
+            .bind(book_id as super::Pid)
+            .execute(&self.pool)
+            .await
+            .map_err(pg_error("delete was failed"))?;
 
-            transaction.commit().await.map_err(|err| {
-                tracing::info!("commiting was failed: {}", err);
-            })?;
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::infra::repo::Pid;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_build_search_books_query() {
+        let sql = build_search_books_query(2, false);
+        assert!(sql.contains("(b.title ILIKE $1 ESCAPE '\\' OR "));
+        assert!(sql.contains(" OR $2 <% b.title) AND (b.title ILIKE $3 "));
+        assert!(sql.contains("word_similarity($4, b.title)"));
+        assert!(!sql.contains("b.isbn"));
+        assert!(sql.ends_with(" DESC, b.id LIMIT $5"));
+
+        let sql = build_search_books_query(1, true);
+        assert!(sql.contains(" OR b.isbn = $3) GROUP BY b.id "));
+        assert!(sql.ends_with(" DESC, b.id LIMIT $4"));
+    }
 
-            Ok(result.rows_affected() == 1)
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!(like_pattern("Rust"), "%Rust%");
+        assert_eq!(like_pattern("100%_\\"), "%100\\%\\_\\\\%");
+    }
+
+    #[test]
+    fn test_build_list_books_query_first_page() {
+        let sql = build_list_books_query(&BookListQuery::default());
+        assert!(sql.ends_with(
+            " WHERE b.id IN (SELECT b.id FROM books b ORDER BY b.id ASC LIMIT $1) \
+             GROUP BY b.id ORDER BY b.id ASC"
+        ));
+    }
+
+    #[test]
+    fn test_build_list_books_query_after_cursor() {
+        let query = BookListQuery {
+            sort: BookSortKey::CreatedAt,
+            order: SortOrder::Desc,
+            after: Some(BookCursor {
+                key: BookCursorKey::CreatedAt(Utc.ymd(2021, 12, 26).and_hms(0, 0, 0)),
+                order: SortOrder::Desc,
+                id: 10,
+            }),
+            ..BookListQuery::default()
         };
 
-        let result: Result<bool, ()> = inner().await;
-        if let Ok(result) = result {
-            result
-        } else {
-            false
-        }
+        let sql = build_list_books_query(&query);
+        assert!(sql.ends_with(
+            " WHERE b.id IN (SELECT b.id FROM books b \
+             WHERE (b.created_at, b.id) < ($1, $2) \
+             ORDER BY b.created_at DESC, b.id DESC LIMIT $3) \
+             GROUP BY b.id ORDER BY b.created_at DESC, b.id DESC"
+        ));
     }
 
-    async fn delete_book(&self, book_id: u32) -> bool {
-        let inner = || async move {
-            let mut transaction = self.pool.begin().await.map_err(|err| {
-                tracing::info!("cannot establish transaction: {}", err);
-            })?;
+    #[test]
+    fn test_build_list_books_query_in_library() {
+        let query = BookListQuery {
+            after: Some(BookCursor {
+                key: BookCursorKey::Title("t".to_string()),
+                order: SortOrder::Asc,
+                id: 10,
+            }),
+            sort: BookSortKey::Title,
+            library_of: Some(1),
+            ..BookListQuery::default()
+        };
+
+        let sql = build_list_books_query(&query);
+        assert!(sql.ends_with(
+            " WHERE b.id IN (SELECT b.id FROM books b \
+             WHERE (b.title, b.id) > ($1, $2) \
+             AND b.id IN (SELECT book_id FROM user_books WHERE user_id = $3) \
+             ORDER BY b.title ASC, b.id ASC LIMIT $4) \
+             GROUP BY b.id ORDER BY b.title ASC, b.id ASC"
+        ));
+    }
+
+    #[test]
+    fn test_build_list_books_query_after_id() {
+        let query = BookListQuery {
+            after: Some(BookCursor {
+                key: BookCursorKey::Id,
+                order: SortOrder::Asc,
+                id: 10,
+            }),
+            ..BookListQuery::default()
+        };
+
+        let sql = build_list_books_query(&query);
+        assert!(sql.ends_with(
+            " WHERE b.id IN (SELECT b.id FROM books b WHERE b.id > $1 ORDER BY b.id ASC LIMIT $2) \
+             GROUP BY b.id ORDER BY b.id ASC"
+        ));
+    }
 
-            let result = sqlx::query("DELETE FROM books WHERE id = $1")
-                .bind(book_id)
-                .execute(&mut transaction)
+    // `sqlx migrate run`で適用済みのDBで、一覧のSQLを実際に実行する。
+    // 他のテストの本と混ざらないよう、専用のユーザの本棚に絞り込む。
+    #[tokio::test]
+    #[ignore]
+    async fn test_list_books_in_library() {
+        let settings = envy::from_env::<Settings>().unwrap();
+        let pool = PgPool::connect(&settings.database_url).await.unwrap();
+        let user_id: Pid =
+            sqlx::query("INSERT INTO users (subject, username) VALUES ($1, $1) RETURNING id")
+                .bind(Uuid::new_v4().to_string())
+                .fetch_one(&pool)
                 .await
-                .map_err(|err| {
-                    tracing::info!("delete was failed: {}", err);
-                })?;
+                .unwrap()
+                .get(0);
+        let user_id = user_id as u32;
+        let repo = BookRepositoryImpl::new(pool.clone());
 
-            transaction.commit().await.map_err(|err| {
-                tracing::info!("commiting was failed: {}", err);
-            })?;
+        let mut book_ids = vec![];
+        for (title, authors) in [("c", vec![]), ("a", vec!["x", "y"]), ("b", vec!["z"])] {
+            let book = BookEntityForCreation {
+                title: title.to_string(),
+                authors: authors.into_iter().map(String::from).collect(),
+                isbn: None,
+                publisher: None,
+                page_count: None,
+                language: None,
+                published_date: None,
+            };
+            book_ids.push(repo.create_book(user_id, book).await.unwrap());
+        }
 
-            Ok(result.rows_affected() == 1)
+        let mut query = BookListQuery {
+            limit: 2,
+            sort: BookSortKey::Title,
+            library_of: Some(user_id),
+            with_total: true,
+            ..BookListQuery::default()
         };
+        let first = repo.list_books(query.clone()).await;
+        query.after = first.as_ref().ok().and_then(|page| page.next.clone());
+        let second = repo.list_books(query).await;
 
-        let result: Result<bool, ()> = inner().await;
-        if let Ok(result) = result {
-            result
-        } else {
-            false
+        for book_id in book_ids {
+            repo.delete_book(user_id, book_id).await.unwrap();
         }
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id as Pid)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let first = first.unwrap();
+        let books: Vec<_> = first
+            .books
+            .iter()
+            .map(|book| (book.title.as_str(), book.authors.clone()))
+            .collect();
+        assert_eq!(
+            books,
+            vec![
+                ("a", vec!["x".to_string(), "y".to_string()]),
+                ("b", vec!["z".to_string()]),
+            ]
+        );
+        assert_eq!(first.total, Some(3));
+
+        let second = second.unwrap();
+        assert_eq!(second.books.len(), 1);
+        assert_eq!(second.books[0].title, "c");
+        assert!(second.books[0].authors.is_empty());
+        assert!(second.next.is_none());
     }
 }
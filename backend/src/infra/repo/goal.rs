@@ -0,0 +1,106 @@
+use axum::async_trait;
+use sqlx::{postgres::PgPool, Row};
+
+use crate::domain::entity::{self, goal::GoalEntity};
+use crate::domain::repo_if::{goal::GoalRepository, RepoError};
+use crate::infra::repo::{pg_error, schema::GoalRow};
+
+pub struct GoalRepositoryImpl {
+    pool: PgPool,
+}
+
+impl GoalRepositoryImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl GoalRepository for GoalRepositoryImpl {
+    async fn list_goals(&self, user_id: entity::Pid) -> Result<Vec<GoalEntity>, RepoError> {
+        let rows = sqlx::query_as::<_, GoalRow>(
+            "SELECT * FROM goals WHERE user_id = $1 ORDER BY starts_on DESC, id DESC",
+        )
+        .bind(user_id as super::Pid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(pg_error("cannot fetch goals"))?;
+
+        Ok(rows.into_iter().map(GoalEntity::from).collect())
+    }
+
+    async fn get_goal(
+        &self,
+        user_id: entity::Pid,
+        goal_id: entity::Pid,
+    ) -> Result<GoalEntity, RepoError> {
+        sqlx::query_as::<_, GoalRow>("SELECT * FROM goals WHERE id = $1 AND user_id = $2")
+            .bind(goal_id as super::Pid)
+            .bind(user_id as super::Pid)
+            .fetch_one(&self.pool)
+            .await
+            .map(GoalEntity::from)
+            .map_err(pg_error("cannot fetch the goal"))
+    }
+
+    async fn create_goal(&self, goal: GoalEntity) -> Result<entity::Pid, RepoError> {
+        let row = sqlx::query(
+            "INSERT INTO goals (user_id, metric, period, target, starts_on, timezone) \
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        )
+        .bind(goal.user_id as super::Pid)
+        .bind(goal.metric.as_str())
+        .bind(goal.period.as_str())
+        .bind(goal.target)
+        .bind(goal.starts_on)
+        .bind(goal.timezone.name())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(pg_error("insert was failed"))?;
+
+        row.try_get::<i32, _>("id")
+            // SQLの仕様ではsignedだが、値は0以上のものが返ってくる
+            .map(|id| id as entity::Pid)
+            .map_err(pg_error("parsing inserted id was failed"))
+    }
+
+    async fn update_goal(&self, goal: GoalEntity) -> Result<(), RepoError> {
+        let result = sqlx::query(
+            "UPDATE goals SET metric = $1, period = $2, target = $3, starts_on = $4, timezone = $5 \
+             WHERE id = $6 AND user_id = $7",
+        )
+        .bind(goal.metric.as_str())
+        .bind(goal.period.as_str())
+        .bind(goal.target)
+        .bind(goal.starts_on)
+        .bind(goal.timezone.name())
+        .bind(goal.id as super::Pid)
+        .bind(goal.user_id as super::Pid)
+        .execute(&self.pool)
+        .await
+        .map_err(pg_error("update was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_goal(
+        &self,
+        user_id: entity::Pid,
+        goal_id: entity::Pid,
+    ) -> Result<(), RepoError> {
+        let result = sqlx::query("DELETE FROM goals WHERE id = $1 AND user_id = $2")
+            .bind(goal_id as super::Pid)
+            .bind(user_id as super::Pid)
+            .execute(&self.pool)
+            .await
+            .map_err(pg_error("delete was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+//! 外部サービスに依存しない、メモリ上のrepository。
+//! Postgres, Redis, IdPなしでHTTPの振る舞いをテストするために使う。
+
+pub mod book;
+pub mod goal;
+pub mod record;
+pub mod shelf;
+pub mod stats;
+pub mod user;
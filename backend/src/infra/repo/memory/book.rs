@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use axum::async_trait;
+
+use crate::domain::entity::{
+    book::{
+        BookCursor, BookCursorKey, BookEntity, BookEntityForCreation, BookListQuery, BookPage,
+        BookSearchQuery, SortOrder,
+    },
+    Pid,
+};
+use crate::domain::repo_if::{book::BookRepository, RepoError};
+
+#[derive(Default)]
+pub struct InMemoryBookRepository {
+    books: Mutex<BTreeMap<Pid, BookEntity>>,
+    last_id: Mutex<Pid>,
+    /// 本棚に入れた`(ユーザID, 本のID)`
+    libraries: Mutex<BTreeSet<(Pid, Pid)>>,
+}
+
+/// Postgresの一意制約と同じく、ISBNの重複を`RepoError::Conflict`にする
+fn check_isbn(books: &BTreeMap<Pid, BookEntity>, book: &BookEntity) -> Result<(), RepoError> {
+    let duplicated = book.isbn.is_some()
+        && books
+            .values()
+            .any(|other| other.id != book.id && other.isbn == book.isbn);
+    if duplicated {
+        Err(RepoError::Conflict)
+    } else {
+        Ok(())
+    }
+}
+
+/// Postgres版と同じく`(並び替えのキー, id)`の組で比較する
+fn compare(book: &BookEntity, key: &BookCursorKey, id: Pid) -> Ordering {
+    match key {
+        BookCursorKey::Id => Ordering::Equal,
+        BookCursorKey::Title(title) => book.title.cmp(title),
+        BookCursorKey::CreatedAt(created_at) => book.created_at.cmp(created_at),
+    }
+    .then(book.id.cmp(&id))
+}
+
+/// Postgres版の類似度による一致は省き、大文字小文字を区別しない部分一致だけで関連度を付ける
+fn search_score(book: &BookEntity, query: &BookSearchQuery) -> Option<f64> {
+    if query.isbn.is_some() && book.isbn == query.isbn {
+        return Some(3.0);
+    }
+
+    let title = book.title.to_lowercase();
+    let authors: Vec<String> = book.authors.iter().map(|a| a.to_lowercase()).collect();
+    let mut score = 0.0;
+    for term in query.terms.iter().map(|term| term.to_lowercase()) {
+        let title_matches = title.contains(&term);
+        let author_matches = authors.iter().any(|author| author.contains(&term));
+        if !title_matches && !author_matches {
+            return None;
+        }
+        if title_matches {
+            score += 1.0;
+        }
+        if author_matches {
+            score += 0.5;
+        }
+    }
+    Some(score)
+}
+
+#[async_trait]
+impl BookRepository for InMemoryBookRepository {
+    async fn list_books(&self, query: BookListQuery) -> Result<BookPage, RepoError> {
+        let mut books: Vec<BookEntity> = self.books.lock().unwrap().values().cloned().collect();
+        if let Some(user_id) = query.library_of {
+            let libraries = self.libraries.lock().unwrap();
+            books.retain(|book| libraries.contains(&(user_id, book.id)));
+        }
+        let total = books.len() as i64;
+
+        books.sort_by(|a, b| {
+            let key = BookCursor::after(b, query.sort, query.order).key;
+            match query.order {
+                SortOrder::Asc => compare(a, &key, b.id),
+                SortOrder::Desc => compare(a, &key, b.id).reverse(),
+            }
+        });
+        if let Some(cursor) = &query.after {
+            books.retain(|book| {
+                let ordering = compare(book, &cursor.key, cursor.id);
+                match query.order {
+                    SortOrder::Asc => ordering == Ordering::Greater,
+                    SortOrder::Desc => ordering == Ordering::Less,
+                }
+            });
+        }
+
+        let next = if books.len() > query.limit as usize {
+            books.truncate(query.limit as usize);
+            books
+                .last()
+                .map(|book| BookCursor::after(book, query.sort, query.order))
+        } else {
+            None
+        };
+
+        Ok(BookPage {
+            books,
+            next,
+            total: if query.with_total { Some(total) } else { None },
+        })
+    }
+
+    async fn search_books(&self, query: &BookSearchQuery) -> Result<Vec<BookEntity>, RepoError> {
+        let mut hits: Vec<(f64, BookEntity)> = self
+            .books
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|book| search_score(book, query).map(|score| (score, book.clone())))
+            .collect();
+        hits.sort_by(|(a, a_book), (b, b_book)| b.total_cmp(a).then(a_book.id.cmp(&b_book.id)));
+
+        Ok(hits
+            .into_iter()
+            .take(query.limit as usize)
+            .map(|(_, book)| book)
+            .collect())
+    }
+
+    async fn get_book(&self, book_id: Pid) -> Result<BookEntity, RepoError> {
+        self.books
+            .lock()
+            .unwrap()
+            .get(&book_id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn create_book(
+        &self,
+        created_by: Pid,
+        book: BookEntityForCreation,
+    ) -> Result<Pid, RepoError> {
+        let mut books = self.books.lock().unwrap();
+        let mut last_id = self.last_id.lock().unwrap();
+
+        let book = BookEntity {
+            created_by: Some(created_by),
+            ..BookEntity::from((*last_id + 1, book))
+        };
+        check_isbn(&books, &book)?;
+
+        *last_id = book.id;
+        books.insert(book.id, book);
+        self.libraries
+            .lock()
+            .unwrap()
+            .insert((created_by, *last_id));
+        Ok(*last_id)
+    }
+
+    async fn update_book(&self, book: BookEntity) -> Result<(), RepoError> {
+        let mut books = self.books.lock().unwrap();
+        let (created_at, created_by) = match books.get(&book.id) {
+            Some(stored) => (stored.created_at, stored.created_by),
+            None => return Err(RepoError::NotFound),
+        };
+        check_isbn(&books, &book)?;
+
+        books.insert(
+            book.id,
+            BookEntity {
+                created_at,
+                created_by,
+                ..book
+            },
+        );
+        Ok(())
+    }
+
+    /// 読書記録や読書は他のrepositoryにあるので、本棚に入れているかだけで参照を確認する
+    async fn delete_book(&self, user_id: Pid, book_id: Pid) -> Result<(), RepoError> {
+        let referenced = self
+            .libraries
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|&(other, id)| id == book_id && other != user_id);
+        if referenced && self.books.lock().unwrap().contains_key(&book_id) {
+            return Err(RepoError::Conflict);
+        }
+        self.books
+            .lock()
+            .unwrap()
+            .remove(&book_id)
+            .ok_or(RepoError::NotFound)?;
+        self.libraries
+            .lock()
+            .unwrap()
+            .retain(|(_, id)| *id != book_id);
+        Ok(())
+    }
+
+    async fn add_to_library(&self, user_id: Pid, book_id: Pid) -> Result<(), RepoError> {
+        if !self.books.lock().unwrap().contains_key(&book_id) {
+            return Err(RepoError::NotFound);
+        }
+        self.libraries.lock().unwrap().insert((user_id, book_id));
+        Ok(())
+    }
+
+    async fn remove_from_library(&self, user_id: Pid, book_id: Pid) -> Result<(), RepoError> {
+        if self.libraries.lock().unwrap().remove(&(user_id, book_id)) {
+            Ok(())
+        } else {
+            Err(RepoError::NotFound)
+        }
+    }
+}
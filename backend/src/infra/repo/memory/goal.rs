@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+
+use crate::domain::entity::{goal::GoalEntity, Pid};
+use crate::domain::repo_if::{goal::GoalRepository, RepoError};
+
+#[derive(Default)]
+pub struct InMemoryGoalRepository {
+    goals: Mutex<BTreeMap<Pid, GoalEntity>>,
+    last_id: Mutex<Pid>,
+}
+
+/// Postgresの一意インデックスと同じく、同じ期間に同じものを数える目標の重複を拒む
+fn is_duplicated(goals: &BTreeMap<Pid, GoalEntity>, goal: &GoalEntity) -> bool {
+    goals.values().any(|other| {
+        other.id != goal.id
+            && other.user_id == goal.user_id
+            && other.metric == goal.metric
+            && other.period == goal.period
+            && other.starts_on == goal.starts_on
+    })
+}
+
+#[async_trait]
+impl GoalRepository for InMemoryGoalRepository {
+    async fn list_goals(&self, user_id: Pid) -> Result<Vec<GoalEntity>, RepoError> {
+        let mut goals: Vec<GoalEntity> = self
+            .goals
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|goal| goal.user_id == user_id)
+            .cloned()
+            .collect();
+        goals.sort_by(|a, b| b.starts_on.cmp(&a.starts_on).then(b.id.cmp(&a.id)));
+        Ok(goals)
+    }
+
+    async fn get_goal(&self, user_id: Pid, goal_id: Pid) -> Result<GoalEntity, RepoError> {
+        self.goals
+            .lock()
+            .unwrap()
+            .get(&goal_id)
+            .filter(|goal| goal.user_id == user_id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn create_goal(&self, goal: GoalEntity) -> Result<Pid, RepoError> {
+        let mut goals = self.goals.lock().unwrap();
+        let mut last_id = self.last_id.lock().unwrap();
+
+        let goal = GoalEntity {
+            id: *last_id + 1,
+            ..goal
+        };
+        if is_duplicated(&goals, &goal) {
+            return Err(RepoError::Conflict);
+        }
+
+        *last_id += 1;
+        goals.insert(*last_id, goal);
+        Ok(*last_id)
+    }
+
+    async fn update_goal(&self, goal: GoalEntity) -> Result<(), RepoError> {
+        let mut goals = self.goals.lock().unwrap();
+        match goals.get(&goal.id) {
+            Some(stored) if stored.user_id == goal.user_id => {
+                if is_duplicated(&goals, &goal) {
+                    return Err(RepoError::Conflict);
+                }
+                goals.insert(goal.id, goal);
+                Ok(())
+            }
+            _ => Err(RepoError::NotFound),
+        }
+    }
+
+    async fn delete_goal(&self, user_id: Pid, goal_id: Pid) -> Result<(), RepoError> {
+        let mut goals = self.goals.lock().unwrap();
+        match goals.get(&goal_id) {
+            Some(stored) if stored.user_id == user_id => {
+                goals.remove(&goal_id);
+                Ok(())
+            }
+            _ => Err(RepoError::NotFound),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::Utc;
+
+use crate::domain::entity::{
+    record::{RecordEntity, RecordEntityForCreation, RecordFilter},
+    Pid,
+};
+use crate::domain::repo_if::{record::RecordRepository, RepoError};
+
+#[derive(Default)]
+pub struct InMemoryRecordRepository {
+    records: Mutex<BTreeMap<Pid, RecordEntity>>,
+    last_id: Mutex<Pid>,
+}
+
+fn matches_filter(record: &RecordEntity, filter: &RecordFilter) -> bool {
+    (filter.user_ids.is_empty() || filter.user_ids.contains(&record.user_id))
+        && (filter.book_ids.is_empty() || filter.book_ids.contains(&record.book_id))
+        && filter
+            .since
+            .is_none_or(|since| record.registered_datetime >= since)
+        && filter
+            .until
+            .is_none_or(|until| record.registered_datetime < until)
+}
+
+#[async_trait]
+impl RecordRepository for InMemoryRecordRepository {
+    async fn list_records(&self, filter: RecordFilter) -> Result<Vec<RecordEntity>, RepoError> {
+        let mut records: Vec<RecordEntity> = self
+            .records
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|record| matches_filter(record, &filter))
+            .cloned()
+            .collect();
+        records.sort_by_key(|record| (record.registered_datetime, record.id));
+        Ok(records)
+    }
+
+    async fn get_record(&self, record_id: Pid) -> Result<RecordEntity, RepoError> {
+        self.records
+            .lock()
+            .unwrap()
+            .get(&record_id)
+            .cloned()
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn create_record(
+        &self,
+        user_id: Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<Pid, RepoError> {
+        let mut records = self.records.lock().unwrap();
+        let mut last_id = self.last_id.lock().unwrap();
+
+        *last_id += 1;
+        records.insert(
+            *last_id,
+            RecordEntity {
+                id: *last_id,
+                user_id,
+                book_id: record.book_id,
+                start_page: record.start_page,
+                end_page: record.end_page,
+                registered_datetime: Utc::now(),
+                comment: record.comment,
+            },
+        );
+        Ok(*last_id)
+    }
+
+    async fn update_record(
+        &self,
+        user_id: Pid,
+        record_id: Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<(), RepoError> {
+        match self.records.lock().unwrap().get_mut(&record_id) {
+            Some(stored) if stored.user_id == user_id => {
+                stored.book_id = record.book_id;
+                stored.start_page = record.start_page;
+                stored.end_page = record.end_page;
+                stored.comment = record.comment;
+                Ok(())
+            }
+            _ => Err(RepoError::NotFound),
+        }
+    }
+
+    async fn delete_record(&self, user_id: Pid, record_id: Pid) -> Result<(), RepoError> {
+        let mut records = self.records.lock().unwrap();
+        match records.get(&record_id) {
+            Some(stored) if stored.user_id == user_id => {
+                records.remove(&record_id);
+                Ok(())
+            }
+            _ => Err(RepoError::NotFound),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+
+use crate::domain::entity::{shelf::ReadingEntity, Pid};
+use crate::domain::repo_if::{shelf::ShelfRepository, RepoError};
+
+#[derive(Default)]
+pub struct InMemoryShelfRepository {
+    readings: Mutex<BTreeMap<Pid, ReadingEntity>>,
+    last_id: Mutex<Pid>,
+}
+
+#[async_trait]
+impl ShelfRepository for InMemoryShelfRepository {
+    async fn list_readings(&self, user_id: Pid) -> Result<Vec<ReadingEntity>, RepoError> {
+        Ok(self
+            .readings
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|reading| reading.user_id == user_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_book_readings(
+        &self,
+        user_id: Pid,
+        book_id: Pid,
+    ) -> Result<Vec<ReadingEntity>, RepoError> {
+        Ok(self
+            .readings
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|reading| reading.user_id == user_id && reading.book_id == book_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn current_reading(
+        &self,
+        user_id: Pid,
+        book_id: Pid,
+    ) -> Result<Option<ReadingEntity>, RepoError> {
+        Ok(self
+            .readings
+            .lock()
+            .unwrap()
+            .values()
+            .find(|reading| {
+                reading.user_id == user_id
+                    && reading.book_id == book_id
+                    && !reading.status.is_closed()
+            })
+            .cloned())
+    }
+
+    async fn create_reading(&self, reading: ReadingEntity) -> Result<Pid, RepoError> {
+        let mut readings = self.readings.lock().unwrap();
+        let mut last_id = self.last_id.lock().unwrap();
+
+        // Postgresの部分一意インデックスと同じく、読み終えていない読書の重複を拒む
+        let duplicated = !reading.status.is_closed()
+            && readings.values().any(|other| {
+                other.user_id == reading.user_id
+                    && other.book_id == reading.book_id
+                    && !other.status.is_closed()
+            });
+        if duplicated {
+            return Err(RepoError::Conflict);
+        }
+
+        *last_id += 1;
+        readings.insert(
+            *last_id,
+            ReadingEntity {
+                id: *last_id,
+                ..reading
+            },
+        );
+        Ok(*last_id)
+    }
+
+    async fn update_reading(&self, reading: ReadingEntity) -> Result<(), RepoError> {
+        match self.readings.lock().unwrap().get_mut(&reading.id) {
+            Some(stored) => {
+                stored.status = reading.status;
+                stored.started_at = reading.started_at;
+                stored.finished_at = reading.finished_at;
+                stored.updated_at = reading.updated_at;
+                Ok(())
+            }
+            None => Err(RepoError::NotFound),
+        }
+    }
+
+    async fn delete_reading(&self, user_id: Pid, reading_id: Pid) -> Result<(), RepoError> {
+        let mut readings = self.readings.lock().unwrap();
+        match readings.get(&reading_id) {
+            Some(stored) if stored.user_id == user_id => {
+                readings.remove(&reading_id);
+                Ok(())
+            }
+            _ => Err(RepoError::NotFound),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use super::{record::InMemoryRecordRepository, shelf::InMemoryShelfRepository};
+use crate::domain::entity::{
+    record::RecordFilter,
+    shelf::ShelfStatus,
+    stats::{BooksPoint, PagesPoint, StatsInterval, StatsQuery, Streaks},
+    Pid,
+};
+use crate::domain::repo_if::{
+    record::RecordRepository, shelf::ShelfRepository, stats::StatsRepository, RepoError,
+};
+
+/// 他のメモリ上のrepositoryに入っている記録と読書を集計する
+pub struct InMemoryStatsRepository {
+    record_repository: Arc<InMemoryRecordRepository>,
+    shelf_repository: Arc<InMemoryShelfRepository>,
+}
+
+impl InMemoryStatsRepository {
+    pub fn new(
+        record_repository: Arc<InMemoryRecordRepository>,
+        shelf_repository: Arc<InMemoryShelfRepository>,
+    ) -> Self {
+        Self {
+            record_repository,
+            shelf_repository,
+        }
+    }
+
+    /// 期間内の記録を、付けた日付と読んだページ数の組にする
+    async fn daily_pages(
+        &self,
+        user_id: Pid,
+        query: &StatsQuery,
+    ) -> Result<Vec<(NaiveDate, i64)>, RepoError> {
+        let records = self
+            .record_repository
+            .list_records(RecordFilter {
+                user_ids: vec![user_id],
+                ..Default::default()
+            })
+            .await?;
+        Ok(records
+            .into_iter()
+            .map(|record| {
+                (
+                    local_date(record.registered_datetime, query),
+                    (record.end_page - record.start_page + 1) as i64,
+                )
+            })
+            .filter(|(day, _)| (query.since..=query.until).contains(day))
+            .collect())
+    }
+}
+
+fn local_date(datetime: DateTime<Utc>, query: &StatsQuery) -> NaiveDate {
+    datetime.with_timezone(&query.timezone).naive_local().date()
+}
+
+/// 区間ごとに合計し、記録のない区間も0として埋める
+fn per_interval(
+    values: impl IntoIterator<Item = (NaiveDate, i64)>,
+    query: &StatsQuery,
+    interval: StatsInterval,
+) -> Vec<(NaiveDate, i64, i64)> {
+    let mut sums = BTreeMap::new();
+    for (day, value) in values {
+        *sums.entry(interval.start_of(day)).or_insert(0) += value;
+    }
+    let mut cumulative = 0;
+    query
+        .periods(interval)
+        .into_iter()
+        .map(|start| {
+            let value = sums.get(&start).copied().unwrap_or(0);
+            cumulative += value;
+            (start, value, cumulative)
+        })
+        .collect()
+}
+
+#[async_trait]
+impl StatsRepository for InMemoryStatsRepository {
+    async fn pages_per_interval(
+        &self,
+        user_id: Pid,
+        query: &StatsQuery,
+        interval: StatsInterval,
+    ) -> Result<Vec<PagesPoint>, RepoError> {
+        let daily_pages = self.daily_pages(user_id, query).await?;
+        Ok(per_interval(daily_pages, query, interval)
+            .into_iter()
+            .map(|(start, pages, cumulative_pages)| PagesPoint {
+                start,
+                pages,
+                cumulative_pages,
+            })
+            .collect())
+    }
+
+    async fn books_finished_per_month(
+        &self,
+        user_id: Pid,
+        query: &StatsQuery,
+    ) -> Result<Vec<BooksPoint>, RepoError> {
+        let finished = self
+            .shelf_repository
+            .list_readings(user_id)
+            .await?
+            .into_iter()
+            .filter(|reading| reading.status == ShelfStatus::Finished)
+            .filter_map(|reading| reading.finished_at)
+            .map(|finished_at| (local_date(finished_at, query), 1))
+            .filter(|(day, _)| (query.since..=query.until).contains(day));
+        Ok(per_interval(finished, query, StatsInterval::Month)
+            .into_iter()
+            .map(|(start, books, cumulative_books)| BooksPoint {
+                start,
+                books,
+                cumulative_books,
+            })
+            .collect())
+    }
+
+    async fn streaks(&self, user_id: Pid, query: &StatsQuery) -> Result<Streaks, RepoError> {
+        let days: BTreeSet<NaiveDate> = self
+            .daily_pages(user_id, query)
+            .await?
+            .into_iter()
+            .map(|(day, _)| day)
+            .collect();
+        let days: Vec<NaiveDate> = days.into_iter().collect();
+        Ok(Streaks::from_days(&days, query.until))
+    }
+}
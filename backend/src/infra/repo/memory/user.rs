@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use axum::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use openidconnect::core::CoreClient;
+use openidconnect::{Nonce, PkceCodeChallenge};
+use uuid::Uuid;
+
+use super::super::session::{LoginSessionStorage, RefreshSessionStorage};
+use super::super::user::exchange_code;
+use crate::domain::entity::{
+    user::{
+        ClientInfo, LoginError, LoginSession, RefreshSession, RefreshToken, RefreshTokenError,
+        RefreshTokenExtract, SignUpCode, SignUpError, UserEntityForCreation,
+    },
+    Pid,
+};
+use crate::domain::repo_if::{user::UserRepository, RepoError};
+use crate::settings::Settings;
+
+/// IdPの代わりに`issue_authorization_code`で認可コードを発行できる、メモリ上のrepository。
+/// `with_id_provider`でIdPを設定した場合は、認可コードをIdPで引き換える。
+/// ログインセッションとsign upコードの有効期限は扱わない。
+pub struct InMemoryUserRepository {
+    settings: Settings,
+    client: Option<CoreClient>,
+    store: Mutex<Store>,
+}
+
+#[derive(Default)]
+struct Store {
+    /// インデックス+1がユーザID
+    users: Vec<(String, String)>,
+    login_sessions: HashMap<String, LoginSessionStorage>,
+    /// 認可コードと、その発行時に渡されたnonceとsubject
+    authorization_codes: HashMap<String, (String, String)>,
+    sign_up_codes: HashMap<String, String>,
+    /// ローテーション済みのものも含むrefresh tokenと、そのセッションIDおよび有効期限
+    refresh_tokens: HashMap<String, (String, DateTime<Utc>)>,
+    refresh_sessions: HashMap<String, RefreshSessionStorage>,
+}
+
+impl Store {
+    fn user_id_from_subject(&self, subject: &str) -> Option<Pid> {
+        self.users
+            .iter()
+            .position(|(stored, _)| stored == subject)
+            .map(|index| index as Pid + 1)
+    }
+
+    /// Postgres/Redis版と同じく、ローテーション済みのtokenが提示された場合はセッションごと無効にする
+    fn verify_refresh_token(&mut self, token: &str) -> Result<String, RefreshTokenError> {
+        let session_id = match self.refresh_tokens.get(token) {
+            Some((session_id, expires_at)) if *expires_at > Utc::now() => session_id.to_owned(),
+            _ => return Err(RefreshTokenError::InvalidRefreshToken),
+        };
+
+        match self.refresh_sessions.get(&session_id) {
+            Some(session) if session.token == token => Ok(session_id),
+            Some(session) => {
+                tracing::warn!(
+                    target: "security",
+                    "refresh token reuse detected; revoking session {} of user {}",
+                    session_id,
+                    session.user_id,
+                );
+                self.refresh_sessions.remove(&session_id);
+                Err(RefreshTokenError::InvalidRefreshToken)
+            }
+            None => Err(RefreshTokenError::InvalidRefreshToken),
+        }
+    }
+}
+
+impl InMemoryUserRepository {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            client: None,
+            store: Mutex::new(Store::default()),
+        }
+    }
+
+    pub fn with_id_provider(mut self, client: CoreClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// IdPでの認証が済んだものとして認可コードを発行する。
+    /// `nonce`はログインセッションの作成時に返されたものを渡す。
+    pub fn issue_authorization_code(&self, nonce: &str, subject: &str) -> String {
+        let code = Uuid::new_v4().to_string();
+        self.store
+            .lock()
+            .unwrap()
+            .authorization_codes
+            .insert(code.to_owned(), (nonce.to_string(), subject.to_string()));
+        code
+    }
+
+    /// セッションに新しいrefresh tokenを発行し、その値とともに返す
+    fn new_refresh_token(&self, store: &mut Store, session_id: &str) -> (String, RefreshToken) {
+        let token = Uuid::new_v4().to_string();
+        let expires_at = Utc::now() + Duration::seconds(self.settings.refresh_exp as i64);
+        store
+            .refresh_tokens
+            .insert(token.to_owned(), (session_id.to_string(), expires_at));
+        (token.to_owned(), RefreshToken::new(token, expires_at))
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUserRepository {
+    async fn get_user_id_from_subject(&self, subject: &str) -> Result<Pid, RepoError> {
+        self.store
+            .lock()
+            .unwrap()
+            .user_id_from_subject(subject)
+            .ok_or(RepoError::NotFound)
+    }
+
+    async fn create_user(
+        &self,
+        subject: String,
+        user: UserEntityForCreation,
+    ) -> Result<Pid, RepoError> {
+        let mut store = self.store.lock().unwrap();
+        if store.user_id_from_subject(&subject).is_some() {
+            return Err(RepoError::Conflict);
+        }
+        store.users.push((subject, user.username));
+        Ok(store.users.len() as Pid)
+    }
+
+    async fn does_exist_user_id(&self, user_id: Pid) -> Result<bool, RepoError> {
+        let store = self.store.lock().unwrap();
+        Ok(user_id >= 1 && user_id as usize <= store.users.len())
+    }
+
+    async fn make_login_session(&self) -> Result<LoginSession, LoginError> {
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+        let nonce = Nonce::new_random().secret().to_owned();
+        let session_id = Uuid::new_v4().to_string();
+
+        self.store.lock().unwrap().login_sessions.insert(
+            session_id.to_owned(),
+            LoginSessionStorage::new(&nonce, pkce_verifier.secret()),
+        );
+
+        Ok(LoginSession {
+            session_id,
+            nonce,
+            code_challenge: pkce_challenge,
+        })
+    }
+
+    async fn fetch_user_subject(
+        &self,
+        session_id: String,
+        code: String,
+    ) -> Result<String, LoginError> {
+        let session = self
+            .store
+            .lock()
+            .unwrap()
+            .login_sessions
+            .remove(&session_id)
+            .ok_or(LoginError::InvalidCode)?;
+
+        if let Some(client) = &self.client {
+            return exchange_code(client, code, &session).await;
+        }
+
+        match self.store.lock().unwrap().authorization_codes.remove(&code) {
+            Some((nonce, subject)) if nonce == session.nonce => Ok(subject),
+            _ => Err(LoginError::InvalidCode),
+        }
+    }
+
+    async fn issue_sign_up_code(&self, subject: String) -> Result<SignUpCode, SignUpError> {
+        let code = Uuid::new_v4().to_string();
+        self.store
+            .lock()
+            .unwrap()
+            .sign_up_codes
+            .insert(code.to_owned(), subject);
+        Ok(SignUpCode::from(code))
+    }
+
+    async fn verify_sign_up_code(&self, code: SignUpCode) -> Result<String, SignUpError> {
+        self.store
+            .lock()
+            .unwrap()
+            .sign_up_codes
+            .remove(&code.raw())
+            .ok_or(SignUpError::InvalidCode)
+    }
+
+    async fn issue_refresh_token(
+        &self,
+        userid: Pid,
+        client: ClientInfo,
+    ) -> Result<RefreshToken, RefreshTokenError> {
+        let mut store = self.store.lock().unwrap();
+        let session_id = Uuid::new_v4().to_string();
+        let (token, refresh_token) = self.new_refresh_token(&mut store, &session_id);
+        store.refresh_sessions.insert(
+            session_id,
+            RefreshSessionStorage::new(userid, &token, client),
+        );
+
+        Ok(refresh_token)
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        token: RefreshTokenExtract,
+        client: ClientInfo,
+    ) -> Result<(Pid, RefreshToken), RefreshTokenError> {
+        let mut store = self.store.lock().unwrap();
+        let session_id = store.verify_refresh_token(&token.0)?;
+
+        let (new_token, refresh_token) = self.new_refresh_token(&mut store, &session_id);
+
+        let session = store
+            .refresh_sessions
+            .get_mut(&session_id)
+            .ok_or(RefreshTokenError::Other)?;
+        session.rotate(&new_token, client);
+
+        Ok((session.user_id, refresh_token))
+    }
+
+    async fn revoke_refresh_token(
+        &self,
+        token: RefreshTokenExtract,
+    ) -> Result<Pid, RefreshTokenError> {
+        let mut store = self.store.lock().unwrap();
+        let session_id = store.verify_refresh_token(&token.0)?;
+        store
+            .refresh_sessions
+            .remove(&session_id)
+            .map(|session| session.user_id)
+            .ok_or(RefreshTokenError::Other)
+    }
+
+    async fn list_refresh_sessions(
+        &self,
+        userid: Pid,
+    ) -> Result<Vec<RefreshSession>, RefreshTokenError> {
+        let store = self.store.lock().unwrap();
+        let mut sessions: Vec<RefreshSession> = store
+            .refresh_sessions
+            .iter()
+            .filter(|(_, session)| session.user_id == userid)
+            .map(|(session_id, session)| session.clone().into_session(session_id.to_owned()))
+            .collect();
+        sessions.sort_by_key(|session| session.issued_at);
+        Ok(sessions)
+    }
+
+    async fn revoke_refresh_session(
+        &self,
+        userid: Pid,
+        session_id: &str,
+    ) -> Result<bool, RefreshTokenError> {
+        let mut store = self.store.lock().unwrap();
+        match store.refresh_sessions.get(session_id) {
+            Some(session) if session.user_id == userid => {
+                store.refresh_sessions.remove(session_id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_all_refresh_tokens(&self, userid: Pid) -> Result<(), RefreshTokenError> {
+        self.store
+            .lock()
+            .unwrap()
+            .refresh_sessions
+            .retain(|_, session| session.user_id != userid);
+        Ok(())
+    }
+}
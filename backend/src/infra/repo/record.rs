@@ -0,0 +1,237 @@
+use axum::async_trait;
+use sqlx::{postgres::PgPool, Row};
+
+use crate::domain::entity::{
+    self,
+    record::{RecordEntity, RecordEntityForCreation, RecordFilter},
+};
+use crate::domain::repo_if::{record::RecordRepository, RepoError};
+use crate::infra::repo::{pg_error, schema::RecordRow};
+
+pub struct RecordRepositoryImpl {
+    pool: PgPool,
+}
+
+impl RecordRepositoryImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// `RecordFilter`の指定された条件だけをWHERE句に含めたSELECT文を組み立てる。
+/// プレースホルダは user_ids, book_ids, since, until の順に振られるので、
+/// bindも同じ順序で行う必要がある。
+fn build_list_records_query(filter: &RecordFilter) -> String {
+    let mut conditions = Vec::new();
+    let mut placeholder = 0;
+    let mut next_placeholder = || {
+        placeholder += 1;
+        placeholder
+    };
+
+    if !filter.user_ids.is_empty() {
+        conditions.push(format!("user_id = ANY(${})", next_placeholder()));
+    }
+    if !filter.book_ids.is_empty() {
+        conditions.push(format!("book_id = ANY(${})", next_placeholder()));
+    }
+    if filter.since.is_some() {
+        conditions.push(format!("registered_at >= ${}", next_placeholder()));
+    }
+    if filter.until.is_some() {
+        conditions.push(format!("registered_at < ${}", next_placeholder()));
+    }
+
+    let mut sql = "SELECT * FROM records".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY registered_at ASC, id ASC");
+    sql
+}
+
+fn to_pids(ids: &[entity::Pid]) -> Vec<super::Pid> {
+    ids.iter().map(|&id| id as super::Pid).collect()
+}
+
+#[async_trait]
+impl RecordRepository for RecordRepositoryImpl {
+    async fn list_records(&self, filter: RecordFilter) -> Result<Vec<RecordEntity>, RepoError> {
+        let sql = build_list_records_query(&filter);
+        let mut query = sqlx::query_as::<_, RecordRow>(&sql);
+        if !filter.user_ids.is_empty() {
+            query = query.bind(to_pids(&filter.user_ids));
+        }
+        if !filter.book_ids.is_empty() {
+            query = query.bind(to_pids(&filter.book_ids));
+        }
+        if let Some(since) = filter.since {
+            query = query.bind(since);
+        }
+        if let Some(until) = filter.until {
+            query = query.bind(until);
+        }
+
+        let rows = query
+            .fetch_all(&self.pool)
+            .await
+            .map_err(pg_error("cannot fetch records"))?;
+
+        Ok(rows.into_iter().map(RecordEntity::from).collect())
+    }
+
+    async fn get_record(&self, record_id: entity::Pid) -> Result<RecordEntity, RepoError> {
+        sqlx::query_as::<_, RecordRow>("SELECT * FROM records WHERE id = $1")
+            .bind(record_id as super::Pid)
+            .fetch_one(&self.pool)
+            .await
+            .map(RecordEntity::from)
+            .map_err(pg_error("cannot fetch the record"))
+    }
+
+    async fn create_record(
+        &self,
+        user_id: entity::Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<entity::Pid, RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("cannot establish transaction"))?;
+
+        let row = sqlx::query(
+            "INSERT INTO records (user_id, book_id, start_page, end_page, comment) \
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(user_id as super::Pid)
+        .bind(record.book_id as super::Pid)
+        .bind(record.start_page)
+        .bind(record.end_page)
+        .bind(record.comment)
+        .fetch_one(&mut transaction)
+        .await
+        .map_err(pg_error("insert was failed"))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))?;
+
+        row.try_get::<i32, _>("id")
+            // SQLの仕様ではsignedだが、値は0以上のものが返ってくる
+            .map(|id| id as entity::Pid)
+            .map_err(pg_error("parsing inserted id was failed"))
+    }
+
+    async fn update_record(
+        &self,
+        user_id: entity::Pid,
+        record_id: entity::Pid,
+        record: RecordEntityForCreation,
+    ) -> Result<(), RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("cannot establish transaction"))?;
+
+        let result = sqlx::query(
+            "UPDATE records SET book_id = $1, start_page = $2, end_page = $3, comment = $4 \
+             WHERE id = $5 AND user_id = $6",
+        )
+        .bind(record.book_id as super::Pid)
+        .bind(record.start_page)
+        .bind(record.end_page)
+        .bind(record.comment)
+        .bind(record_id as super::Pid)
+        .bind(user_id as super::Pid)
+        .execute(&mut transaction)
+        .await
+        .map_err(pg_error("update was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))
+    }
+
+    async fn delete_record(
+        &self,
+        user_id: entity::Pid,
+        record_id: entity::Pid,
+    ) -> Result<(), RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("cannot establish transaction"))?;
+
+        let result = sqlx::query("DELETE FROM records WHERE id = $1 AND user_id = $2")
+            .bind(record_id as super::Pid)
+            .bind(user_id as super::Pid)
+            .execute(&mut transaction)
+            .await
+            .map_err(pg_error("delete was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn test_build_list_records_query_without_filter() {
+        assert_eq!(
+            build_list_records_query(&RecordFilter::default()),
+            "SELECT * FROM records ORDER BY registered_at ASC, id ASC"
+        );
+    }
+
+    #[test]
+    fn test_build_list_records_query_with_all_filters() {
+        let filter = RecordFilter {
+            user_ids: vec![1, 2],
+            book_ids: vec![3],
+            since: Some(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0)),
+            until: Some(Utc.ymd(2022, 1, 1).and_hms(0, 0, 0)),
+        };
+        assert_eq!(
+            build_list_records_query(&filter),
+            "SELECT * FROM records \
+             WHERE user_id = ANY($1) AND book_id = ANY($2) \
+             AND registered_at >= $3 AND registered_at < $4 \
+             ORDER BY registered_at ASC, id ASC"
+        );
+    }
+
+    #[test]
+    fn test_build_list_records_query_numbers_placeholders_in_order() {
+        let filter = RecordFilter {
+            book_ids: vec![3],
+            until: Some(Utc.ymd(2022, 1, 1).and_hms(0, 0, 0)),
+            ..Default::default()
+        };
+        assert_eq!(
+            build_list_records_query(&filter),
+            "SELECT * FROM records WHERE book_id = ANY($1) AND registered_at < $2 \
+             ORDER BY registered_at ASC, id ASC"
+        );
+    }
+}
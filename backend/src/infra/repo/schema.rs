@@ -1,12 +1,30 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
 use sqlx::FromRow;
 
 use super::Pid;
-use crate::domain::entity::{self, book::BookEntity, user::UserEntity};
+use crate::domain::entity::{
+    self,
+    book::{BookEntity, Isbn},
+    goal::{GoalEntity, GoalMetric, GoalPeriod},
+    record::RecordEntity,
+    shelf::{ReadingEntity, ShelfStatus},
+    stats::{BooksPoint, PagesPoint},
+};
 
 #[derive(FromRow)]
 pub struct BookRow {
     id: Pid,
     title: String,
+    isbn: Option<String>,
+    publisher: Option<String>,
+    page_count: Option<i32>,
+    language: Option<String>,
+    published_date: Option<NaiveDate>,
+    /// `book_authors.position`順に集約した著者名
+    authors: Vec<String>,
+    created_at: DateTime<Utc>,
+    created_by: Option<Pid>,
 }
 
 impl From<BookRow> for BookEntity {
@@ -14,34 +32,141 @@ impl From<BookRow> for BookEntity {
         Self {
             id: book_row.id as entity::Pid,
             title: book_row.title,
+            authors: book_row.authors,
+            // 保存時に正規化済みなので、ここで失敗することはない
+            isbn: book_row.isbn.and_then(|isbn| Isbn::parse(&isbn).ok()),
+            publisher: book_row.publisher,
+            page_count: book_row.page_count,
+            language: book_row.language,
+            published_date: book_row.published_date,
+            created_at: book_row.created_at,
+            created_by: book_row.created_by.map(|id| id as entity::Pid),
         }
     }
 }
 
 #[derive(FromRow)]
-pub struct UserRow {
+pub struct UserIdRow {
+    id: Pid,
+}
+
+impl From<UserIdRow> for entity::Pid {
+    fn from(row: UserIdRow) -> entity::Pid {
+        row.id as entity::Pid
+    }
+}
+
+#[derive(FromRow)]
+pub struct RecordRow {
     id: Pid,
-    subject: String,
-    username: String,
+    user_id: Pid,
+    book_id: Pid,
+    start_page: i32,
+    end_page: i32,
+    registered_at: DateTime<Utc>,
+    comment: Option<String>,
 }
 
-impl From<UserRow> for UserEntity {
-    fn from(user_row: UserRow) -> UserEntity {
+impl From<RecordRow> for RecordEntity {
+    fn from(record_row: RecordRow) -> RecordEntity {
         Self {
-            id: user_row.id as entity::Pid,
-            subject: user_row.subject,
-            username: user_row.username,
+            id: record_row.id as entity::Pid,
+            user_id: record_row.user_id as entity::Pid,
+            book_id: record_row.book_id as entity::Pid,
+            start_page: record_row.start_page,
+            end_page: record_row.end_page,
+            registered_datetime: record_row.registered_at,
+            comment: record_row.comment,
         }
     }
 }
 
 #[derive(FromRow)]
-pub struct UserIdRow {
+pub struct ReadingRow {
     id: Pid,
+    user_id: Pid,
+    book_id: Pid,
+    status: String,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
 }
 
-impl From<UserIdRow> for entity::Pid {
-    fn from(row: UserIdRow) -> entity::Pid {
-        row.id as entity::Pid
+impl From<ReadingRow> for ReadingEntity {
+    fn from(reading_row: ReadingRow) -> ReadingEntity {
+        Self {
+            id: reading_row.id as entity::Pid,
+            user_id: reading_row.user_id as entity::Pid,
+            book_id: reading_row.book_id as entity::Pid,
+            // CHECK制約により、ここで失敗することはない
+            status: ShelfStatus::parse(&reading_row.status)
+                .expect("the status is checked by the table"),
+            started_at: reading_row.started_at,
+            finished_at: reading_row.finished_at,
+            updated_at: reading_row.updated_at,
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct PagesRow {
+    start: NaiveDate,
+    pages: i64,
+    cumulative_pages: i64,
+}
+
+impl From<PagesRow> for PagesPoint {
+    fn from(pages_row: PagesRow) -> PagesPoint {
+        Self {
+            start: pages_row.start,
+            pages: pages_row.pages,
+            cumulative_pages: pages_row.cumulative_pages,
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct BooksRow {
+    start: NaiveDate,
+    books: i64,
+    cumulative_books: i64,
+}
+
+impl From<BooksRow> for BooksPoint {
+    fn from(books_row: BooksRow) -> BooksPoint {
+        Self {
+            start: books_row.start,
+            books: books_row.books,
+            cumulative_books: books_row.cumulative_books,
+        }
+    }
+}
+
+#[derive(FromRow)]
+pub struct GoalRow {
+    id: Pid,
+    user_id: Pid,
+    metric: String,
+    period: String,
+    target: i32,
+    starts_on: NaiveDate,
+    timezone: String,
+}
+
+impl From<GoalRow> for GoalEntity {
+    fn from(goal_row: GoalRow) -> GoalEntity {
+        Self {
+            id: goal_row.id as entity::Pid,
+            user_id: goal_row.user_id as entity::Pid,
+            // CHECK制約により、ここで失敗することはない
+            metric: GoalMetric::parse(&goal_row.metric)
+                .expect("the metric is checked by the table"),
+            period: GoalPeriod::parse(&goal_row.period)
+                .expect("the period is checked by the table"),
+            target: goal_row.target,
+            starts_on: goal_row.starts_on,
+            // 保存時に検証済み
+            timezone: goal_row.timezone.parse().unwrap_or(Tz::UTC),
+        }
     }
 }
@@ -1,5 +1,11 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::domain::entity::{
+    user::{ClientInfo, RefreshSession},
+    Pid,
+};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LoginSessionStorage {
     pub nonce: String,
@@ -14,3 +20,58 @@ impl LoginSessionStorage {
         }
     }
 }
+
+/// `REF-<token>`に保存する、refresh tokenの持ち主とセッション
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefreshTokenStorage {
+    pub user_id: Pid,
+    pub session_id: String,
+}
+
+/// `REFS-<session_id>`に保存する、端末ごとのセッション情報
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshSessionStorage {
+    pub user_id: Pid,
+    /// 現在有効なrefresh token
+    pub token: String,
+    pub issued_at: DateTime<Utc>,
+    pub last_refreshed_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl RefreshSessionStorage {
+    pub fn new(user_id: Pid, token: &str, client: ClientInfo) -> Self {
+        let now = Utc::now();
+        Self {
+            user_id,
+            token: token.to_string(),
+            issued_at: now,
+            last_refreshed_at: now,
+            user_agent: client.user_agent,
+            ip: client.ip,
+        }
+    }
+
+    /// ローテーションで発行した新しいtokenを記録する
+    pub fn rotate(&mut self, token: &str, client: ClientInfo) {
+        self.token = token.to_string();
+        self.last_refreshed_at = Utc::now();
+        if client.user_agent.is_some() {
+            self.user_agent = client.user_agent;
+        }
+        if client.ip.is_some() {
+            self.ip = client.ip;
+        }
+    }
+
+    pub fn into_session(self, session_id: String) -> RefreshSession {
+        RefreshSession {
+            id: session_id,
+            issued_at: self.issued_at,
+            last_refreshed_at: self.last_refreshed_at,
+            user_agent: self.user_agent,
+            ip: self.ip,
+        }
+    }
+}
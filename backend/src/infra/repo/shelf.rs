@@ -0,0 +1,124 @@
+use axum::async_trait;
+use sqlx::{postgres::PgPool, Row};
+
+use crate::domain::entity::{self, shelf::ReadingEntity};
+use crate::domain::repo_if::{shelf::ShelfRepository, RepoError};
+use crate::infra::repo::{pg_error, schema::ReadingRow};
+
+pub struct ShelfRepositoryImpl {
+    pool: PgPool,
+}
+
+impl ShelfRepositoryImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ShelfRepository for ShelfRepositoryImpl {
+    async fn list_readings(&self, user_id: entity::Pid) -> Result<Vec<ReadingEntity>, RepoError> {
+        let rows = sqlx::query_as::<_, ReadingRow>(
+            "SELECT * FROM readings WHERE user_id = $1 ORDER BY id ASC",
+        )
+        .bind(user_id as super::Pid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(pg_error("cannot fetch readings"))?;
+
+        Ok(rows.into_iter().map(ReadingEntity::from).collect())
+    }
+
+    async fn list_book_readings(
+        &self,
+        user_id: entity::Pid,
+        book_id: entity::Pid,
+    ) -> Result<Vec<ReadingEntity>, RepoError> {
+        let rows = sqlx::query_as::<_, ReadingRow>(
+            "SELECT * FROM readings WHERE user_id = $1 AND book_id = $2 ORDER BY id ASC",
+        )
+        .bind(user_id as super::Pid)
+        .bind(book_id as super::Pid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(pg_error("cannot fetch readings"))?;
+
+        Ok(rows.into_iter().map(ReadingEntity::from).collect())
+    }
+
+    async fn current_reading(
+        &self,
+        user_id: entity::Pid,
+        book_id: entity::Pid,
+    ) -> Result<Option<ReadingEntity>, RepoError> {
+        sqlx::query_as::<_, ReadingRow>(
+            "SELECT * FROM readings WHERE user_id = $1 AND book_id = $2 \
+             AND status IN ('want_to_read', 'reading')",
+        )
+        .bind(user_id as super::Pid)
+        .bind(book_id as super::Pid)
+        .fetch_optional(&self.pool)
+        .await
+        .map(|row| row.map(ReadingEntity::from))
+        .map_err(pg_error("cannot fetch the reading"))
+    }
+
+    async fn create_reading(&self, reading: ReadingEntity) -> Result<entity::Pid, RepoError> {
+        let row = sqlx::query(
+            "INSERT INTO readings (user_id, book_id, status, started_at, finished_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+        )
+        .bind(reading.user_id as super::Pid)
+        .bind(reading.book_id as super::Pid)
+        .bind(reading.status.as_str())
+        .bind(reading.started_at)
+        .bind(reading.finished_at)
+        .bind(reading.updated_at)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(pg_error("insert was failed"))?;
+
+        row.try_get::<i32, _>("id")
+            // SQLの仕様ではsignedだが、値は0以上のものが返ってくる
+            .map(|id| id as entity::Pid)
+            .map_err(pg_error("parsing inserted id was failed"))
+    }
+
+    async fn update_reading(&self, reading: ReadingEntity) -> Result<(), RepoError> {
+        let result = sqlx::query(
+            "UPDATE readings SET status = $1, started_at = $2, finished_at = $3, updated_at = $4 \
+             WHERE id = $5",
+        )
+        .bind(reading.status.as_str())
+        .bind(reading.started_at)
+        .bind(reading.finished_at)
+        .bind(reading.updated_at)
+        .bind(reading.id as super::Pid)
+        .execute(&self.pool)
+        .await
+        .map_err(pg_error("update was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete_reading(
+        &self,
+        user_id: entity::Pid,
+        reading_id: entity::Pid,
+    ) -> Result<(), RepoError> {
+        let result = sqlx::query("DELETE FROM readings WHERE id = $1 AND user_id = $2")
+            .bind(reading_id as super::Pid)
+            .bind(user_id as super::Pid)
+            .execute(&self.pool)
+            .await
+            .map_err(pg_error("delete was failed"))?;
+
+        if result.rows_affected() != 1 {
+            return Err(RepoError::NotFound);
+        }
+        Ok(())
+    }
+}
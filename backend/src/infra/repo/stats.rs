@@ -0,0 +1,374 @@
+use axum::async_trait;
+use sqlx::{postgres::PgPool, Row};
+
+use crate::domain::entity::{
+    self,
+    stats::{BooksPoint, PagesPoint, StatsInterval, StatsQuery, Streaks},
+};
+use crate::domain::repo_if::{stats::StatsRepository, RepoError};
+use crate::infra::repo::{
+    pg_error,
+    schema::{BooksRow, PagesRow},
+};
+
+pub struct StatsRepositoryImpl {
+    pool: PgPool,
+}
+
+impl StatsRepositoryImpl {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// 日付はすべて`$tz`での暦日で扱う。
+/// `since`と`until`はその日の0時として、`AT TIME ZONE`で時刻の範囲に直す。
+const RECORDS_IN_RANGE: &str = "\
+    SELECT (registered_at AT TIME ZONE $tz) AS local_at, end_page - start_page + 1 AS pages \
+    FROM records \
+    WHERE user_id = $user \
+      AND registered_at >= ($since::date::timestamp AT TIME ZONE $tz) \
+      AND registered_at < (($until::date + 1)::timestamp AT TIME ZONE $tz)";
+
+/// 記録のない区間も0として返すよう、`generate_series`の区間に外部結合する。
+/// 累計はwindow関数で求める。
+const PAGES_PER_INTERVAL: &str = "\
+    WITH periods AS ( \
+        SELECT generate_series( \
+            date_trunc($interval, $since::date::timestamp), \
+            $until::date::timestamp, \
+            ('1 ' || $interval)::interval \
+        )::date AS start \
+    ), records_in_range AS (RECORDS_IN_RANGE), \
+    pages AS ( \
+        SELECT date_trunc($interval, local_at)::date AS start, SUM(pages) AS pages \
+        FROM records_in_range \
+        GROUP BY 1 \
+    ) \
+    SELECT p.start, \
+        COALESCE(pages.pages, 0)::BIGINT AS pages, \
+        SUM(COALESCE(pages.pages, 0)) OVER (ORDER BY p.start)::BIGINT AS cumulative_pages \
+    FROM periods p LEFT JOIN pages USING (start) \
+    ORDER BY p.start";
+
+const BOOKS_FINISHED_PER_MONTH: &str = "\
+    WITH periods AS ( \
+        SELECT generate_series( \
+            date_trunc('month', $since::date::timestamp), \
+            $until::date::timestamp, \
+            '1 month'::interval \
+        )::date AS start \
+    ), books AS ( \
+        SELECT date_trunc('month', finished_at AT TIME ZONE $tz)::date AS start, \
+            COUNT(*) AS books \
+        FROM readings \
+        WHERE user_id = $user AND status = 'finished' \
+          AND finished_at >= ($since::date::timestamp AT TIME ZONE $tz) \
+          AND finished_at < (($until::date + 1)::timestamp AT TIME ZONE $tz) \
+        GROUP BY 1 \
+    ) \
+    SELECT p.start, \
+        COALESCE(books.books, 0)::BIGINT AS books, \
+        SUM(COALESCE(books.books, 0)) OVER (ORDER BY p.start)::BIGINT AS cumulative_books \
+    FROM periods p LEFT JOIN books USING (start) \
+    ORDER BY p.start";
+
+/// 連続した日付から行番号を引くと同じ値になることを使って、連続日数ごとにまとめる。
+const STREAKS: &str = "\
+    WITH records_in_range AS (RECORDS_IN_RANGE), \
+    days AS ( \
+        SELECT DISTINCT local_at::date AS day FROM records_in_range \
+    ), islands AS ( \
+        SELECT day, day - (ROW_NUMBER() OVER (ORDER BY day))::INTEGER AS island \
+        FROM days \
+    ), runs AS ( \
+        SELECT MAX(day) AS last_day, COUNT(*) AS length \
+        FROM islands \
+        GROUP BY island \
+    ) \
+    SELECT \
+        COALESCE(MAX(length) FILTER (WHERE last_day >= $until::date - 1), 0)::BIGINT AS current, \
+        COALESCE(MAX(length), 0)::BIGINT AS longest \
+    FROM runs";
+
+/// 名前付きの引数を位置引数に置き換える。
+/// 順番は`$1`がユーザID、`$2`が`since`、`$3`が`until`、`$4`がタイムゾーン、`$5`が区間の単位。
+fn build_query(template: &str) -> String {
+    template
+        .replace("RECORDS_IN_RANGE", RECORDS_IN_RANGE)
+        .replace("$user", "$1")
+        .replace("$since", "$2")
+        .replace("$until", "$3")
+        .replace("$tz", "$4")
+        .replace("$interval", "$5")
+}
+
+#[async_trait]
+impl StatsRepository for StatsRepositoryImpl {
+    async fn pages_per_interval(
+        &self,
+        user_id: entity::Pid,
+        query: &StatsQuery,
+        interval: StatsInterval,
+    ) -> Result<Vec<PagesPoint>, RepoError> {
+        let rows = sqlx::query_as::<_, PagesRow>(&build_query(PAGES_PER_INTERVAL))
+            .bind(user_id as super::Pid)
+            .bind(query.since)
+            .bind(query.until)
+            .bind(query.timezone.name())
+            .bind(interval.as_str())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(pg_error("cannot aggregate pages"))?;
+
+        Ok(rows.into_iter().map(PagesPoint::from).collect())
+    }
+
+    async fn books_finished_per_month(
+        &self,
+        user_id: entity::Pid,
+        query: &StatsQuery,
+    ) -> Result<Vec<BooksPoint>, RepoError> {
+        let rows = sqlx::query_as::<_, BooksRow>(&build_query(BOOKS_FINISHED_PER_MONTH))
+            .bind(user_id as super::Pid)
+            .bind(query.since)
+            .bind(query.until)
+            .bind(query.timezone.name())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(pg_error("cannot aggregate finished books"))?;
+
+        Ok(rows.into_iter().map(BooksPoint::from).collect())
+    }
+
+    async fn streaks(
+        &self,
+        user_id: entity::Pid,
+        query: &StatsQuery,
+    ) -> Result<Streaks, RepoError> {
+        let row = sqlx::query(&build_query(STREAKS))
+            .bind(user_id as super::Pid)
+            .bind(query.since)
+            .bind(query.until)
+            .bind(query.timezone.name())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(pg_error("cannot aggregate streaks"))?;
+
+        Ok(Streaks {
+            current: row
+                .try_get("current")
+                .map_err(pg_error("parsing streaks was failed"))?,
+            longest: row
+                .try_get("longest")
+                .map_err(pg_error("parsing streaks was failed"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use chrono_tz::Tz;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::infra::repo::Pid;
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_build_query() {
+        let query = build_query(STREAKS);
+        assert!(query.contains("FROM records"));
+        assert!(query.contains("registered_at AT TIME ZONE $4"));
+        assert!(!query.contains("RECORDS_IN_RANGE"));
+        assert!(!query.contains("$tz"));
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2022, month, day)
+    }
+
+    /// 東京で2022年1月3日(月)から記録を付けたユーザを作り、ユーザIDと本のIDを返す。
+    /// UTCでは日付や週・月の変わり目をまたぐ時刻を選んでいる。
+    async fn insert_user_with_records(pool: &PgPool) -> (Pid, Pid) {
+        let subject = Uuid::new_v4().to_string();
+        let user_id: Pid =
+            sqlx::query("INSERT INTO users (subject, username) VALUES ($1, $1) RETURNING id")
+                .bind(&subject)
+                .fetch_one(pool)
+                .await
+                .unwrap()
+                .get(0);
+        let book_id: Pid =
+            sqlx::query("INSERT INTO books (title, created_by) VALUES ('t', $1) RETURNING id")
+                .bind(user_id)
+                .fetch_one(pool)
+                .await
+                .unwrap()
+                .get(0);
+
+        for (registered_at, pages) in [
+            // 東京では2021-12-31で、期間より前
+            ("2021-12-31T14:00:00Z", 100),
+            // 東京では2022-01-03(月)。UTCでは前の週の日曜日
+            ("2022-01-02T15:30:00Z", 10),
+            ("2022-01-03T10:00:00Z", 5),
+            ("2022-01-04T14:59:00Z", 20),
+            ("2022-01-05T03:00:00Z", 3),
+            // 東京では2022-02-01。UTCではまだ1月
+            ("2022-01-31T15:00:00Z", 30),
+            ("2022-02-02T01:00:00Z", 7),
+        ] {
+            sqlx::query(
+                "INSERT INTO records (user_id, book_id, start_page, end_page, registered_at) \
+                 VALUES ($1, $2, 1, $3, $4::timestamptz)",
+            )
+            .bind(user_id)
+            .bind(book_id)
+            .bind(pages)
+            .bind(registered_at)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        for (status, finished_at) in [
+            ("finished", Some("2022-01-10T00:00:00Z")),
+            // 東京では2022-02-01
+            ("finished", Some("2022-01-31T16:00:00Z")),
+            ("reading", None),
+        ] {
+            sqlx::query(
+                "INSERT INTO readings (user_id, book_id, status, finished_at) \
+                 VALUES ($1, $2, $3, $4::timestamptz)",
+            )
+            .bind(user_id)
+            .bind(book_id)
+            .bind(status)
+            .bind(finished_at)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        (user_id, book_id)
+    }
+
+    async fn delete_user(pool: &PgPool, user_id: Pid, book_id: Pid) {
+        // 記録と本棚は外部キーで一緒に消える
+        sqlx::query("DELETE FROM books WHERE id = $1")
+            .bind(book_id)
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    // `sqlx migrate run`で適用済みのDBで、集計のSQLを実際に実行する。
+    // 他のユーザの行に影響しないよう、専用のユーザを作って最後に消す。
+    #[tokio::test]
+    #[ignore]
+    async fn test_aggregate_in_timezone() {
+        let settings = envy::from_env::<Settings>().unwrap();
+        let pool = PgPool::connect(&settings.database_url).await.unwrap();
+        let (user_id, book_id) = insert_user_with_records(&pool).await;
+        let repo = StatsRepositoryImpl::new(pool.clone());
+        let query = StatsQuery {
+            since: date(1, 1),
+            until: date(2, 3),
+            timezone: chrono_tz::Asia::Tokyo,
+        };
+        let user_id_ = user_id as entity::Pid;
+
+        let weeks = repo
+            .pages_per_interval(user_id_, &query, StatsInterval::Week)
+            .await;
+        let months = repo
+            .pages_per_interval(user_id_, &query, StatsInterval::Month)
+            .await;
+        let days = repo
+            .pages_per_interval(user_id_, &query, StatsInterval::Day)
+            .await;
+        let books = repo.books_finished_per_month(user_id_, &query).await;
+        let streaks = repo.streaks(user_id_, &query).await;
+        // 最終日の前日までに記録がなければ、今の連続日数は途切れる
+        let later = StatsQuery {
+            until: date(2, 5),
+            ..query.clone()
+        };
+        let later_streaks = repo.streaks(user_id_, &later).await;
+        let utc = StatsQuery {
+            timezone: Tz::UTC,
+            ..query.clone()
+        };
+        let utc_months = repo
+            .pages_per_interval(user_id_, &utc, StatsInterval::Month)
+            .await;
+
+        delete_user(&pool, user_id, book_id).await;
+
+        // 週は月曜日から始まり、最初の週は`since`より前から始まる
+        let weeks: Vec<_> = weeks
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|point| (point.start, point.pages, point.cumulative_pages))
+            .collect();
+        assert_eq!(
+            weeks,
+            vec![
+                (NaiveDate::from_ymd(2021, 12, 27), 0, 0),
+                (date(1, 3), 38, 38),
+                (date(1, 10), 0, 38),
+                (date(1, 17), 0, 38),
+                (date(1, 24), 0, 38),
+                (date(1, 31), 37, 75),
+            ]
+        );
+
+        let months: Vec<_> = months
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|point| (point.start, point.pages, point.cumulative_pages))
+            .collect();
+        assert_eq!(months, vec![(date(1, 1), 38, 38), (date(2, 1), 37, 75)]);
+
+        let days = days.ok().unwrap();
+        assert_eq!(days.len() as i64, query.days());
+        assert_eq!(days[2].start, date(1, 3));
+        assert_eq!(days[2].pages, 15);
+        assert_eq!(days.last().unwrap().cumulative_pages, 75);
+
+        let books: Vec<_> = books
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|point| (point.start, point.books, point.cumulative_books))
+            .collect();
+        assert_eq!(books, vec![(date(1, 1), 1, 1), (date(2, 1), 1, 2)]);
+
+        assert_eq!(
+            streaks.ok(),
+            Some(Streaks {
+                current: 2,
+                longest: 3
+            })
+        );
+        assert_eq!(later_streaks.ok().map(|streaks| streaks.current), Some(0));
+
+        // UTCでは2月1日の30ページが1月に入る
+        let utc_months: Vec<_> = utc_months
+            .ok()
+            .unwrap()
+            .into_iter()
+            .map(|point| point.pages)
+            .collect();
+        assert_eq!(utc_months, vec![68, 7]);
+    }
+}
@@ -1,26 +1,26 @@
-use axum::{
-    async_trait,
-    extract::{Extension, FromRequest, RequestParts},
-};
+use axum::async_trait;
 use chrono::{Duration, Utc};
 use openidconnect::core::CoreClient;
 use openidconnect::reqwest::async_http_client;
 use openidconnect::{AuthorizationCode, Nonce, PkceCodeChallenge, PkceCodeVerifier, TokenResponse};
-use redis::{aio::ConnectionLike, AsyncCommands, Client as RedisClient, RedisResult};
-use sqlx::{postgres::PgPool, Error as SqlxError, Row};
+use redis::{
+    aio::{Connection, ConnectionLike},
+    AsyncCommands, Client as RedisClient, RedisResult,
+};
+use sqlx::{postgres::PgPool, Row};
 use uuid::Uuid;
 
-use super::schema::{UserIdRow, UserRow};
-use super::session::LoginSessionStorage;
+use super::pg_error;
+use super::schema::UserIdRow;
+use super::session::{LoginSessionStorage, RefreshSessionStorage, RefreshTokenStorage};
 use crate::domain::entity::{
     self,
     user::{
-        LoginError, LoginSession, RefreshToken, RefreshTokenError, RefreshTokenExtract, SignUpCode,
-        SignUpError, UserEntity, UserEntityForCreation, UserError,
+        ClientInfo, LoginError, LoginSession, RefreshSession, RefreshToken, RefreshTokenError,
+        RefreshTokenExtract, SignUpCode, SignUpError, UserEntityForCreation,
     },
-    AxumError,
 };
-use crate::domain::repo_if::user::UserRepository;
+use crate::domain::repo_if::{user::UserRepository, RepoError};
 use crate::settings::Settings;
 
 pub struct UserRepositoryImpl {
@@ -30,37 +30,21 @@ pub struct UserRepositoryImpl {
     client: CoreClient,
 }
 
-#[async_trait]
-impl<B> FromRequest<B> for UserRepositoryImpl
-where
-    B: Send,
-{
-    type Rejection = AxumError;
-
-    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let Extension(settings) = Extension::<Settings>::from_request(req)
-            .await
-            .map_err(|_| AxumError::OtherError("Settings extension error".to_string()))?;
-        let Extension(pool) = Extension::<PgPool>::from_request(req)
-            .await
-            .map_err(|_| AxumError::PgConnectionError)?;
-        let Extension(redis_cli) = Extension::<RedisClient>::from_request(req)
-            .await
-            .map_err(|_| AxumError::RedisConnectionError)?;
-        let Extension(client) = Extension::<CoreClient>::from_request(req)
-            .await
-            .map_err(|_| AxumError::OtherError("OIDC extension error".to_string()))?;
-
-        Ok(Self {
+impl UserRepositoryImpl {
+    pub fn new(
+        settings: Settings,
+        pool: PgPool,
+        redis_cli: RedisClient,
+        client: CoreClient,
+    ) -> Self {
+        Self {
             settings,
             pool,
             redis_cli,
             client,
-        })
+        }
     }
-}
 
-impl UserRepositoryImpl {
     /// Redisから値を取得し、取得した後の値は削除する
     async fn get_one_time_code<C, T>(&self, con: &mut C, key: &str) -> RedisResult<T>
     where
@@ -76,55 +60,299 @@ impl UserRepositoryImpl {
             .ignore();
         pipe.query_async(con).await.map(|(res,): (T,)| res)
     }
-}
 
-#[async_trait]
-impl UserRepository for UserRepositoryImpl {
-    async fn get_user(&self, id: entity::Pid) -> Result<UserEntity, UserError> {
-        sqlx::query_as::<_, UserRow>("SELECT * FROM users WHERE id = $1")
-            .bind(id as super::Pid)
-            .fetch_one(&self.pool)
+    fn refresh_token_key(&self, token: &str) -> String {
+        format!("{}{}", self.settings.refresh_prefix, token)
+    }
+
+    fn refresh_session_key(&self, session_id: &str) -> String {
+        format!("{}{}", self.settings.refresh_session_prefix, session_id)
+    }
+
+    fn refresh_user_key(&self, userid: entity::Pid) -> String {
+        format!("{}{}", self.settings.refresh_user_prefix, userid)
+    }
+
+    async fn get_refresh_session(
+        &self,
+        con: &mut Connection,
+        session_id: &str,
+    ) -> Result<Option<RefreshSessionStorage>, RefreshTokenError> {
+        let session: Option<String> = con
+            .get(self.refresh_session_key(session_id))
             .await
-            .map(UserEntity::from)
-            .map_err(|err| match err {
-                SqlxError::RowNotFound => UserError::Nonexistent,
-                _ => UserError::Other,
+            .map_err(|err| {
+                tracing::error!(
+                    "in get_refresh_session: error in fetching session {}: {}",
+                    session_id,
+                    err
+                );
+                RefreshTokenError::Other
+            })?;
+
+        session
+            .map(|session| serde_json::from_str(&session))
+            .transpose()
+            .map_err(|err| {
+                tracing::error!("in get_refresh_session: broken session info: {}", err);
+                RefreshTokenError::Other
             })
     }
 
-    async fn get_user_from_subject(&self, subject: &str) -> Result<UserEntity, UserError> {
-        sqlx::query_as::<_, UserRow>("SELECT * FROM users WHERE subject = $1")
-            .bind(subject)
-            .fetch_one(&self.pool)
+    /// セッション情報と現在のrefresh tokenを保存し、
+    /// ユーザごとのセッション一覧に追加する。
+    /// WATCHしていたセッションが他の要求で変更されていた場合は何もせずNoneを返す。
+    async fn store_refresh_session(
+        &self,
+        con: &mut Connection,
+        session_id: &str,
+        session: &RefreshSessionStorage,
+    ) -> Result<Option<RefreshToken>, RefreshTokenError> {
+        let token_info = RefreshTokenStorage {
+            user_id: session.user_id,
+            session_id: session_id.to_string(),
+        };
+        let user_key = self.refresh_user_key(session.user_id);
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .set_ex(
+                self.refresh_token_key(&session.token),
+                serde_json::to_string(&token_info).unwrap(),
+                self.settings.refresh_exp,
+            )
+            .ignore()
+            .set_ex(
+                self.refresh_session_key(session_id),
+                serde_json::to_string(session).unwrap(),
+                self.settings.refresh_exp,
+            )
+            .ignore()
+            .sadd(&user_key, session_id)
+            .ignore()
+            .expire(&user_key, self.settings.refresh_exp)
+            .ignore();
+        let stored: Option<redis::Value> = pipe.query_async(con).await.map_err(|err| {
+            tracing::error!(
+                "in store_refresh_session: error in storing session {}: {}",
+                session_id,
+                err
+            );
+            RefreshTokenError::Other
+        })?;
+
+        // 定数なのでエラーは出ない前提でasを使う
+        let expires_at = Utc::now() + Duration::seconds(self.settings.refresh_exp as i64);
+
+        Ok(stored.map(|_| RefreshToken::new(session.token.to_owned(), expires_at)))
+    }
+
+    /// セッションとその現在のrefresh tokenを削除する
+    async fn delete_refresh_session(
+        &self,
+        con: &mut Connection,
+        session_id: &str,
+        session: &RefreshSessionStorage,
+    ) -> Result<(), RefreshTokenError> {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(self.refresh_token_key(&session.token))
+            .ignore()
+            .del(self.refresh_session_key(session_id))
+            .ignore()
+            .srem(self.refresh_user_key(session.user_id), session_id)
+            .ignore();
+        pipe.query_async(con).await.map_err(|err| {
+            tracing::error!(
+                "in delete_refresh_session: error in deleting session {}: {}",
+                session_id,
+                err
+            );
+            RefreshTokenError::Other
+        })
+    }
+
+    /// Refresh tokenを検証し、属するセッション（token family）を返す。
+    /// 返却時点でセッションをWATCHしているので、
+    /// 続くトランザクションは他の要求と競合した場合に失敗する。
+    ///
+    /// ローテーション済みのtokenが提示された場合は盗用とみなし、
+    /// OAuth 2.0 Security BCPに従ってセッションごと無効にする。
+    async fn verify_refresh_token(
+        &self,
+        con: &mut Connection,
+        token: &RefreshTokenExtract,
+    ) -> Result<(String, RefreshSessionStorage), RefreshTokenError> {
+        // ローテーション済みのtokenも、盗用検知のため期限切れまで残してある
+        let info: Option<String> =
+            con.get(self.refresh_token_key(&token.0))
+                .await
+                .map_err(|err| {
+                    tracing::error!("in verify_refresh_token: error in fetching token: {}", err);
+                    RefreshTokenError::Other
+                })?;
+        let info = info.ok_or_else(|| {
+            tracing::info!("in verify_refresh_token: invalid or expired refresh token");
+            RefreshTokenError::InvalidRefreshToken
+        })?;
+
+        let info: RefreshTokenStorage = serde_json::from_str(&info).map_err(|err| {
+            tracing::error!("in verify_refresh_token: broken token info: {}", err);
+            RefreshTokenError::Other
+        })?;
+
+        let _: () = redis::cmd("WATCH")
+            .arg(self.refresh_session_key(&info.session_id))
+            .query_async(con)
             .await
-            .map(UserEntity::from)
-            .map_err(|err| match err {
-                SqlxError::RowNotFound => UserError::Nonexistent,
-                _ => UserError::Other,
-            })
+            .map_err(|err| {
+                tracing::error!(
+                    "in verify_refresh_token: error in watching session: {}",
+                    err
+                );
+                RefreshTokenError::Other
+            })?;
+
+        match self.get_refresh_session(con, &info.session_id).await? {
+            Some(session) if session.token == token.0 => Ok((info.session_id, session)),
+            Some(session) => {
+                self.revoke_token_family(con, &info.session_id, &session)
+                    .await?;
+                Err(RefreshTokenError::InvalidRefreshToken)
+            }
+            None => {
+                tracing::info!(
+                    "in verify_refresh_token: the session {} was already revoked",
+                    info.session_id
+                );
+                Err(RefreshTokenError::InvalidRefreshToken)
+            }
+        }
     }
 
-    async fn get_user_id_from_subject(&self, subject: &str) -> Result<entity::Pid, UserError> {
+    /// ローテーション済みのrefresh tokenが再利用されたときに、
+    /// 同じセッションから発行された全てのtokenを無効にする
+    async fn revoke_token_family(
+        &self,
+        con: &mut Connection,
+        session_id: &str,
+        session: &RefreshSessionStorage,
+    ) -> Result<(), RefreshTokenError> {
+        tracing::warn!(
+            target: "security",
+            "refresh token reuse detected; revoking session {} of user {} (ip: {:?}, user agent: {:?})",
+            session_id,
+            session.user_id,
+            session.ip,
+            session.user_agent,
+        );
+
+        let _: () = redis::cmd("UNWATCH")
+            .query_async(con)
+            .await
+            .map_err(|err| {
+                tracing::error!("in revoke_token_family: error in unwatching: {}", err);
+                RefreshTokenError::Other
+            })?;
+        self.delete_refresh_session(con, session_id, session).await
+    }
+
+    /// `verify_refresh_token`でWATCHしたセッションに、ローテーションした`session`を保存する。
+    /// 同じtokenによるローテーションが並行して行われていた場合は、盗用とみなしてセッションごと無効にする。
+    async fn finish_rotation(
+        &self,
+        con: &mut Connection,
+        session_id: &str,
+        session: &RefreshSessionStorage,
+    ) -> Result<(entity::Pid, RefreshToken), RefreshTokenError> {
+        match self.store_refresh_session(con, session_id, session).await? {
+            Some(refresh_token) => Ok((session.user_id, refresh_token)),
+            None => {
+                if let Some(current) = self.get_refresh_session(con, session_id).await? {
+                    self.revoke_token_family(con, session_id, &current).await?;
+                }
+                Err(RefreshTokenError::InvalidRefreshToken)
+            }
+        }
+    }
+
+    async fn refresh_connection(&self, caller: &str) -> Result<Connection, RefreshTokenError> {
+        self.redis_cli.get_async_connection().await.map_err(|err| {
+            tracing::error!(
+                "in {}: error in making connection to Redis: {}",
+                caller,
+                err
+            );
+            RefreshTokenError::Other
+        })
+    }
+}
+
+/// IdPでauthorization codeをID tokenと引き換え、検証したうえでユーザ識別子を返す
+pub(super) async fn exchange_code(
+    client: &CoreClient,
+    code: String,
+    session: &LoginSessionStorage,
+) -> Result<String, LoginError> {
+    let nonce = Nonce::new(session.nonce.to_owned());
+    let pkce_verifier = PkceCodeVerifier::new(session.pkce_verifier.to_owned());
+
+    // IdPでauthorization codeと引き換えてトークンをもらう
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code))
+        .set_pkce_verifier(pkce_verifier)
+        .request_async(async_http_client)
+        .await
+        .map_err(|err| {
+            tracing::info!(
+                "in fetch_authed_user: error in exchanging ID token, {:?}",
+                err
+            );
+            LoginError::InvalidCode
+        })?;
+
+    // IDトークンだけ取り出す
+    let id_token = token_response.id_token().ok_or_else(|| {
+        tracing::info!("in fetch_authed_user: ID token is missing");
+        LoginError::IdTokenMissing
+    })?;
+
+    // IDトークンの検証とnonceの一致の確認
+    // 検証はiss, audの一致と、署名について行われる（openidconnect v2.1.1のソースコードを確認）
+    let claims = id_token
+        .claims(&client.id_token_verifier(), &nonce)
+        .map_err(|err| {
+            tracing::info!(
+                "in fetch_authed_user: ID token verification failure: {:?}",
+                err
+            );
+            LoginError::InvalidCode
+        })?;
+
+    Ok(claims.subject().as_str().to_string())
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryImpl {
+    async fn get_user_id_from_subject(&self, subject: &str) -> Result<entity::Pid, RepoError> {
         sqlx::query_as::<_, UserIdRow>("SELECT id FROM users WHERE subject = $1")
             .bind(subject)
             .fetch_one(&self.pool)
             .await
             .map(entity::Pid::from)
-            .map_err(|err| match err {
-                SqlxError::RowNotFound => UserError::Nonexistent,
-                _ => UserError::Other,
-            })
+            .map_err(pg_error("cannot fetch the user id"))
     }
 
-    async fn does_exist_user_id(&self, user_id: entity::Pid) -> Result<bool, UserError> {
+    async fn does_exist_user_id(&self, user_id: entity::Pid) -> Result<bool, RepoError> {
         match sqlx::query_as::<_, UserIdRow>("SELECT id FROM users WHERE id = $1")
             .bind(user_id as super::Pid)
             .fetch_one(&self.pool)
             .await
+            .map_err(pg_error("cannot fetch the user id"))
         {
             Ok(_) => Ok(true),
-            Err(SqlxError::RowNotFound) => Ok(false),
-            _ => Err(UserError::Other),
+            Err(RepoError::NotFound) => Ok(false),
+            Err(err) => Err(err),
         }
     }
 
@@ -132,40 +360,35 @@ impl UserRepository for UserRepositoryImpl {
         &self,
         subject: String,
         user: UserEntityForCreation,
-    ) -> Result<entity::Pid, UserError> {
-        let mut transaction = self.pool.begin().await.map_err(|err| {
-            tracing::info!("could not establish transaction: {}", err);
-            UserError::Other
-        })?;
+    ) -> Result<entity::Pid, RepoError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(pg_error("could not establish transaction"))?;
 
         let row = sqlx::query("INSERT INTO users (subject, username) VALUES ($1, $2) RETURNING id")
             .bind(subject)
             .bind(user.username)
             .fetch_one(&mut transaction)
             .await
-            .map_err(|err| match err {
-                SqlxError::Database(_) => {
+            .map_err(pg_error("insert was failed"))
+            .map_err(|err| {
+                if err == RepoError::Conflict {
                     tracing::warn!("tried to create a user with the same subject");
-                    UserError::Duplicated
-                }
-                _ => {
-                    tracing::info!("insert was failed: {}", err);
-                    UserError::Other
                 }
+                err
             })?;
 
-        transaction.commit().await.map_err(|err| {
-            tracing::info!("commiting was failed: {}", err);
-            UserError::Other
-        })?;
+        transaction
+            .commit()
+            .await
+            .map_err(pg_error("commiting was failed"))?;
 
         row.try_get::<i32, _>("id")
             // SQLの仕様ではsignedだが、値は0以上のものが返ってくる
             .map(|id| id as u32)
-            .map_err(|err| {
-                tracing::info!("parsing inserted id was failed: {}", err);
-                UserError::Other
-            })
+            .map_err(pg_error("parsing inserted id was failed"))
     }
 
     async fn make_login_session(&self) -> Result<LoginSession, LoginError> {
@@ -254,43 +477,7 @@ impl UserRepository for UserRepositoryImpl {
             LoginError::Other
         })?;
 
-        let nonce = Nonce::new(info.nonce);
-        let pkce_verifier = PkceCodeVerifier::new(info.pkce_verifier);
-
-        // IdPでauthorization codeと引き換えてトークンをもらう
-        let token_response = self
-            .client
-            .exchange_code(AuthorizationCode::new(code))
-            .set_pkce_verifier(pkce_verifier)
-            .request_async(async_http_client)
-            .await
-            .map_err(|err| {
-                tracing::info!(
-                    "in fetch_authed_user: error in exchanging ID token, {:?}",
-                    err
-                );
-                LoginError::InvalidCode
-            })?;
-
-        // IDトークンだけ取り出す
-        let id_token = token_response.id_token().ok_or_else(|| {
-            tracing::info!("in fetch_authed_user: ID token is missing");
-            LoginError::IdTokenMissing
-        })?;
-
-        // IDトークンの検証とnonceの一致の確認
-        // 検証はiss, audの一致と、署名について行われる（openidconnect v2.1.1のソースコードを確認）
-        let claims = id_token
-            .claims(&self.client.id_token_verifier(), &nonce)
-            .map_err(|err| {
-                tracing::info!(
-                    "in fetch_authed_user: ID token verification failure: {:?}",
-                    err
-                );
-                LoginError::InvalidCode
-            })?;
-
-        Ok(claims.subject().as_str().to_string())
+        exchange_code(&self.client, code, &info).await
     }
 
     async fn issue_sign_up_code(&self, subject: String) -> Result<SignUpCode, SignUpError> {
@@ -345,59 +532,329 @@ impl UserRepository for UserRepositoryImpl {
     async fn issue_refresh_token(
         &self,
         userid: entity::Pid,
+        client: ClientInfo,
     ) -> Result<RefreshToken, RefreshTokenError> {
+        let mut con = self.refresh_connection("issue_refresh_token").await?;
+
+        let session_id = Uuid::new_v4().to_string();
         let token = Uuid::new_v4().to_string();
+        let session = RefreshSessionStorage::new(userid, &token, client);
 
-        // Redisにrefresh tokenを保存
-        let mut con = self.redis_cli.get_async_connection().await.map_err(|err| {
+        tracing::info!("Refresh session start: {}", session_id);
+
+        // WATCHしていないので必ず保存される
+        self.store_refresh_session(&mut con, &session_id, &session)
+            .await?
+            .ok_or(RefreshTokenError::Other)
+    }
+
+    async fn rotate_refresh_token(
+        &self,
+        token: RefreshTokenExtract,
+        client: ClientInfo,
+    ) -> Result<(entity::Pid, RefreshToken), RefreshTokenError> {
+        let mut con = self.refresh_connection("rotate_refresh_token").await?;
+
+        let (session_id, mut session) = self.verify_refresh_token(&mut con, &token).await?;
+        session.rotate(&Uuid::new_v4().to_string(), client);
+
+        self.finish_rotation(&mut con, &session_id, &session).await
+    }
+
+    async fn revoke_refresh_token(
+        &self,
+        token: RefreshTokenExtract,
+    ) -> Result<entity::Pid, RefreshTokenError> {
+        let mut con = self.refresh_connection("revoke_refresh_token").await?;
+
+        let (session_id, session) = self.verify_refresh_token(&mut con, &token).await?;
+        // 並行してローテーションされていてもセッションごと消すので、WATCHは不要
+        let _: () = redis::cmd("UNWATCH")
+            .query_async(&mut con)
+            .await
+            .map_err(|err| {
+                tracing::error!("in revoke_refresh_token: error in unwatching: {}", err);
+                RefreshTokenError::Other
+            })?;
+        self.delete_refresh_session(&mut con, &session_id, &session)
+            .await?;
+
+        Ok(session.user_id)
+    }
+
+    async fn list_refresh_sessions(
+        &self,
+        userid: entity::Pid,
+    ) -> Result<Vec<RefreshSession>, RefreshTokenError> {
+        let mut con = self.refresh_connection("list_refresh_sessions").await?;
+
+        let user_key = self.refresh_user_key(userid);
+        let session_ids: Vec<String> = con.smembers(&user_key).await.map_err(|err| {
             tracing::error!(
-                "in issue_refresh_token: error in making connection to Redis: {}",
+                "in list_refresh_sessions: error in listing sessions: {}",
                 err
             );
             RefreshTokenError::Other
         })?;
 
-        let _: () = con
-            .set_ex(
-                format!("{}{}", self.settings.refresh_prefix, token),
-                userid,
-                self.settings.refresh_exp,
-            )
-            .await
-            .map_err(|err| {
-                tracing::error!(
-                    "in issue_refresh_token: error in making a refresh token: {}",
-                    err
-                );
-                RefreshTokenError::Other
-            })?;
+        let mut sessions = Vec::with_capacity(session_ids.len());
+        for session_id in session_ids {
+            match self.get_refresh_session(&mut con, &session_id).await? {
+                Some(session) => sessions.push(session.into_session(session_id)),
+                None => {
+                    // 期限切れで消えたセッションを一覧からも取り除く
+                    let _: RedisResult<()> = con.srem(&user_key, &session_id).await;
+                }
+            }
+        }
+        sessions.sort_by_key(|session| session.issued_at);
 
-        // 定数なのでエラーは出ない前提でasを使う
-        let expires_at = Utc::now() + Duration::seconds(self.settings.refresh_exp as i64);
+        Ok(sessions)
+    }
 
-        Ok(RefreshToken::new(token, expires_at))
+    async fn revoke_refresh_session(
+        &self,
+        userid: entity::Pid,
+        session_id: &str,
+    ) -> Result<bool, RefreshTokenError> {
+        let mut con = self.refresh_connection("revoke_refresh_session").await?;
+
+        match self.get_refresh_session(&mut con, session_id).await? {
+            Some(session) if session.user_id == userid => {
+                self.delete_refresh_session(&mut con, session_id, &session)
+                    .await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
     }
 
-    async fn verify_refresh_token(
+    async fn revoke_all_refresh_tokens(
         &self,
-        token: RefreshTokenExtract,
-    ) -> Result<entity::Pid, RefreshTokenError> {
-        let mut con = self.redis_cli.get_async_connection().await.map_err(|err| {
+        userid: entity::Pid,
+    ) -> Result<(), RefreshTokenError> {
+        let mut con = self.refresh_connection("revoke_all_refresh_tokens").await?;
+
+        let user_key = self.refresh_user_key(userid);
+        let session_ids: Vec<String> = con.smembers(&user_key).await.map_err(|err| {
             tracing::error!(
-                "in verify_refresh_token: error in making connection to Redis: {}",
+                "in revoke_all_refresh_tokens: error in listing sessions: {}",
                 err
             );
             RefreshTokenError::Other
         })?;
 
-        // 型が一致しているので、型変換のコードは書かずにおく
-        let key = format!("{}{}", self.settings.refresh_prefix, token.0);
-        self.get_one_time_code(&mut con, &key).await.map_err(|err| {
-            tracing::info!(
-                "in verify_refresh_token: invalid or expired refresh token: {}",
+        let mut keys = vec![user_key];
+        for session_id in session_ids.iter() {
+            if let Some(session) = self.get_refresh_session(&mut con, session_id).await? {
+                keys.push(self.refresh_token_key(&session.token));
+            }
+            keys.push(self.refresh_session_key(session_id));
+        }
+
+        let _: () = con.del(keys).await.map_err(|err| {
+            tracing::error!(
+                "in revoke_all_refresh_tokens: error in deleting sessions: {}",
                 err
             );
-            RefreshTokenError::InvalidRefreshToken
-        })
+            RefreshTokenError::Other
+        })?;
+
+        tracing::info!(
+            "revoked {} refresh sessions of user {}",
+            session_ids.len(),
+            userid
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openidconnect::{AuthUrl, ClientId, IssuerUrl, JsonWebKeySet};
+
+    use super::*;
+
+    /// `REDIS_URL`のRedisを使うrepository。
+    /// 他のテストと鍵が衝突しないよう、接頭辞をテストごとに変える。
+    /// Postgresには接続しない。
+    fn repository() -> UserRepositoryImpl {
+        let mut settings = envy::from_env::<Settings>().unwrap();
+        let prefix = format!("test:{}:", Uuid::new_v4());
+        settings.refresh_prefix = format!("{}refresh:", prefix);
+        settings.refresh_session_prefix = format!("{}session:", prefix);
+        settings.refresh_user_prefix = format!("{}user:", prefix);
+
+        let pool = PgPool::connect_lazy(&settings.database_url).unwrap();
+        let redis_cli = RedisClient::open(settings.redis_url.to_owned()).unwrap();
+        let client = CoreClient::new(
+            ClientId::new(settings.id_provider_client_id.to_owned()),
+            None,
+            IssuerUrl::new(settings.id_provider_url.to_owned()).unwrap(),
+            AuthUrl::new(format!("{}/auth", settings.id_provider_url)).unwrap(),
+            None,
+            None,
+            JsonWebKeySet::default(),
+        );
+        UserRepositoryImpl::new(settings, pool, redis_cli, client)
+    }
+
+    fn extract(token: &RefreshToken) -> RefreshTokenExtract {
+        RefreshTokenExtract(token.as_str().to_string())
+    }
+
+    async fn rotate(
+        repo: &UserRepositoryImpl,
+        token: &RefreshToken,
+    ) -> Result<(entity::Pid, RefreshToken), RefreshTokenError> {
+        repo.rotate_refresh_token(extract(token), ClientInfo::default())
+            .await
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_rotate_refresh_token() {
+        let repo = repository();
+        let token = repo
+            .issue_refresh_token(1, ClientInfo::default())
+            .await
+            .ok()
+            .unwrap();
+
+        let (user_id, rotated) = rotate(&repo, &token).await.ok().unwrap();
+        assert_eq!(user_id, 1);
+        assert_ne!(rotated.as_str(), token.as_str());
+
+        // セッションIDはローテーションしても変わらない
+        let sessions = repo.list_refresh_sessions(1).await.ok().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(rotate(&repo, &rotated).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reused_refresh_token_revokes_family() {
+        let repo = repository();
+        let token = repo
+            .issue_refresh_token(1, ClientInfo::default())
+            .await
+            .ok()
+            .unwrap();
+        let (_, rotated) = rotate(&repo, &token).await.ok().unwrap();
+
+        assert!(matches!(
+            rotate(&repo, &token).await,
+            Err(RefreshTokenError::InvalidRefreshToken)
+        ));
+        // 正当な利用者が持つ新しいtokenも無効になる
+        assert!(matches!(
+            rotate(&repo, &rotated).await,
+            Err(RefreshTokenError::InvalidRefreshToken)
+        ));
+        assert!(repo.list_refresh_sessions(1).await.ok().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_concurrent_rotation_revokes_family() {
+        let repo = repository();
+        let token = repo
+            .issue_refresh_token(1, ClientInfo::default())
+            .await
+            .ok()
+            .unwrap();
+
+        // 検証してWATCHしたところで、別の接続が同じtokenで先にローテーションする
+        let mut con = repo.refresh_connection("test").await.ok().unwrap();
+        let (session_id, mut session) = repo
+            .verify_refresh_token(&mut con, &extract(&token))
+            .await
+            .ok()
+            .unwrap();
+        let (_, rotated) = rotate(&repo, &token).await.ok().unwrap();
+
+        // EXECがnilを返し、セッションごと無効にする
+        session.rotate(&Uuid::new_v4().to_string(), ClientInfo::default());
+        assert!(matches!(
+            repo.finish_rotation(&mut con, &session_id, &session).await,
+            Err(RefreshTokenError::InvalidRefreshToken)
+        ));
+        assert!(matches!(
+            rotate(&repo, &rotated).await,
+            Err(RefreshTokenError::InvalidRefreshToken)
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_revoke_refresh_session() {
+        let repo = repository();
+        let token = repo
+            .issue_refresh_token(1, ClientInfo::default())
+            .await
+            .ok()
+            .unwrap();
+        let other = repo
+            .issue_refresh_token(1, ClientInfo::default())
+            .await
+            .ok()
+            .unwrap();
+        let sessions = repo.list_refresh_sessions(1).await.ok().unwrap();
+        assert_eq!(sessions.len(), 2);
+
+        // 他のユーザのセッションは消せない
+        let session_id = &sessions[0].id;
+        assert!(!repo
+            .revoke_refresh_session(2, session_id)
+            .await
+            .ok()
+            .unwrap());
+        assert!(repo
+            .revoke_refresh_session(1, session_id)
+            .await
+            .ok()
+            .unwrap());
+        assert!(!repo
+            .revoke_refresh_session(1, session_id)
+            .await
+            .ok()
+            .unwrap());
+
+        let remaining = repo.list_refresh_sessions(1).await.ok().unwrap();
+        assert_eq!(remaining.len(), 1);
+        let results = [rotate(&repo, &token).await, rotate(&repo, &other).await];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_revoke_all_refresh_tokens() {
+        let repo = repository();
+        let mut tokens = vec![];
+        for _ in 0..2 {
+            tokens.push(
+                repo.issue_refresh_token(1, ClientInfo::default())
+                    .await
+                    .ok()
+                    .unwrap(),
+            );
+        }
+        let others = repo
+            .issue_refresh_token(2, ClientInfo::default())
+            .await
+            .ok()
+            .unwrap();
+
+        assert!(repo.revoke_all_refresh_tokens(1).await.is_ok());
+        assert!(repo.list_refresh_sessions(1).await.ok().unwrap().is_empty());
+        for token in tokens.iter() {
+            assert!(matches!(
+                rotate(&repo, token).await,
+                Err(RefreshTokenError::InvalidRefreshToken)
+            ));
+        }
+        // 他のユーザのセッションには影響しない
+        assert!(rotate(&repo, &others).await.is_ok());
     }
 }
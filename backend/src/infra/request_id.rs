@@ -0,0 +1,155 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::http::{header::HeaderName, HeaderValue, Request, Response};
+use tower::{Layer, Service};
+use tracing::Span;
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// リクエストごとのID。ログの相関に使う。
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// 受け取った`X-Request-Id`を引き継ぐか、なければ生成してレスポンスにも付けるlayer。
+/// IDはextensionとして`RequestId`に入れるので、`request_span`より外側に被せる。
+#[derive(Clone)]
+pub struct RequestIdLayer;
+
+impl<S> Layer<S> for RequestIdLayer {
+    type Service = RequestIdService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestIdService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestIdService<S> {
+    inner: S,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for RequestIdService<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let value = req
+            .headers()
+            .get(X_REQUEST_ID)
+            .filter(|value| is_valid_request_id(value))
+            .cloned()
+            .unwrap_or_else(|| HeaderValue::from_str(&Uuid::new_v4().to_string()).unwrap());
+        let id = value.to_str().unwrap().to_string();
+
+        req.headers_mut()
+            .insert(HeaderName::from_static(X_REQUEST_ID), value.clone());
+        req.extensions_mut().insert(RequestId(id));
+
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = future.await?;
+            response
+                .headers_mut()
+                .insert(HeaderName::from_static(X_REQUEST_ID), value);
+            Ok(response)
+        })
+    }
+}
+
+/// ログを汚されないよう、長すぎるものや表示できない文字を含むものは引き継がない
+fn is_valid_request_id(value: &HeaderValue) -> bool {
+    let bytes = value.as_bytes();
+    !bytes.is_empty() && bytes.len() <= 128 && bytes.iter().all(|b| b.is_ascii_graphic())
+}
+
+/// `TraceLayer`で使うリクエストのspan。
+/// `user_id`は認証した時点で`record_user_id`により埋める。
+pub fn request_span<B>(req: &Request<B>) -> Span {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.as_str())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        uri = %req.uri(),
+        user_id = tracing::field::Empty,
+    )
+}
+
+/// 現在のリクエストのspanにユーザIDを記録する
+pub fn record_user_id(user_id: impl Into<u64>) {
+    Span::current().record("user_id", &user_id.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, extract::Extension, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn test_app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|Extension(RequestId(id)): Extension<RequestId>| async move { id }),
+            )
+            .layer(RequestIdLayer)
+    }
+
+    async fn call(request_id: Option<&str>) -> (String, String) {
+        let mut builder = Request::builder().uri("/");
+        if let Some(request_id) = request_id {
+            builder = builder.header(X_REQUEST_ID, request_id);
+        }
+        let response = test_app()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let header = response.headers()[X_REQUEST_ID]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn generate_request_id() {
+        let (header, body) = call(None).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(header, body);
+    }
+
+    #[tokio::test]
+    async fn propagate_request_id() {
+        let (header, body) = call(Some("abc-123")).await;
+        assert_eq!(header, "abc-123");
+        assert_eq!(body, "abc-123");
+    }
+
+    #[tokio::test]
+    async fn replace_invalid_request_id() {
+        let (header, _) = call(Some("has space")).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+
+        let (header, _) = call(Some(&"a".repeat(129))).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+    }
+}
@@ -2,91 +2,238 @@ mod controller;
 mod domain;
 mod infra;
 mod settings;
+mod state;
 
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{AddExtensionLayer, Router};
 use dotenv::dotenv;
-use openidconnect::core::{CoreClient, CoreProviderMetadata};
-use openidconnect::reqwest::async_http_client;
-use openidconnect::{ClientId, ClientSecret, IssuerUrl, RedirectUrl};
 use sqlx::postgres::PgPool;
+use tokio::sync::Notify;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
-use self::controller::{book::book_app, user::user_app};
+use self::controller::{
+    book::book_app,
+    goal::goal_app,
+    health::{health_app, start_draining},
+    metrics::metrics_app,
+    record::record_app,
+    shelf::shelf_app,
+    stats::stats_app,
+    user::user_app,
+    well_known::well_known_app,
+};
+use self::infra::{
+    id_provider::discover_client,
+    keys::AccessTokenKeys,
+    metrics::MetricsLayer,
+    migration,
+    request_id::{request_span, RequestIdLayer},
+};
 use self::settings::Settings;
+use self::state::AppState;
 
 #[tokio::main]
 async fn main() {
     // .envファイル読み込み
     dotenv().ok();
 
+    // 設定変数の初期化
+    let settings = envy::from_env::<Settings>().expect(
+        "initialization error: failed in constructing app's settings from environment variables",
+    );
+
     let trace_level = if std::env::var("DEBUG").is_ok() {
         tracing::Level::DEBUG
     } else {
         tracing::Level::INFO
     };
-    tracing_subscriber::fmt().with_max_level(trace_level).init();
+    let subscriber = tracing_subscriber::fmt().with_max_level(trace_level);
+    if settings.log_format == "json" {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
 
     tracing::info!("initialization start");
 
-    // 設定変数の初期化
-    let settings = envy::from_env::<Settings>().expect(
-        "initialization error: failed in constructing app's settings from environment variables",
+    // サブコマンドの実行
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {}
+        ["migrate", command] => {
+            migrate_command(&settings, command).await;
+            return;
+        }
+        _ => {
+            eprintln!("usage: book-record [migrate {{up|down|status}}]");
+            std::process::exit(2);
+        }
+    }
+    match settings.validate() {
+        Ok(warnings) => {
+            for warning in warnings {
+                tracing::warn!("insecure setting for development: {}", warning);
+            }
+        }
+        Err(report) => panic!("initialization error: {}", report),
+    }
+
+    // access tokenの署名鍵の読み込み
+    let keys = Arc::new(
+        AccessTokenKeys::from_settings(&settings).unwrap_or_else(|err| {
+            panic!(
+                "initialization error: failed in loading access token keys: {}",
+                err
+            )
+        }),
     );
 
     // repository層の外部アクセス先の初期化
     let pg_pool = PgPool::connect(&settings.database_url)
         .await
         .expect("initialization error: connecting Postgres server failed");
+    if settings.auto_migrate {
+        migration::up(&pg_pool)
+            .await
+            .unwrap_or_else(|err| panic!("initialization error: migration failed: {}", err));
+        tracing::info!("migrations are up to date");
+    }
     let redis_cli = redis::Client::open(settings.redis_url.to_owned())
         .expect("initialization error: connecting Redis server failed");
 
     // IdPの設定初期化
-    let provider_metadata = CoreProviderMetadata::discover_async(
-        IssuerUrl::new(settings.id_provider_url.to_owned())
-            .expect("initialization error: failed in parsing the URL of IdP"),
-        async_http_client,
-    )
-    .await
-    .expect("initialization error: failed in discovering the IdP's metadata");
-
-    let id_cli = CoreClient::from_provider_metadata(
-        provider_metadata,
-        ClientId::new(settings.id_provider_client_id.to_owned()),
-        Some(ClientSecret::new(
-            settings.id_provider_client_secret.to_owned(),
-        )),
-    )
-    .set_redirect_uri(
-        RedirectUrl::new(settings.id_provider_redirect_url.to_owned()).expect(
-            "initialization error: failed in parsing the redirect URL which be passed to IdP",
-        ),
-    );
+    let id_cli = discover_client(&settings)
+        .await
+        .unwrap_or_else(|err| panic!("initialization error: {}", err));
 
     let addr = SocketAddr::from(([0, 0, 0, 0], settings.port));
+    let drain_timeout = Duration::from_secs(settings.drain_timeout);
+    let pre_stop_delay = Duration::from_secs(settings.pre_stop_delay);
+    let state = AppState::new(settings, keys, pg_pool.clone(), redis_cli, id_cli);
+    let draining = state.draining.clone();
 
     // axumのアプリケーション構築
-    let app = Router::new().nest(
-        "/v1",
-        Router::new()
-            .merge(book_app())
-            .merge(user_app())
-            .layer(AddExtensionLayer::new(settings))
-            .layer(AddExtensionLayer::new(id_cli))
-            .layer(AddExtensionLayer::new(pg_pool))
-            .layer(AddExtensionLayer::new(redis_cli))
-            .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::permissive()),
-    );
+    let app = Router::new()
+        .nest(
+            "/v1",
+            Router::new()
+                .merge(book_app())
+                .merge(record_app())
+                .merge(shelf_app())
+                .merge(stats_app())
+                .merge(goal_app())
+                .merge(user_app())
+                .layer(AddExtensionLayer::new(state.clone()))
+                .layer(MetricsLayer(state.metrics.clone()))
+                .layer(TraceLayer::new_for_http().make_span_with(request_span))
+                .layer(RequestIdLayer)
+                .layer(CorsLayer::permissive()),
+        )
+        .merge(health_app().layer(AddExtensionLayer::new(state.clone())))
+        .merge(metrics_app().layer(AddExtensionLayer::new(state.clone())))
+        .merge(
+            well_known_app()
+                .layer(AddExtensionLayer::new(state))
+                .layer(TraceLayer::new_for_http().make_span_with(request_span))
+                .layer(RequestIdLayer)
+                .layer(CorsLayer::permissive()),
+        );
 
     tracing::info!(
         "initialization complete; now the server is listening on {}",
         addr
     );
 
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+    // シグナルを受けたらreadinessを落とし、しばらくしてから新しい接続の受け付けをやめて、処理中のリクエストを待つ
+    let shutdown = Arc::new(Notify::new());
+    let server = axum::Server::bind(&addr)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr, _>())
+        .with_graceful_shutdown({
+            let shutdown = shutdown.clone();
+            async move {
+                start_draining(shutdown_signal(), draining, pre_stop_delay).await;
+                shutdown.notify_one();
+                tracing::info!("stopped accepting connections; draining in-flight requests");
+            }
+        });
+
+    tokio::select! {
+        result = server => result.expect("axum server stopped unexpectedly"),
+        _ = async {
+            shutdown.notified().await;
+            tokio::time::sleep(drain_timeout).await;
+        } => {
+            tracing::warn!(
+                "drain timeout ({} secs) elapsed; dropping remaining connections",
+                drain_timeout.as_secs()
+            );
+        }
+    }
+
+    pg_pool.close().await;
+    tracing::info!("shutdown complete");
+}
+
+/// SIGINTかSIGTERMを待つ
+async fn shutdown_signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed in installing the SIGINT handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed in installing the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => {},
+        _ = terminate => {},
+    }
+}
+
+/// `book-record migrate {up|down|status}`
+async fn migrate_command(settings: &Settings, command: &str) {
+    let pg_pool = PgPool::connect(&settings.database_url)
         .await
-        .expect("initialization error: axum server couldn't start");
+        .expect("connecting Postgres server failed");
+
+    match command {
+        "up" => {
+            migration::up(&pg_pool)
+                .await
+                .unwrap_or_else(|err| panic!("migration failed: {}", err));
+            println!("all migrations are applied");
+        }
+        "down" => {
+            match migration::down(&pg_pool)
+                .await
+                .unwrap_or_else(|err| panic!("migration failed: {}", err))
+            {
+                Some(version) => println!("reverted {}", version),
+                None => println!("no migration to revert"),
+            }
+        }
+        "status" => {
+            for status in migration::status(&pg_pool)
+                .await
+                .unwrap_or_else(|err| panic!("migration failed: {}", err))
+            {
+                println!("{}", status);
+            }
+        }
+        _ => {
+            eprintln!("usage: book-record migrate {{up|down|status}}");
+            std::process::exit(2);
+        }
+    }
 }
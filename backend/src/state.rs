@@ -0,0 +1,215 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequest, RequestParts},
+};
+use openidconnect::core::CoreClient;
+use redis::Client as RedisClient;
+use sqlx::postgres::PgPool;
+
+use crate::domain::entity::AxumError;
+use crate::domain::repo_if::{
+    book::BookRepository, goal::GoalRepository, record::RecordRepository, shelf::ShelfRepository,
+    stats::StatsRepository, user::UserRepository,
+};
+use crate::infra::health::{IdProviderProbe, PostgresProbe, Probe, RedisProbe};
+use crate::infra::keys::AccessTokenKeys;
+use crate::infra::metrics::Metrics;
+#[cfg(test)]
+use crate::infra::repo::memory::{
+    book::InMemoryBookRepository, goal::InMemoryGoalRepository, record::InMemoryRecordRepository,
+    shelf::InMemoryShelfRepository, stats::InMemoryStatsRepository, user::InMemoryUserRepository,
+};
+use crate::infra::repo::{
+    book::BookRepositoryImpl, goal::GoalRepositoryImpl, record::RecordRepositoryImpl,
+    shelf::ShelfRepositoryImpl, stats::StatsRepositoryImpl, user::UserRepositoryImpl,
+};
+use crate::settings::Settings;
+
+/// アプリケーション全体で共有する状態。
+/// Extensionとして渡し、serviceはここからrepositoryを受け取って組み立てる。
+#[derive(Clone)]
+pub struct AppState {
+    pub settings: Settings,
+    pub keys: Arc<AccessTokenKeys>,
+    pub book_repository: Arc<dyn BookRepository>,
+    pub record_repository: Arc<dyn RecordRepository>,
+    pub shelf_repository: Arc<dyn ShelfRepository>,
+    pub stats_repository: Arc<dyn StatsRepository>,
+    pub goal_repository: Arc<dyn GoalRepository>,
+    pub user_repository: Arc<dyn UserRepository>,
+    /// `/readyz`で確認する依存先
+    pub probes: Vec<Arc<dyn Probe>>,
+    pub metrics: Arc<Metrics>,
+    /// 終了処理中であり、新しいリクエストを受けるべきでない
+    pub draining: Arc<AtomicBool>,
+}
+
+impl AppState {
+    /// Postgres, Redis, IdPを使う本番用の状態
+    pub fn new(
+        settings: Settings,
+        keys: Arc<AccessTokenKeys>,
+        pg_pool: PgPool,
+        redis_cli: RedisClient,
+        id_cli: CoreClient,
+    ) -> Self {
+        let mut probes: Vec<Arc<dyn Probe>> = vec![
+            Arc::new(PostgresProbe(pg_pool.clone())),
+            Arc::new(RedisProbe(redis_cli.clone())),
+        ];
+        if settings.readiness_check_id_provider {
+            probes.push(Arc::new(IdProviderProbe(settings.clone())));
+        }
+
+        let metrics = Arc::new(Metrics::new().with_pg_pool(pg_pool.clone()));
+
+        Self {
+            book_repository: Arc::new(BookRepositoryImpl::new(pg_pool.clone())),
+            record_repository: Arc::new(RecordRepositoryImpl::new(pg_pool.clone())),
+            shelf_repository: Arc::new(ShelfRepositoryImpl::new(pg_pool.clone())),
+            stats_repository: Arc::new(StatsRepositoryImpl::new(pg_pool.clone())),
+            goal_repository: Arc::new(GoalRepositoryImpl::new(pg_pool.clone())),
+            user_repository: Arc::new(UserRepositoryImpl::new(
+                settings.clone(),
+                pg_pool,
+                redis_cli,
+                id_cli,
+            )),
+            settings,
+            keys,
+            probes,
+            metrics,
+            draining: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// 外部サービスに依存しない、メモリ上のrepositoryを使う状態。
+    /// ユーザのrepositoryはIdPの代わりに認可コードを発行できるよう、具体型で返す。
+    #[cfg(test)]
+    pub fn in_memory(settings: Settings) -> (Self, Arc<InMemoryUserRepository>) {
+        let keys = Arc::new(
+            AccessTokenKeys::from_secret(&settings.access_secret)
+                .expect("the access token secret must be base64-encoded"),
+        );
+        let user_repository = Arc::new(InMemoryUserRepository::new(settings.clone()));
+        let record_repository = Arc::new(InMemoryRecordRepository::default());
+        let shelf_repository = Arc::new(InMemoryShelfRepository::default());
+
+        let state = Self {
+            settings,
+            keys,
+            book_repository: Arc::new(InMemoryBookRepository::default()),
+            record_repository: record_repository.clone(),
+            shelf_repository: shelf_repository.clone(),
+            stats_repository: Arc::new(InMemoryStatsRepository::new(
+                record_repository,
+                shelf_repository,
+            )),
+            goal_repository: Arc::new(InMemoryGoalRepository::default()),
+            user_repository: user_repository.clone(),
+            probes: vec![],
+            metrics: Arc::new(Metrics::new()),
+            draining: Arc::new(AtomicBool::new(false)),
+        };
+        (state, user_repository)
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for AppState
+where
+    B: Send,
+{
+    type Rejection = AxumError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Extension(state) = Extension::<AppState>::from_request(req)
+            .await
+            .map_err(|_| AxumError::OtherError("AppState extension error".to_string()))?;
+        Ok(state)
+    }
+}
+
+/// コントローラのテストで共通に使う、ユーザとリクエストの準備
+#[cfg(test)]
+pub mod testing {
+    use axum::{
+        body::Body,
+        http::{self, Method, Request, StatusCode},
+        Router,
+    };
+    use chrono::Utc;
+    use jsonwebtoken::encode;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use super::AppState;
+    use crate::domain::entity::user::{AccessTokenClaims, UserEntityForCreation};
+    use crate::domain::repo_if::user::UserRepository;
+    use crate::settings::Settings;
+
+    /// メモリ上の状態に2人のユーザ(alice, bob)を登録し、それぞれのaccess tokenを返す
+    pub async fn in_memory_with_users(settings: Settings) -> (AppState, Vec<String>) {
+        let (state, user_repository) = AppState::in_memory(settings);
+
+        let mut access_tokens = vec![];
+        for name in ["alice", "bob"] {
+            let user_id = user_repository
+                .create_user(
+                    name.to_string(),
+                    UserEntityForCreation {
+                        username: name.to_string(),
+                    },
+                )
+                .await
+                .unwrap();
+            let claims = AccessTokenClaims::new(
+                state.settings.access_iss.to_owned(),
+                user_id,
+                (Utc::now().timestamp() + 60) as usize,
+            );
+            access_tokens
+                .push(encode(&state.keys.header(), &claims, state.keys.encoding_key()).unwrap());
+        }
+        (state, access_tokens)
+    }
+
+    pub fn json_request(
+        method: Method,
+        uri: &str,
+        access_token: &str,
+        body: Value,
+    ) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header(
+                http::header::AUTHORIZATION,
+                format!("Bearer {}", access_token),
+            )
+            .body(Body::from(serde_json::to_vec(&body).unwrap()))
+            .unwrap()
+    }
+
+    /// リクエストを送り、ステータスとJSONの本文を返す。本文がJSONでなければ`Value::Null`になる
+    pub async fn call(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        access_token: &str,
+        body: Value,
+    ) -> (StatusCode, Value) {
+        let response = app
+            .to_owned()
+            .oneshot(json_request(method, uri, access_token, body))
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+}
@@ -9,6 +9,9 @@ use crate::atom::common::{ButtonSecondary, ModalButtonSecondary};
 pub struct Book {
     pub id: usize,
     pub title: String,
+    /// 検索結果として表示するときの、タイトル中の一致した範囲（文字単位）
+    #[serde(default)]
+    pub title_highlights: Vec<(usize, usize)>,
 }
 
 #[derive(Clone, Default, PartialEq, Serialize)]
@@ -63,7 +66,7 @@ pub fn books_list(
 
             html! {
                 <>
-                    <BookListItem title={book.title.clone()} on_click={on_book_click} on_edit_click={on_edit_click} on_delete_click={on_delete_click} delete_target={delete_target.clone()}/>
+                    <BookListItem title={book.title.clone()} highlights={book.title_highlights.clone()} on_click={on_book_click} on_edit_click={on_edit_click} on_delete_click={on_delete_click} delete_target={delete_target.clone()}/>
                 </>
             }
         })
@@ -79,19 +82,43 @@ pub fn books_list(
 #[derive(Properties, PartialEq)]
 pub struct BookItemProps {
     pub title: String,
+    #[prop_or_default]
+    pub highlights: Vec<(usize, usize)>,
     pub on_click: Callback<MouseEvent>,
     pub on_edit_click: Callback<MouseEvent>,
     pub on_delete_click: Callback<MouseEvent>,
     pub delete_target: String,
 }
 
+/// `spans`の範囲を`<mark>`で囲んで表示する
+fn highlighted(text: &str, spans: &[(usize, usize)]) -> Html {
+    let chars: Vec<char> = text.chars().collect();
+    let mut nodes = vec![];
+    let mut last = 0;
+    for &(start, end) in spans {
+        let (start, end) = (start.min(chars.len()), end.min(chars.len()));
+        if start < last {
+            continue;
+        }
+        let before: String = chars[last..start].iter().collect();
+        let matched: String = chars[start..end].iter().collect();
+        nodes.push(html! { { before } });
+        nodes.push(html! { <mark>{ matched }</mark> });
+        last = end;
+    }
+    let rest: String = chars[last..].iter().collect();
+    nodes.push(html! { { rest } });
+
+    nodes.into_iter().collect()
+}
+
 #[function_component(BookListItem)]
 pub fn book_list_item(book: &BookItemProps) -> Html {
     html! {
         <div class="col">
             <div class="card book-list-item" onclick={book.on_click.clone()}>
                 <div class="card-body">
-                    <h5 class="card-title mb-3">{ book.title.to_owned() }</h5>
+                    <h5 class="card-title mb-3">{ highlighted(&book.title, &book.highlights) }</h5>
                     <ButtonSecondary message={"編集".to_string()} on_click={book.on_edit_click.clone()} additional_class={vec!["me-2".to_string()]}/>
                     <ModalButtonSecondary message={"削除".to_string()} on_click={book.on_delete_click.clone()} modal_target={book.delete_target.clone()}/>
                 </div>
@@ -100,6 +127,54 @@ pub fn book_list_item(book: &BookItemProps) -> Html {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct SearchBookFormProps {
+    /// 検索文字列。空のときは検索をやめて一覧に戻す
+    pub on_search: Callback<String>,
+}
+
+#[function_component(SearchBookForm)]
+pub fn search_book_form(SearchBookFormProps { on_search }: &SearchBookFormProps) -> Html {
+    let query_ref = use_node_ref();
+    let on_submit = {
+        let query_ref = query_ref.clone();
+        let on_search = on_search.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            e.stop_propagation();
+
+            if let Some(query_input) = query_ref.cast::<HtmlInputElement>() {
+                on_search.emit(query_input.value().trim().to_string());
+            }
+        })
+    };
+    let on_clear = {
+        let query_ref = query_ref.clone();
+        let on_search = on_search.clone();
+        Callback::from(move |e: MouseEvent| {
+            e.prevent_default();
+            e.stop_propagation();
+
+            if let Some(query_input) = query_ref.cast::<HtmlInputElement>() {
+                query_input.set_value("");
+            }
+            on_search.emit(String::new());
+        })
+    };
+
+    html! {
+        <form class="row g-2 mb-3">
+            <div class="col">
+                <input ref={query_ref} type="search" class="form-control" placeholder="タイトル・著者名・ISBNで検索" />
+            </div>
+            <div class="col-auto">
+                <button type="submit" class="btn btn-primary me-2" onclick={on_submit}>{ "検索" }</button>
+                <ButtonSecondary message={"クリア".to_string()} on_click={on_clear} />
+            </div>
+        </form>
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Properties)]
 pub struct CreateBookFormProps {
     pub on_submit: Callback<BookForSend>,
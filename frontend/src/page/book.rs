@@ -3,8 +3,10 @@ use serde::Deserialize;
 use yew::prelude::*;
 use yew_router::prelude::*;
 
-use crate::atom::common::SectionTitle;
-use crate::organism::book::{Book, BookForSend, BooksList, CreateBookForm, EditBookForm};
+use crate::atom::common::{ButtonSecondary, SectionTitle};
+use crate::organism::book::{
+    Book, BookForSend, BooksList, CreateBookForm, EditBookForm, SearchBookForm,
+};
 use crate::organism::common::{DeleteModal, Footer, Header};
 use crate::routes::Route;
 use crate::settings::Settings;
@@ -12,6 +14,23 @@ use crate::settings::Settings;
 #[derive(Deserialize)]
 struct BooksResponse {
     books: Vec<Book>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchHighlights {
+    title: Vec<(usize, usize)>,
+}
+
+#[derive(Deserialize)]
+struct SearchHit {
+    book: Book,
+    highlights: SearchHighlights,
+}
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    results: Vec<SearchHit>,
 }
 
 #[function_component(BooksListPage)]
@@ -20,9 +39,13 @@ pub fn list_books() -> Html {
     let history = use_history().expect("history API encounters a critical error");
 
     let books = use_state(|| vec![]);
+    // 次のページのカーソル。最後のページまで読み込んだらNone
+    let next = use_state(|| None);
 
+    // 最初のページから読み込み直す
     let fetch_books = {
         let settings = settings.clone();
+        let next = next.clone();
         |books: UseStateHandle<Vec<Book>>| async move {
             let response: BooksResponse =
                 Request::get(settings.base_url.join("books").unwrap().as_str())
@@ -33,6 +56,7 @@ pub fn list_books() -> Html {
                     .await
                     .unwrap();
             books.set(response.books);
+            next.set(response.next);
         }
     };
 
@@ -51,6 +75,76 @@ pub fn list_books() -> Html {
         );
     }
 
+    // 続きのページを読み込み、一覧の末尾に追加する
+    let on_more_click = {
+        let settings = settings.clone();
+        let books = books.clone();
+        let next = next.clone();
+        Callback::from(move |_| {
+            let settings = settings.clone();
+            let books = books.clone();
+            let next = next.clone();
+            if let Some(cursor) = (*next).clone() {
+                wasm_bindgen_futures::spawn_local(async move {
+                    let mut url = settings.base_url.join("books").unwrap();
+                    url.query_pairs_mut().append_pair("cursor", &cursor);
+                    let response: BooksResponse = Request::get(url.as_str())
+                        .send()
+                        .await
+                        .unwrap()
+                        .json()
+                        .await
+                        .unwrap();
+                    let mut loaded = (*books).clone();
+                    loaded.extend(response.books);
+                    books.set(loaded);
+                    next.set(response.next);
+                });
+            }
+        })
+    };
+
+    // 検索結果で一覧を置き換える。検索結果にはページの続きがない
+    let on_search = {
+        let settings = settings.clone();
+        let books = books.clone();
+        let next = next.clone();
+        let fetch_books = fetch_books.clone();
+        Callback::from(move |query: String| {
+            let settings = settings.clone();
+            let books = books.clone();
+            let next = next.clone();
+            let fetch_books = fetch_books.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if query.is_empty() {
+                    fetch_books(books).await;
+                    return;
+                }
+
+                let mut url = settings.base_url.join("books/search").unwrap();
+                url.query_pairs_mut().append_pair("q", &query);
+                let response: SearchResponse = Request::get(url.as_str())
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+                books.set(
+                    response
+                        .results
+                        .into_iter()
+                        .map(|hit| Book {
+                            title_highlights: hit.highlights.title,
+                            ..hit.book
+                        })
+                        .collect(),
+                );
+                next.set(None);
+            });
+        })
+    };
+
     let on_click = {
         let history = history.clone();
         Callback::once(move |book: Book| history.push(Route::BookDetail { id: book.id }))
@@ -119,12 +213,24 @@ pub fn list_books() -> Html {
         })
     };
 
+    let more_button = if next.is_some() {
+        html! {
+            <div class="mb-3">
+                <ButtonSecondary message={"もっと見る".to_string()} on_click={on_more_click} />
+            </div>
+        }
+    } else {
+        html! {}
+    };
+
     html! {
         <>
             <Header />
             <div class="container-lg">
                 <SectionTitle title={"登録された本の一覧".to_string()} />
+                <SearchBookForm {on_search} />
                 <BooksList books={(*books).clone()} {on_click} {on_edit_click} {on_delete_click} delete_target={format!("#{}", delete_modal_id)}/>
+                { more_button }
                 <CreateBookForm {on_submit}/>
             </div>
             <DeleteModal title={"確認".to_string()} message={delete_modal_message.clone()} id={delete_modal_id.clone()} label={"deleteModalLabel".to_string()} on_click={on_delete_confirmed} />